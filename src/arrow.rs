@@ -0,0 +1,556 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use uuid::Uuid;
+
+// what marks the end of a stream, a message of no length
+pub const END: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+
+// arrow's flatbuffer ids
+const METADATA_V5: i16 = 4;
+const SCHEMA: u8 = 1;
+const RECORD_BATCH: u8 = 3;
+const UTF8: u8 = 5;
+const TIMESTAMP: u8 = 10;
+const FIXED_SIZE_BINARY: u8 = 15;
+
+// `--output-format arrow`, an arrow ipc stream: the schema, then a record batch whenever the
+// hits are written out, so readers can take them in while the run is still going. the columns are
+// `uuid` (16 bytes, tagged as the `arrow.uuid` extension type), `name` and with `--timestamps`
+// `found_at`, seconds in utc.
+pub fn schema(timestamps: bool) -> Vec<u8> {
+    let mut fields = vec![
+        field(
+            "uuid",
+            FIXED_SIZE_BINARY,
+            vec![Slot::I32(16)],
+            vec![
+                key_value("ARROW:extension:name", "arrow.uuid"),
+                key_value("ARROW:extension:metadata", ""),
+            ],
+        ),
+        field("name", UTF8, vec![], vec![]),
+    ];
+    if timestamps {
+        fields.push(field(
+            "found_at",
+            TIMESTAMP,
+            // seconds
+            vec![Slot::I16(0), Slot::Ref(Node::Str("UTC".to_string()))],
+            vec![],
+        ));
+    }
+    // little endian
+    let schema = Node::Table(vec![Slot::I16(0), Slot::Ref(Node::Tables(fields))]);
+    message(SCHEMA, schema, &[])
+}
+
+fn field(name: &str, type_id: u8, type_slots: Vec<Slot>, metadata: Vec<Node>) -> Node {
+    Node::Table(vec![
+        Slot::Ref(Node::Str(name.to_string())),
+        // not nullable
+        Slot::U8(0),
+        Slot::U8(type_id),
+        Slot::Ref(Node::Table(type_slots)),
+        // no dictionary
+        Slot::Absent,
+        Slot::Ref(Node::Tables(vec![])),
+        Slot::Ref(Node::Tables(metadata)),
+    ])
+}
+
+fn key_value(key: &str, value: &str) -> Node {
+    Node::Table(vec![
+        Slot::Ref(Node::Str(key.to_string())),
+        Slot::Ref(Node::Str(value.to_string())),
+    ])
+}
+
+// a record batch of hits, with when each was found if the schema has the column for it
+pub fn batch(hits: &[(Uuid, String, u64)], timestamps: bool) -> Vec<u8> {
+    let mut body = Body::default();
+    let rows = i64::try_from(hits.len()).unwrap_or(i64::MAX);
+    // every column has no nulls, so an empty validity buffer
+    body.buffer(&[]);
+    body.buffer(
+        &hits
+            .iter()
+            .flat_map(|(uuid, ..)| *uuid.as_bytes())
+            .collect::<Vec<_>>(),
+    );
+    body.buffer(&[]);
+    let mut offsets = vec![0_i32];
+    let mut names = vec![];
+    for (_, name, _) in hits {
+        names.extend(name.as_bytes());
+        offsets.push(i32::try_from(names.len()).unwrap_or(i32::MAX));
+    }
+    body.buffer(
+        &offsets
+            .iter()
+            .flat_map(|o| o.to_le_bytes())
+            .collect::<Vec<_>>(),
+    );
+    body.buffer(&names);
+    let mut nodes = vec![(rows, 0), (rows, 0)];
+    if timestamps {
+        nodes.push((rows, 0));
+        body.buffer(&[]);
+        body.buffer(
+            &hits
+                .iter()
+                .flat_map(|(.., at)| at.cast_signed().to_le_bytes())
+                .collect::<Vec<_>>(),
+        );
+    }
+    let batch = Node::Table(vec![
+        Slot::I64(rows),
+        Slot::Ref(Node::Pairs(nodes)),
+        Slot::Ref(Node::Pairs(body.buffers)),
+    ]);
+    message(RECORD_BATCH, batch, &body.data)
+}
+
+#[derive(Default)]
+struct Body {
+    data: Vec<u8>,
+    // offset and length of every buffer in `data`
+    buffers: Vec<(i64, i64)>,
+}
+
+impl Body {
+    fn buffer(&mut self, data: &[u8]) {
+        let offset = i64::try_from(self.data.len()).unwrap_or(i64::MAX);
+        let len = i64::try_from(data.len()).unwrap_or(i64::MAX);
+        self.buffers.push((offset, len));
+        self.data.extend(data);
+        pad(&mut self.data, 8);
+    }
+}
+
+// a message with its framing: the continuation marker, the metadata length and the body after it
+fn message(header_type: u8, header: Node, body: &[u8]) -> Vec<u8> {
+    let metadata = Builder::finish(&Node::Table(vec![
+        Slot::I16(METADATA_V5),
+        Slot::U8(header_type),
+        Slot::Ref(header),
+        Slot::I64(i64::try_from(body.len()).unwrap_or(i64::MAX)),
+    ]));
+    let mut out = Vec::with_capacity(8 + metadata.len() + body.len());
+    out.extend([0xff; 4]);
+    out.extend(
+        u32::try_from(metadata.len())
+            .unwrap_or(u32::MAX)
+            .to_le_bytes(),
+    );
+    out.extend(metadata);
+    out.extend(body);
+    out
+}
+
+// gets an existing stream ready for more batches. the end marker goes, and so does a message cut
+// off by a crash. returns how many bytes were cut and whether the file is empty, so it still
+// needs the schema.
+pub fn reopen(path: &str, schema: &[u8]) -> io::Result<(u64, bool)> {
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok((0, true));
+    }
+    let mut first = vec![0; schema.len()];
+    file.seek(SeekFrom::Start(0))?;
+    if file.read_exact(&mut first).is_err() || first != schema {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an arrow output written by uuidump with these --timestamps",
+        ));
+    }
+    let mut keep = schema.len() as u64;
+    // a run that finished left the end marker, that's not cut off by anything
+    let mut ended = false;
+    loop {
+        let mut prefix = [0; 8];
+        if file.read_exact(&mut prefix).is_err() {
+            break;
+        }
+        if prefix == END {
+            ended = true;
+            break;
+        }
+        let size = u32::from_le_bytes(prefix[4..].try_into().expect("4 bytes"));
+        let mut metadata = vec![0; size as usize];
+        if file.read_exact(&mut metadata).is_err() {
+            break;
+        }
+        let end = keep + 8 + u64::from(size) + body_len(&metadata);
+        if end > len {
+            break;
+        }
+        keep = end;
+        file.seek(SeekFrom::Start(keep))?;
+    }
+    file.set_len(keep)?;
+    Ok((len - keep - if ended { 8 } else { 0 }, false))
+}
+
+// the `bodyLength` of a message's metadata, field 3 of its root table
+fn body_len(metadata: &[u8]) -> u64 {
+    let read = |at: usize, n: usize| metadata.get(at..at + n);
+    let u32_at = |at| read(at, 4).map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")));
+    let u16_at = |at| read(at, 2).map(|b| u16::from_le_bytes(b.try_into().expect("2 bytes")));
+    let len = (|| {
+        let table = u32_at(0)? as usize;
+        let vtable = table.checked_sub(u32_at(table)? as usize)?;
+        let field = u16_at(vtable + 4 + 2 * 3).filter(|field| *field != 0)?;
+        let b = read(table + usize::from(field), 8)?;
+        Some(u64::from_le_bytes(b.try_into().expect("8 bytes")))
+    })();
+    len.unwrap_or(0)
+}
+
+fn pad(buf: &mut Vec<u8>, align: usize) {
+    buf.resize(buf.len().next_multiple_of(align), 0);
+}
+
+// just enough of flatbuffers for arrow's messages. everything is laid out front to back, each
+// table or vector before what it points to, since offsets only go forward.
+enum Node {
+    // slot `i` is field `i`
+    Table(Vec<Slot>),
+    Str(String),
+    Tables(Vec<Self>),
+    // structs of two longs, `FieldNode` and `Buffer` are all arrow needs
+    Pairs(Vec<(i64, i64)>),
+}
+
+enum Slot {
+    Absent,
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Ref(Node),
+}
+
+struct Builder(Vec<u8>);
+
+impl Builder {
+    fn finish(root: &Node) -> Vec<u8> {
+        let mut b = Self(vec![0; 4]);
+        let at = b.node(root);
+        b.point(0, at);
+        pad(&mut b.0, 8);
+        b.0
+    }
+
+    // fills in the offset at `from` to point at `to`
+    fn point(&mut self, from: usize, to: usize) {
+        let offset = u32::try_from(to - from).expect("flatbuffer too big");
+        self.0[from..from + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    fn put_u16(&mut self, at: usize, v: usize) {
+        let v = u16::try_from(v).expect("table too big");
+        self.0[at..at + 2].copy_from_slice(&v.to_le_bytes());
+    }
+
+    fn node(&mut self, node: &Node) -> usize {
+        match node {
+            Node::Table(slots) => self.table(slots),
+            Node::Str(s) => {
+                pad(&mut self.0, 4);
+                let at = self.0.len();
+                self.0
+                    .extend(u32::try_from(s.len()).unwrap_or(u32::MAX).to_le_bytes());
+                self.0.extend(s.as_bytes());
+                self.0.push(0);
+                at
+            }
+            Node::Tables(items) => {
+                pad(&mut self.0, 4);
+                let at = self.0.len();
+                self.0
+                    .extend(u32::try_from(items.len()).unwrap_or(u32::MAX).to_le_bytes());
+                let first = self.0.len();
+                self.0.resize(first + 4 * items.len(), 0);
+                for (i, item) in items.iter().enumerate() {
+                    let to = self.node(item);
+                    self.point(first + 4 * i, to);
+                }
+                at
+            }
+            Node::Pairs(pairs) => {
+                // the longs after the length have to be aligned
+                while !(self.0.len() + 4).is_multiple_of(8) {
+                    self.0.push(0);
+                }
+                let at = self.0.len();
+                self.0
+                    .extend(u32::try_from(pairs.len()).unwrap_or(u32::MAX).to_le_bytes());
+                for (a, b) in pairs {
+                    self.0.extend(a.to_le_bytes());
+                    self.0.extend(b.to_le_bytes());
+                }
+                at
+            }
+        }
+    }
+
+    // the vtable goes first, the offsets of the fields in it are filled in as they're written
+    fn table(&mut self, slots: &[Slot]) -> usize {
+        pad(&mut self.0, 2);
+        let vtable = self.0.len();
+        self.0.resize(vtable + 4 + 2 * slots.len(), 0);
+        self.put_u16(vtable, 4 + 2 * slots.len());
+        pad(&mut self.0, 4);
+        let table = self.0.len();
+        self.0.extend(
+            i32::try_from(table - vtable)
+                .expect("table too big")
+                .to_le_bytes(),
+        );
+        let mut refs = vec![];
+        for (i, slot) in slots.iter().enumerate() {
+            let bytes = match slot {
+                Slot::Absent => continue,
+                Slot::U8(v) => vec![*v],
+                Slot::I16(v) => v.to_le_bytes().to_vec(),
+                Slot::I32(v) => v.to_le_bytes().to_vec(),
+                Slot::I64(v) => v.to_le_bytes().to_vec(),
+                Slot::Ref(_) => vec![0; 4],
+            };
+            pad(&mut self.0, bytes.len());
+            let at = self.0.len();
+            self.0.extend(bytes);
+            self.put_u16(vtable + 4 + 2 * i, at - table);
+            if let Slot::Ref(node) = slot {
+                refs.push((at, node));
+            }
+        }
+        self.put_u16(vtable + 2, self.0.len() - table);
+        for (at, node) in refs {
+            let to = self.node(node);
+            self.point(at, to);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTCH: Uuid = Uuid::from_u128(0x069a_79f4_44e9_4726_a5be_fca9_0e38_aaf5);
+
+    // reads flatbuffers the way arrow's readers do, without trusting how `Builder` lays them out
+    #[derive(Clone, Copy)]
+    struct Table<'a> {
+        buf: &'a [u8],
+        at: usize,
+    }
+
+    fn u32_at(buf: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(buf[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    fn i64_at(buf: &[u8], at: usize) -> i64 {
+        i64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+    }
+
+    impl<'a> Table<'a> {
+        fn root(buf: &'a [u8]) -> Self {
+            Self {
+                buf,
+                at: u32_at(buf, 0),
+            }
+        }
+
+        fn field(self, i: usize) -> Option<usize> {
+            let soffset = i32::from_le_bytes(self.buf[self.at..self.at + 4].try_into().unwrap());
+            let vtable = self.at.checked_add_signed(-soffset as isize).unwrap();
+            let len = u16::from_le_bytes(self.buf[vtable..vtable + 2].try_into().unwrap());
+            let slot = vtable + 4 + 2 * i;
+            if slot + 2 > vtable + usize::from(len) {
+                return None;
+            }
+            let offset = u16::from_le_bytes(self.buf[slot..slot + 2].try_into().unwrap());
+            (offset != 0).then(|| self.at + usize::from(offset))
+        }
+
+        fn u8(self, i: usize) -> u8 {
+            self.field(i).map_or(0, |at| self.buf[at])
+        }
+
+        fn i16(self, i: usize) -> i16 {
+            self.field(i).map_or(0, |at| {
+                i16::from_le_bytes(self.buf[at..at + 2].try_into().unwrap())
+            })
+        }
+
+        fn i32(self, i: usize) -> i32 {
+            self.field(i).map_or(0, |at| {
+                i32::from_le_bytes(self.buf[at..at + 4].try_into().unwrap())
+            })
+        }
+
+        fn i64(self, i: usize) -> i64 {
+            self.field(i).map_or(0, |at| i64_at(self.buf, at))
+        }
+
+        fn target(self, i: usize) -> usize {
+            let at = self.field(i).unwrap();
+            at + u32_at(self.buf, at)
+        }
+
+        fn table(self, i: usize) -> Self {
+            Self {
+                buf: self.buf,
+                at: self.target(i),
+            }
+        }
+
+        fn str(self, i: usize) -> &'a str {
+            let at = self.target(i);
+            let len = u32_at(self.buf, at);
+            assert_eq!(self.buf[at + 4 + len], 0, "strings end in a nul");
+            std::str::from_utf8(&self.buf[at + 4..at + 4 + len]).unwrap()
+        }
+
+        fn tables(self, i: usize) -> Vec<Self> {
+            let at = self.target(i);
+            (0..u32_at(self.buf, at))
+                .map(|n| {
+                    let item = at + 4 + 4 * n;
+                    Self {
+                        buf: self.buf,
+                        at: item + u32_at(self.buf, item),
+                    }
+                })
+                .collect()
+        }
+
+        fn pairs(self, i: usize) -> Vec<(i64, i64)> {
+            let at = self.target(i) + 4;
+            assert_eq!(at % 8, 0, "structs of longs are aligned");
+            (0..u32_at(self.buf, at - 4))
+                .map(|n| {
+                    (
+                        i64_at(self.buf, at + 16 * n),
+                        i64_at(self.buf, at + 16 * n + 8),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    // splits a message off the front of `stream`: its metadata's root table and its body
+    fn message(stream: &[u8]) -> (Table<'_>, &[u8], &[u8]) {
+        assert_eq!(stream[..4], [0xff; 4], "continuation marker");
+        let len = u32_at(stream, 4);
+        assert_eq!(len % 8, 0, "metadata is padded to 8 bytes");
+        let metadata = &stream[8..8 + len];
+        let root = Table::root(metadata);
+        assert_eq!(root.i16(0), METADATA_V5);
+        let body_len = usize::try_from(root.i64(3)).unwrap();
+        assert_eq!(body_len % 8, 0, "bodies are padded to 8 bytes");
+        let body = &stream[8 + len..8 + len + body_len];
+        (root, body, &stream[8 + len + body_len..])
+    }
+
+    #[test]
+    fn schema_describes_the_columns() {
+        let stream = schema(true);
+        let (root, body, rest) = message(&stream);
+        assert!(body.is_empty() && rest.is_empty());
+        assert_eq!(root.u8(1), SCHEMA);
+        let described = root.table(2);
+        assert_eq!(described.i16(0), 0, "little endian");
+        let fields = described.tables(1);
+        let names = fields.iter().map(|f| f.str(0)).collect::<Vec<_>>();
+        assert_eq!(names, ["uuid", "name", "found_at"]);
+        let types = fields.iter().map(|f| f.u8(2)).collect::<Vec<_>>();
+        assert_eq!(types, [FIXED_SIZE_BINARY, UTF8, TIMESTAMP]);
+        assert!(fields.iter().all(|f| f.u8(1) == 0), "nothing is nullable");
+
+        assert_eq!(fields[0].table(3).i32(0), 16, "uuids are 16 bytes");
+        let metadata = fields[0].tables(6);
+        assert_eq!(
+            metadata
+                .iter()
+                .map(|kv| (kv.str(0), kv.str(1)))
+                .collect::<Vec<_>>(),
+            [
+                ("ARROW:extension:name", "arrow.uuid"),
+                ("ARROW:extension:metadata", "")
+            ]
+        );
+        let timestamp = fields[2].table(3);
+        assert_eq!((timestamp.i16(0), timestamp.str(1)), (0, "UTC"));
+
+        let stream = schema(false);
+        let (root, ..) = message(&stream);
+        assert_eq!(root.table(2).tables(1).len(), 2);
+    }
+
+    #[test]
+    fn batches_lay_out_the_columns_in_their_body() {
+        let hits = [
+            (NOTCH, "Notch".to_string(), 1_700_000_000),
+            (Uuid::nil(), "jeb_".to_string(), 1_700_000_001),
+        ];
+        let stream = batch(&hits, true);
+        let (root, body, rest) = message(&stream);
+        assert!(rest.is_empty());
+        assert_eq!(root.u8(1), RECORD_BATCH);
+        let batch = root.table(2);
+        assert_eq!(batch.i64(0), 2);
+        assert_eq!(batch.pairs(1), [(2, 0); 3]);
+        // validity, uuids, validity, offsets, names, validity, seconds, all 8 byte aligned
+        assert_eq!(
+            batch.pairs(2),
+            [
+                (0, 0),
+                (0, 32),
+                (32, 0),
+                (32, 12),
+                (48, 9),
+                (64, 0),
+                (64, 16)
+            ]
+        );
+        let mut expected = NOTCH.as_bytes().to_vec();
+        expected.extend([0; 16]);
+        expected.extend([0, 0, 0, 0, 5, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend(b"Notchjeb_\0\0\0\0\0\0\0");
+        expected.extend(1_700_000_000_i64.to_le_bytes());
+        expected.extend(1_700_000_001_i64.to_le_bytes());
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn streams_are_resumed_after_their_last_complete_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.arrow");
+        let path = path.to_str().unwrap();
+        let schema = schema(false);
+        let batch = batch(&[(NOTCH, "Notch".to_string(), 0)], false);
+        let whole = [schema.as_slice(), &batch].concat();
+
+        std::fs::write(path, [whole.as_slice(), &END].concat()).unwrap();
+        assert_eq!(reopen(path, &schema).unwrap(), (0, false));
+        assert_eq!(std::fs::read(path).unwrap(), whole);
+
+        std::fs::write(path, [whole.as_slice(), &batch[..batch.len() - 3]].concat()).unwrap();
+        assert_eq!(
+            reopen(path, &schema).unwrap(),
+            (batch.len() as u64 - 3, false)
+        );
+        assert_eq!(std::fs::read(path).unwrap(), whole);
+
+        std::fs::write(path, "").unwrap();
+        assert_eq!(reopen(path, &schema).unwrap(), (0, true));
+        std::fs::write(path, batch).unwrap();
+        assert!(reopen(path, &schema).is_err());
+    }
+}
@@ -0,0 +1,605 @@
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::dns::Resolver;
+use crate::limits;
+use crate::proxy;
+use crate::recording::{self, Kind, Replay};
+use crate::retry::{ErrorClass, RequestError, count_error};
+use crate::term::clear_line;
+use crate::throttle::{MAX_COOLDOWN, Pacer};
+use crate::{
+    Cli, MOWOJANG, Mode, REQ_COUNTER, THROTTLE, UUID_ALL_COUNTER, latency, random_seed, tor, trace,
+};
+
+// where names get resolved. `fixture:<path>` serves answers from a json file in the same shape
+// the api answers with (`[{"id": ..., "name": ...}, ...]`) instead of the network, so runs can
+// be reproduced without touching any mirror. `--mode offline-uuid` doesn't resolve anything and
+// answers every name with its offline-mode uuid. `--replay` answers from a `--record`ing.
+#[derive(Clone)]
+pub enum Backend {
+    Http {
+        client: reqwest::Client,
+        url: Arc<str>,
+        // `--proxy-rps`, shared by the clients going through the same proxy, and the backend's
+        // `rps=`, shared by every client of the backend
+        pacers: Vec<Arc<Pacer>>,
+    },
+    Fixture(Arc<Fixture>),
+    Offline,
+    Replay(Arc<Replay>),
+}
+
+// a `--backend`, `url` with optional `,concurrency=<n>` and `,rps=<n>` after it
+#[derive(Debug, Clone)]
+pub struct BackendSpec {
+    pub url: String,
+    pub concurrency: Option<usize>,
+    pub rps: Option<f64>,
+}
+
+impl FromStr for BackendSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut spec = Self {
+            url: s.to_string(),
+            concurrency: None,
+            rps: None,
+        };
+        // options are taken off the end, so a comma inside the url stays part of it
+        while let Some((rest, option)) = spec.url.rsplit_once(',') {
+            let (rest, option) = (rest.to_string(), option.to_string());
+            match option.split_once('=') {
+                Some(("concurrency", n)) => {
+                    let n = n
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("expected a positive concurrency, got `{n}`"))?;
+                    spec.concurrency = Some(n);
+                }
+                Some(("rps", n)) => {
+                    let n = n
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|n| *n > 0.0 && n.is_finite())
+                        .ok_or_else(|| format!("expected a positive rps, got `{n}`"))?;
+                    spec.rps = Some(n);
+                }
+                _ => break,
+            }
+            spec.url = rest;
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for BackendSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.url)?;
+        if let Some(n) = self.concurrency {
+            write!(f, ",concurrency={n}")?;
+        }
+        if let Some(n) = self.rps {
+            write!(f, ",rps={n}")?;
+        }
+        Ok(())
+    }
+}
+
+// the backends every client of a `--backend` can be reached through, and how many request
+// threads that `--backend` gets to itself. the ones without a concurrency share `--threads`.
+pub struct Group {
+    pub backends: Vec<Backend>,
+    pub concurrency: Option<usize>,
+}
+
+// the `--backend`s given, or the public instance
+pub fn specs(args: &Cli) -> Vec<BackendSpec> {
+    if args.backends.is_empty() {
+        vec![BackendSpec {
+            url: MOWOJANG.to_string(),
+            concurrency: None,
+            rps: None,
+        }]
+    } else {
+        args.backends.clone()
+    }
+}
+
+// request threads across every backend, `--threads` plus the ones backends have to themselves
+pub fn total_threads(args: &Cli) -> usize {
+    let specs = specs(args);
+    let dedicated = specs
+        .iter()
+        .filter_map(|spec| spec.concurrency)
+        .sum::<usize>();
+    let shared = if specs.iter().any(|spec| spec.concurrency.is_none()) {
+        args.threads.max(1)
+    } else {
+        0
+    };
+    shared + dedicated
+}
+
+pub struct Fixture {
+    // lowercase name -> profile as it appears in the fixture
+    profiles: HashMap<String, serde_json::Value>,
+}
+
+impl Fixture {
+    async fn load(path: &str) -> eyre::Result<Self> {
+        let json: serde_json::Value =
+            serde_json::from_str(&tokio::fs::read_to_string(path).await?)?;
+        let Some(pls) = json.as_array() else {
+            eyre::bail!("fixture {path} is not a json array of profiles");
+        };
+        let mut profiles = HashMap::new();
+        for pl in pls {
+            let Some(name) = pl["name"].as_str() else {
+                eyre::bail!("fixture {path} contains a profile without a name: {pl}");
+            };
+            profiles.insert(name.to_ascii_lowercase(), pl.clone());
+        }
+        Ok(Self { profiles })
+    }
+
+    fn lookup(&self, names: &[String]) -> Vec<serde_json::Value> {
+        names
+            .iter()
+            .filter_map(|name| self.profiles.get(&name.to_ascii_lowercase()).cloned())
+            .collect()
+    }
+}
+
+pub async fn build_backends(args: &Cli) -> eyre::Result<Vec<Group>> {
+    if args.mode == Mode::OfflineUuid {
+        return Ok(vec![Group {
+            backends: vec![Backend::Offline],
+            concurrency: None,
+        }]);
+    }
+    if let Some(dir) = &args.replay {
+        let replay = Replay::load(dir).await?;
+        info!("loaded {} recorded requests", replay.len());
+        return Ok(vec![Group {
+            backends: vec![Backend::Replay(Arc::new(replay))],
+            concurrency: None,
+        }]);
+    }
+
+    let ca_cert = if let Some(path) = &args.ca_cert {
+        let pem = tokio::fs::read(path).await?;
+        Some(reqwest::Certificate::from_pem(&pem)?)
+    } else {
+        None
+    };
+    let local_addrs = if args.local_addrs.is_empty() {
+        vec![None]
+    } else {
+        args.local_addrs.iter().copied().map(Some).collect()
+    };
+    let routes = routes(args).await?;
+    let resolver = Arc::new(Resolver::new(&args.resolve, args.dns_ttl));
+    // a proxy's rate is shared by every backend sent through it
+    let route_pacers = routes
+        .iter()
+        .map(|_| args.proxy_rps.map(|rps| Arc::new(Pacer::new(rps))))
+        .collect::<Vec<_>>();
+
+    let mut groups = vec![];
+    for spec in specs(args) {
+        if let Some(path) = spec.url.strip_prefix("fixture:") {
+            let fixture = Fixture::load(path).await?;
+            info!("loaded {} profiles from fixture", fixture.profiles.len());
+            groups.push(Group {
+                backends: vec![Backend::Fixture(Arc::new(fixture))],
+                concurrency: spec.concurrency,
+            });
+            continue;
+        }
+        let url: Arc<str> = spec.url.as_str().into();
+        let pacer = spec.rps.map(|rps| Arc::new(Pacer::new(rps)));
+        // every proxy from every local address
+        let mut backends = vec![];
+        for (route, route_pacer) in routes.iter().zip(&route_pacers) {
+            for addr in &local_addrs {
+                backends.push(Backend::Http {
+                    client: build_client(args, ca_cert.as_ref(), *addr, route.clone(), &resolver)?,
+                    url: url.clone(),
+                    pacers: route_pacer.iter().chain(&pacer).cloned().collect(),
+                });
+            }
+        }
+        groups.push(Group {
+            backends,
+            concurrency: spec.concurrency,
+        });
+    }
+    Ok(groups)
+}
+
+// the proxies (or tor circuits) requests go through, a single `None` for going direct
+async fn routes(args: &Cli) -> eyre::Result<Vec<Option<reqwest::Proxy>>> {
+    let mut proxies = args.proxies.clone();
+    if let Some(path) = &args.proxy_list {
+        let listed = proxy::load_list(path).await?;
+        info!("loaded {} proxies", listed.len());
+        proxies.extend(listed);
+    }
+    let mut routes = vec![];
+    if args.tor {
+        if !proxies.is_empty() {
+            eyre::bail!(
+                "--tor already decides where requests go, it can't be combined with proxies"
+            );
+        }
+        let tor = tor::proxies(
+            &args.tor_socks,
+            &args.tor_control,
+            args.tor_password.as_deref(),
+            total_threads(args),
+            random_seed(),
+        )
+        .await?;
+        info!("sending requests through tor on {} circuits", tor.len());
+        routes.extend(tor.into_iter().map(Some));
+    }
+    for proxy in &proxies {
+        let proxy = proxy
+            .clone()
+            .or_auth(args.proxy_user.as_deref(), args.proxy_password.as_deref());
+        routes.push(Some(proxy.to_reqwest().await?));
+    }
+    if let Some(rps) = args.proxy_rps {
+        if routes.is_empty() {
+            eyre::bail!("--proxy-rps needs proxies from --proxy, --proxy-list or --tor");
+        }
+        if !(rps > 0.0 && rps.is_finite()) {
+            eyre::bail!("--proxy-rps has to be more than 0, got {rps}");
+        }
+    }
+    if routes.is_empty() {
+        routes.push(None);
+    }
+    Ok(routes)
+}
+
+fn build_client(
+    args: &Cli,
+    ca_cert: Option<&reqwest::Certificate>,
+    local_addr: Option<IpAddr>,
+    proxy: Option<reqwest::Proxy>,
+    resolver: &Arc<Resolver>,
+) -> eyre::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .dns_resolver(resolver.clone())
+        .local_address(local_addr)
+        .timeout(args.timeout)
+        .danger_accept_invalid_certs(args.insecure);
+    // idle connections past the limit would only hold on to file descriptors
+    if let Some(max) = limits::max_connections() {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(ca_cert) = ca_cert {
+        builder = builder.add_root_certificate(ca_cert.clone());
+    }
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy);
+    }
+    Ok(builder.build()?)
+}
+
+// makes sure the endpoint is reachable and answers with the shape `request()` expects
+pub async fn health_check(backend: &Backend) -> eyre::Result<()> {
+    let Backend::Http { client, url, .. } = backend else {
+        // fixtures and recordings are validated when they're loaded, offline needs no endpoint
+        return Ok(());
+    };
+    let res = client
+        .post(&**url)
+        .header("content-type", "application/json")
+        .body(json!(["Notch"]).to_string())
+        .send()
+        .await
+        .map_err(|e| eyre::eyre!("endpoint {url} is unreachable: {e}"))?;
+    if !res.status().is_success() {
+        eyre::bail!(
+            "endpoint {url} returned {} for a health check",
+            res.status()
+        );
+    }
+    let json: serde_json::Value = res
+        .json()
+        .await
+        .map_err(|e| eyre::eyre!("endpoint {url} returned invalid json: {e}"))?;
+    let found = json.as_array().is_some_and(|pls| {
+        pls.iter().any(|pl| {
+            pl["id"]
+                .as_str()
+                .is_some_and(|id| Uuid::from_str(id).is_ok())
+                && pl["name"]
+                    .as_str()
+                    .is_some_and(|name| name.eq_ignore_ascii_case("notch"))
+        })
+    });
+    if !found {
+        eyre::bail!("endpoint {url} returned an unexpected response for Notch: {json}");
+    }
+    Ok(())
+}
+
+// one entry of the api's answer, anything else in it is ignored
+#[derive(Deserialize)]
+struct Profile {
+    id: Uuid,
+    name: String,
+}
+
+pub async fn request(
+    backend: &Backend,
+    names: Vec<String>,
+) -> Result<Vec<(Uuid, String)>, RequestError> {
+    assert!(names.len() <= 10, "too many uuids :(");
+
+    let _connection = connection(backend).await;
+    let started = Instant::now();
+    let res = fetch(backend, &names).await;
+    latency::record(started.elapsed(), names.len(), status(&res));
+    recording::record(Kind::Batch, &names, res.as_ref());
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            count_error(e.class);
+            THROTTLE.record(false);
+            return Err(e);
+        }
+    };
+    THROTTLE.record(true);
+    REQ_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let mut pls = vec![];
+    for pl in res {
+        // a broken entry shouldn't cost the rest of the batch
+        match Profile::deserialize(&pl) {
+            Ok(Profile { id, name }) => {
+                UUID_ALL_COUNTER.fetch_add(1, Ordering::SeqCst);
+                pls.push((id, name));
+            }
+            Err(e) => eprintln!("{}warn: skipping malformed profile {pl}: {e}", clear_line()),
+        }
+    }
+    Ok(pls)
+}
+
+// waits for the proxy's turn under `--proxy-rps` and the backend's under its `rps=`, then for a
+// slot under `--max-connections`. only requests that go over the network need any of them.
+async fn connection(backend: &Backend) -> Option<tokio::sync::SemaphorePermit<'static>> {
+    let Backend::Http { pacers, .. } = backend else {
+        return None;
+    };
+    for pacer in pacers {
+        pacer.wait().await;
+    }
+    limits::connection().await
+}
+
+fn status<T>(res: &Result<T, RequestError>) -> &'static str {
+    res.as_ref().map_or_else(|e| e.class.name(), |_| "ok")
+}
+
+// the raw answer to a batch, without counting it anywhere
+pub async fn fetch(
+    backend: &Backend,
+    names: &[String],
+) -> Result<Vec<serde_json::Value>, RequestError> {
+    match backend {
+        Backend::Http { client, url, .. } => request_http(client, url, names, Instant::now()).await,
+        Backend::Fixture(fixture) => Ok(fixture.lookup(names)),
+        Backend::Offline => Ok(names.iter().map(|name| offline_profile(name)).collect()),
+        Backend::Replay(replay) => {
+            let response = replay.answer(Kind::Batch, names)?;
+            serde_json::from_value(response).map_err(|e| {
+                RequestError::new(
+                    ErrorClass::InvalidJson,
+                    format!("recorded response is not an array of profiles: {e}"),
+                )
+            })
+        }
+    }
+}
+
+// looks up a single name with `GET <url>/<name>`, for when the batch it was in keeps failing.
+// none if the name doesn't exist.
+pub async fn request_single(
+    backend: &Backend,
+    name: &str,
+) -> Result<Option<(Uuid, String)>, RequestError> {
+    let _connection = connection(backend).await;
+    let started = Instant::now();
+    let res = match backend {
+        Backend::Http { client, url, .. } => get_single(client, url, name).await,
+        Backend::Fixture(fixture) => Ok(fixture.lookup(&[name.to_string()]).pop()),
+        Backend::Offline => Ok(Some(offline_profile(name))),
+        Backend::Replay(replay) => replay
+            .answer(Kind::Single, &[name.to_string()])
+            .map(|pl| (!pl.is_null()).then_some(pl)),
+    };
+    latency::record(started.elapsed(), 1, status(&res));
+    recording::record(Kind::Single, &[name.to_string()], res.as_ref());
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            count_error(e.class);
+            THROTTLE.record(false);
+            return Err(e);
+        }
+    };
+    THROTTLE.record(true);
+    REQ_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let Some(pl) = res else {
+        return Ok(None);
+    };
+    let Profile { id, name } = Profile::deserialize(&pl).map_err(|e| {
+        count_error(ErrorClass::InvalidJson);
+        RequestError::new(
+            ErrorClass::InvalidJson,
+            format!("malformed profile {pl}: {e}"),
+        )
+    })?;
+    UUID_ALL_COUNTER.fetch_add(1, Ordering::SeqCst);
+    Ok(Some((id, name)))
+}
+
+// the uuid an offline-mode server assigns a player: an md5 name-based (v3) uuid of
+// `OfflinePlayer:<name>`, like java's `UUID.nameUUIDFromBytes`
+pub fn offline_uuid(name: &str) -> Uuid {
+    let digest = Md5::digest(format!("OfflinePlayer:{name}"));
+    uuid::Builder::from_md5_bytes(digest.into()).into_uuid()
+}
+
+fn offline_profile(name: &str) -> serde_json::Value {
+    json!({ "id": offline_uuid(name), "name": name })
+}
+
+async fn get_single(
+    client: &reqwest::Client,
+    url: &str,
+    name: &str,
+) -> Result<Option<serde_json::Value>, RequestError> {
+    let url = format!("{}/{name}", url.trim_end_matches('/'));
+    let res = client.get(&url).send().await.map_err(|e| {
+        RequestError::new(ErrorClass::from_reqwest(&e), format!("request failed: {e}"))
+    })?;
+    let status = res.status();
+    if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(RequestError::new(
+            ErrorClass::from_status(status),
+            format!("endpoint returned {status}"),
+        ));
+    }
+    let text = res.text().await.map_err(|e| {
+        RequestError::new(
+            ErrorClass::from_reqwest(&e),
+            format!("failed to read response: {e}"),
+        )
+    })?;
+    let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+        RequestError::new(
+            ErrorClass::InvalidJson,
+            format!("failed to parse response json: {e}"),
+        )
+    })?;
+    Ok((!json.is_null()).then_some(json))
+}
+
+async fn request_http(
+    client: &reqwest::Client,
+    url: &str,
+    names: &[String],
+    started: Instant,
+) -> Result<Vec<serde_json::Value>, RequestError> {
+    let body = json!(names).to_string();
+    let traced = trace::sample();
+    let result = client
+        .post(url)
+        .header("content-type", "application/json")
+        .body(body.clone())
+        .send()
+        .await;
+    let res = match result {
+        Ok(res) => res,
+        Err(e) => {
+            if traced {
+                trace::log(url, &body, "failed", started.elapsed(), &e.to_string());
+            }
+            return Err(RequestError::new(
+                ErrorClass::from_reqwest(&e),
+                format!("request failed: {e}"),
+            ));
+        }
+    };
+
+    let status = res.status();
+    let retry_after = retry_after(res.headers());
+    let text = res.text().await;
+    if traced {
+        let text = text
+            .as_ref()
+            .map_or_else(|e| format!("<{e}>"), Clone::clone);
+        trace::log(url, &body, &status.to_string(), started.elapsed(), &text);
+    }
+    match text {
+        _ if !status.is_success() => Err(RequestError {
+            retry_after,
+            ..RequestError::new(
+                ErrorClass::from_status(status),
+                format!("endpoint returned {status}"),
+            )
+        }),
+        Ok(text) => serde_json::from_str(&text).map_err(|e| {
+            RequestError::new(
+                ErrorClass::InvalidJson,
+                format!("failed to parse response json: {e}"),
+            )
+        }),
+        Err(e) => Err(RequestError::new(
+            ErrorClass::from_reqwest(&e),
+            format!("failed to read response: {e}"),
+        )),
+    }
+}
+
+// how long the server asked to wait, at most as long as the breaker would. a large or broken
+// `Retry-After` shouldn't park a thread for good
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let secs: u64 = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs).min(MAX_COOLDOWN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuids_depend_on_case() {
+        assert_eq!(
+            offline_uuid("Notch").to_string(),
+            "b50ad385-829d-3141-a216-7e7d7539ba7f"
+        );
+        assert_eq!(
+            offline_uuid("notch").to_string(),
+            "42653081-a90e-3475-b3d6-3550cdb43f8e"
+        );
+    }
+
+    #[test]
+    fn retry_after_is_capped() {
+        let headers = |value: &str| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(retry_after(&headers("30")), Some(Duration::from_secs(30)));
+        assert_eq!(retry_after(&headers("9999999999")), Some(MAX_COOLDOWN));
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&reqwest::header::HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,121 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+// how many bits per expected candidate are worth spending, more barely lowers the false positives
+const BITS_PER_ITEM: u64 = 20;
+// a false positive is a unique candidate that's never queried, the filter is full once they get
+// more likely than this
+pub const MAX_FALSE_POSITIVES: f64 = 0.001;
+
+// the candidates handed out so far, in a fixed amount of memory. a candidate that was seen is
+// always recognized, one that wasn't is taken for a duplicate with the false positive rate. once
+// it's `full`, it has to be dropped rather than skip more unique candidates.
+#[cfg_attr(test, derive(Clone))]
+pub struct Bloom {
+    bits: Vec<u64>,
+    len: u64,
+    hashes: u32,
+    inserted: u64,
+    // how many fit before the false positive rate passes `MAX_FALSE_POSITIVES`
+    capacity: u64,
+}
+
+impl Bloom {
+    // sized for `expected` candidates if that fits in `max_bytes`, otherwise as big as allowed.
+    // without an expectation it takes all of `max_bytes`.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn new(expected: Option<u64>, max_bytes: u64) -> Self {
+        let max_bits = (max_bytes * 8).max(64);
+        let len = expected.map_or(max_bits, |n| (n * BITS_PER_ITEM).clamp(64, max_bits));
+        let per_item = expected.map_or(BITS_PER_ITEM as f64, |n| len as f64 / n.max(1) as f64);
+        // too little memory for the expected candidates: as many as fit at the rate that's allowed
+        let hashes = (per_item * std::f64::consts::LN_2)
+            .max(-MAX_FALSE_POSITIVES.log2())
+            .round()
+            .clamp(1.0, 16.0) as u32;
+        // the rate solved for n: p = (1 - e^(-kn/m))^k
+        let k = f64::from(hashes);
+        let capacity = -(len as f64 / k) * (1.0 - MAX_FALSE_POSITIVES.powf(1.0 / k)).ln();
+        Self {
+            bits: vec![0; len.div_ceil(64) as usize],
+            len,
+            hashes,
+            inserted: 0,
+            capacity: capacity as u64,
+        }
+    }
+
+    pub const fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    // whether a unique candidate is now too likely to be taken for a duplicate
+    pub const fn full(&self) -> bool {
+        self.inserted >= self.capacity
+    }
+
+    // the chance a new candidate is taken for a duplicate once `n` were inserted
+    #[cfg(test)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn false_positive_rate(&self, n: u64) -> f64 {
+        let k = f64::from(self.hashes);
+        (1.0 - (-k * n as f64 / self.len as f64).exp()).powf(k)
+    }
+
+    // whether `item` was inserted before, inserting it if it wasn't
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn insert(&mut self, item: &str) -> bool {
+        // two hashes combined into as many as needed (kirsch-mitzenmacher). the default hasher
+        // has fixed keys, so a run sees the same duplicates every time.
+        let mut h1 = DefaultHasher::new();
+        item.hash(&mut h1);
+        let h1 = h1.finish();
+        let mut h2 = DefaultHasher::new();
+        (item, 0x9e37_79b9_7f4a_7c15_u64).hash(&mut h2);
+        let h2 = h2.finish() | 1;
+
+        let mut seen = true;
+        for i in 0..u64::from(self.hashes) {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.len;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            seen &= self.bits[word] & mask != 0;
+            self.bits[word] |= mask;
+        }
+        self.inserted += u64::from(!seen);
+        seen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_up_before_skipping_too_many_unique_candidates() {
+        let mut bloom = Bloom::new(Some(1_000_000), 4096);
+        assert!(bloom.capacity() < 1_000_000);
+        let mut n = 0;
+        while !bloom.full() {
+            bloom.insert(&format!("filled{n}"));
+            n += 1;
+        }
+        assert!(n <= bloom.capacity() + 100);
+        // what would be skipped if the filter was kept past this point
+        let probes = 20_000;
+        let skipped = (0..probes)
+            .filter(|i| bloom.clone().insert(&format!("unique{i}")))
+            .count();
+        let skipped = f64::from(u32::try_from(skipped).unwrap());
+        assert!(skipped / f64::from(probes) < MAX_FALSE_POSITIVES * 3.0);
+    }
+
+    #[test]
+    fn sized_for_the_expected_candidates_when_there_is_room() {
+        let bloom = Bloom::new(Some(10_000), 64 << 20);
+        assert!(bloom.capacity() >= 10_000);
+        assert!(bloom.false_positive_rate(10_000) < MAX_FALSE_POSITIVES);
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::term::clear_line;
+
+// name -> result cache persisted across runs as two append-only logs. hits go to `hits.tsv`
+// as `timestamp\tqueried name\tuuid\tname`, names that weren't found go to `misses.tsv` as
+// `timestamp\tqueried name`. both have their own ttl, since misses are most of the answers and
+// are usually fine to trust for longer.
+pub struct Cache {
+    hits: HashMap<String, (u64, Uuid, String)>,
+    misses: HashMap<String, u64>,
+    hit_ttl: Duration,
+    miss_ttl: Duration,
+    hit_log: Mutex<tokio::fs::File>,
+    miss_log: Mutex<tokio::fs::File>,
+}
+
+#[derive(Clone)]
+pub enum Answer {
+    Found(Uuid, String),
+    NotFound,
+}
+
+impl Cache {
+    pub async fn open(dir: &str, hit_ttl: Duration, miss_ttl: Duration) -> eyre::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+        let hits_path = Path::new(dir).join("hits.tsv");
+        let misses_path = Path::new(dir).join("misses.tsv");
+
+        let mut hits = HashMap::new();
+        for line in read_log(&hits_path).await?.lines() {
+            // a crash can leave a half-written last line, just skip it
+            if let Some((queried, at, uuid, name)) = parse_hit(line) {
+                hits.insert(queried, (at, uuid, name));
+            }
+        }
+        let mut misses = HashMap::new();
+        for line in read_log(&misses_path).await?.lines() {
+            if let Some((at, queried)) = line.split_once('\t')
+                && let Ok(at) = at.parse()
+            {
+                misses.insert(queried.to_string(), at);
+            }
+        }
+
+        Ok(Self {
+            hits,
+            misses,
+            hit_ttl,
+            miss_ttl,
+            hit_log: Mutex::new(open_log(&hits_path).await?),
+            miss_log: Mutex::new(open_log(&misses_path).await?),
+        })
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.len()
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.len()
+    }
+
+    // the answer if the name was answered within its ttl
+    pub fn get(&self, name: &str) -> Option<Answer> {
+        let name = name.to_ascii_lowercase();
+        let now = now();
+        if let Some((at, uuid, canonical)) = self.hits.get(&name)
+            && now.saturating_sub(*at) <= self.hit_ttl.as_secs()
+        {
+            return Some(Answer::Found(*uuid, canonical.clone()));
+        }
+        if let Some(at) = self.misses.get(&name)
+            && now.saturating_sub(*at) <= self.miss_ttl.as_secs()
+        {
+            return Some(Answer::NotFound);
+        }
+        None
+    }
+
+    // records the answer to a request, every queried name that isn't in `found` is a miss
+    pub async fn record(&self, queried: &[String], found: &[(Uuid, String)]) {
+        let at = now();
+        let mut hit_lines = String::new();
+        let mut miss_lines = String::new();
+        for name in queried {
+            let queried = name.to_ascii_lowercase();
+            if let Some((uuid, canonical)) =
+                found.iter().find(|(_, n)| n.eq_ignore_ascii_case(name))
+            {
+                let _ = writeln!(hit_lines, "{at}\t{queried}\t{}\t{canonical}", uuid.simple());
+            } else {
+                let _ = writeln!(miss_lines, "{at}\t{queried}");
+            }
+        }
+        for (log, lines) in [(&self.hit_log, hit_lines), (&self.miss_log, miss_lines)] {
+            if lines.is_empty() {
+                continue;
+            }
+            if let Err(e) = log.lock().await.write_all(lines.as_bytes()).await {
+                eprintln!("{}warn: failed to write to the cache: {e}", clear_line());
+            }
+        }
+    }
+}
+
+async fn read_log(path: &Path) -> eyre::Result<String> {
+    if tokio::fs::try_exists(path).await? {
+        Ok(tokio::fs::read_to_string(path).await?)
+    } else {
+        Ok(String::new())
+    }
+}
+
+async fn open_log(path: &Path) -> eyre::Result<tokio::fs::File> {
+    Ok(tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?)
+}
+
+fn parse_hit(line: &str) -> Option<(String, u64, Uuid, String)> {
+    let mut parts = line.split('\t');
+    let at = parts.next()?.parse().ok()?;
+    let queried = parts.next()?.to_string();
+    let uuid = Uuid::from_str(parts.next()?).ok()?;
+    let name = parts.next()?.to_string();
+    Some((queried, at, uuid, name))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a cache with a hit and a miss from an hour ago
+    async fn cache_with(hit_ttl: Duration, miss_ttl: Duration) -> Cache {
+        let dir = tempfile::tempdir().unwrap();
+        let at = now() - 3600;
+        let uuid = Uuid::from_u128(1).simple();
+        tokio::fs::write(
+            dir.path().join("hits.tsv"),
+            format!("{at}\tnotch\t{uuid}\tNotch\n{at}\tgr"),
+        )
+        .await
+        .unwrap();
+        tokio::fs::write(dir.path().join("misses.tsv"), format!("{at}\tjeb_\n"))
+            .await
+            .unwrap();
+        Cache::open(dir.path().to_str().unwrap(), hit_ttl, miss_ttl)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn answers_expire_with_their_own_ttl() {
+        let cache = cache_with(Duration::from_hours(2), Duration::from_mins(1)).await;
+        // the half-written last line is left out
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        assert!(matches!(
+            cache.get("Notch"),
+            Some(Answer::Found(uuid, name)) if uuid == Uuid::from_u128(1) && name == "Notch"
+        ));
+        assert!(cache.get("jeb_").is_none());
+
+        let cache = cache_with(Duration::from_mins(1), Duration::from_hours(2)).await;
+        assert!(cache.get("notch").is_none());
+        assert!(matches!(cache.get("JEB_"), Some(Answer::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn recorded_answers_are_there_after_a_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let ttl = Duration::from_mins(1);
+        let cache = Cache::open(path, ttl, ttl).await.unwrap();
+        let queried = ["Notch".to_string(), "jeb_".to_string()];
+        cache
+            .record(&queried, &[(Uuid::from_u128(1), "Notch".to_string())])
+            .await;
+        for log in [&cache.hit_log, &cache.miss_log] {
+            log.lock().await.flush().await.unwrap();
+        }
+
+        let cache = Cache::open(path, ttl, ttl).await.unwrap();
+        assert!(matches!(cache.get("notch"), Some(Answer::Found(..))));
+        assert!(matches!(cache.get("jeb_"), Some(Answer::NotFound)));
+        assert!(cache.get("dinnerbone").is_none());
+    }
+}
@@ -0,0 +1,82 @@
+use serde_json::json;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::open_output;
+use crate::session;
+use crate::term::clear_line;
+
+// fetches the profile of every found uuid in the background and appends whether the player has a
+// cape (and which one) as json lines, so the session server's rate limit doesn't hold up the run
+pub struct Capes {
+    tx: UnboundedSender<(Uuid, String)>,
+    task: JoinHandle<()>,
+}
+
+impl Capes {
+    // with `only_caped` players without a cape aren't written at all
+    pub async fn start(path: &str, only_caped: bool, timeout: Duration) -> eyre::Result<Self> {
+        let out = BufWriter::new(open_output(path).await?);
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        let (tx, rx) = unbounded_channel();
+        let task = tokio::spawn(check(rx, client, only_caped, out));
+        Ok(Self { tx, task })
+    }
+
+    pub fn push(&self, uuid: Uuid, name: &str) {
+        let _ = self.tx.send((uuid, name.to_string()));
+    }
+
+    // waits for the uuids that are still queued to be looked up
+    pub async fn finish(self) {
+        drop(self.tx);
+        if !self.task.is_finished() {
+            info!("{}waiting for the remaining cape lookups", clear_line());
+        }
+        let _ = self.task.await;
+    }
+}
+
+async fn check(
+    mut rx: UnboundedReceiver<(Uuid, String)>,
+    client: reqwest::Client,
+    only_caped: bool,
+    mut out: BufWriter<tokio::fs::File>,
+) {
+    while let Some((uuid, name)) = rx.recv().await {
+        let cape = match lookup(&client, uuid).await {
+            Ok(cape) => cape,
+            Err(e) => {
+                eprintln!(
+                    "{}warn: failed to look up the cape of {uuid}: {e}",
+                    clear_line()
+                );
+                continue;
+            }
+        };
+        if only_caped && cape.is_none() {
+            continue;
+        }
+        let record = json!({ "uuid": uuid, "name": name, "cape": cape });
+        let res = async {
+            out.write_all(format!("{record}\n").as_bytes()).await?;
+            if rx.is_empty() {
+                out.flush().await?;
+            }
+            std::io::Result::Ok(())
+        };
+        if let Err(e) = res.await {
+            eprintln!("{}warn: failed to write cape record: {e}", clear_line());
+        }
+    }
+    let _ = out.flush().await;
+}
+
+// the texture url of the player's cape, none if they don't wear one or have no profile
+async fn lookup(client: &reqwest::Client, uuid: Uuid) -> eyre::Result<Option<String>> {
+    let profile = session::profile(client, uuid).await?;
+    Ok(profile.map(|profile| profile.cape()).transpose()?.flatten())
+}
@@ -0,0 +1,132 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// a utc calendar date and time
+#[derive(Debug, Clone, Copy)]
+pub struct DateTime {
+    pub year: u64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn from_unix(secs: u64) -> Self {
+        let days = secs / 86400;
+        let rem = (secs % 86400) as u32;
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days, simplified
+        // since there are no dates before the epoch
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z % 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Self {
+            year,
+            month,
+            day,
+            hour: rem / 3600,
+            minute: rem / 60 % 60,
+            second: rem % 60,
+        }
+    }
+
+    // `2024-05-17`
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    // `13-37-00`, with dashes so it can be used in file names
+    pub fn time(&self) -> String {
+        format!("{:02}-{:02}-{:02}", self.hour, self.minute, self.second)
+    }
+
+    // `2024-05-17T13:37:00Z`
+    pub fn iso(&self) -> String {
+        format!(
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date(),
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+
+    // `17 May 2024 13:37:00 +0000`, the way mail headers write dates
+    pub fn rfc2822(&self) -> String {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        format!(
+            "{} {} {} {:02}:{:02}:{:02} +0000",
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+// expands `{date}`, `{time}` and `{unix}` in a path, so scheduled runs get a fresh file
+#[allow(clippy::literal_string_with_formatting_args)]
+pub fn expand_template(template: &str) -> String {
+    let unix = now_unix();
+    let now = DateTime::from_unix(unix);
+    template
+        .replace("{date}", &now.date())
+        .replace("{time}", &now.time())
+        .replace("{unix}", &unix.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_around_leap_days() {
+        for (unix, date, time) in [
+            (0, "1970-01-01", "00-00-00"),
+            (94_694_399, "1972-12-31", "23-59-59"),
+            (951_782_400, "2000-02-29", "00-00-00"),
+            (1_709_251_199, "2024-02-29", "23-59-59"),
+            (1_709_251_200, "2024-03-01", "00-00-00"),
+            // 2100 isn't a leap year
+            (4_107_499_200, "2100-02-28", "12-00-00"),
+            (4_107_542_400, "2100-03-01", "00-00-00"),
+        ] {
+            let at = DateTime::from_unix(unix);
+            assert_eq!((at.date().as_str(), at.time().as_str()), (date, time));
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let now = DateTime::from_unix(1_715_953_020);
+        assert_eq!(now.date(), "2024-05-17");
+        assert_eq!(now.time(), "13-37-00");
+        assert_eq!(now.iso(), "2024-05-17T13:37:00Z");
+        assert_eq!(now.rfc2822(), "17 May 2024 13:37:00 +0000");
+        assert_eq!(
+            DateTime::from_unix(951_782_400).rfc2822(),
+            "29 Feb 2000 00:00:00 +0000"
+        );
+        assert_eq!(
+            DateTime::from_unix(94_694_399).rfc2822(),
+            "31 Dec 1972 23:59:59 +0000"
+        );
+    }
+}
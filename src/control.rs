@@ -0,0 +1,186 @@
+use serde_json::json;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::term::clear_line;
+use crate::{ABORTED, STOPPED, progress};
+
+// what a running scrape can be told over `--control-socket`: all request threads can be paused,
+// and the requests of all of them together held to a rate
+pub struct Control {
+    paused: AtomicBool,
+    resumed: Notify,
+    pacing: Mutex<Pacing>,
+}
+
+struct Pacing {
+    // none doesn't limit the rate
+    interval: Option<Duration>,
+    // when the next request may be made
+    next: Option<Instant>,
+}
+
+impl Control {
+    pub const fn new() -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            resumed: Notify::const_new(),
+            pacing: Mutex::new(Pacing {
+                interval: None,
+                next: None,
+            }),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.resumed.notify_waiters();
+    }
+
+    // requests per second across all threads, 0 doesn't limit them
+    fn set_rps(&self, rps: f64) {
+        let mut pacing = self.pacing.lock().unwrap();
+        pacing.interval = (rps > 0.0).then(|| Duration::from_secs_f64(1.0 / rps));
+        pacing.next = None;
+    }
+
+    fn rps(&self) -> Option<f64> {
+        let interval = self.pacing.lock().unwrap().interval;
+        interval.map(|interval| 1.0 / interval.as_secs_f64())
+    }
+
+    // waits until the run isn't paused and it's this request's turn under the rate limit
+    pub async fn wait(&self) {
+        loop {
+            let resumed = self.resumed.notified();
+            if !self.is_paused() || ABORTED.load(Ordering::SeqCst) {
+                break;
+            }
+            resumed.await;
+        }
+        let slot = {
+            let mut pacing = self.pacing.lock().unwrap();
+            let Some(interval) = pacing.interval else {
+                return;
+            };
+            let slot = pacing
+                .next
+                .map_or_else(Instant::now, |next| next.max(Instant::now()));
+            pacing.next = Some(slot + interval);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+
+    // the answer to one line sent to the socket
+    fn command(&self, line: &str, started: Instant, words: usize) -> String {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("status"), None, _) => {
+                let mut status = progress::snapshot(started, words, false);
+                status["paused"] = json!(self.is_paused());
+                status["rps"] = json!(self.rps());
+                status.to_string()
+            }
+            (Some("pause"), None, _) => {
+                self.pause();
+                eprintln!("{}paused over the control socket", clear_line());
+                "ok".to_string()
+            }
+            (Some("resume"), None, _) => {
+                self.resume();
+                eprintln!("{}resumed over the control socket", clear_line());
+                "ok".to_string()
+            }
+            (Some("set-rps"), Some(rps), None) => match rps.parse::<f64>() {
+                Ok(rps) if rps >= 0.0 && rps.is_finite() => {
+                    self.set_rps(rps);
+                    "ok".to_string()
+                }
+                _ => format!("error: expected requests per second like 20 or 0.5, got `{rps}`"),
+            },
+            (Some("stop"), None, _) => {
+                eprintln!("{}stopping, asked to over the control socket", clear_line());
+                STOPPED.store(true, Ordering::SeqCst);
+                ABORTED.store(true, Ordering::SeqCst);
+                // paused threads would never get to notice
+                self.resume();
+                "ok".to_string()
+            }
+            _ => format!(
+                "error: unknown command `{}`, expected status, pause, resume, set-rps <n> or stop",
+                line.trim()
+            ),
+        }
+    }
+}
+
+// `--control-socket`, answers one line for every line a client sends. a socket left behind by an
+// earlier run is replaced.
+#[cfg(unix)]
+pub fn serve(
+    control: &'static Control,
+    path: &str,
+    started: Instant,
+    words: usize,
+) -> eyre::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!(
+                        "{}warn: failed to accept a control connection: {e}",
+                        clear_line()
+                    );
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let answer = control.command(&line, started, words);
+                    if write
+                        .write_all(format!("{answer}\n").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(
+    _control: &'static Control,
+    _path: &str,
+    _started: Instant,
+    _words: usize,
+) -> eyre::Result<()> {
+    eyre::bail!("--control-socket needs unix sockets, which this platform doesn't have")
+}
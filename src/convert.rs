@@ -0,0 +1,226 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::str::FromStr;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use uuid::Uuid;
+
+use crate::ConvertArgs;
+use crate::session;
+
+// the dump formats `uuidump convert` reads and writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    // a uuid per line, dashed or not when reading, dashed when writing
+    Uuid,
+    // undashed lowercase uuids, like the public dumps
+    Matdump,
+    // `uuid:name` lines, like the console output
+    UuidName,
+    // 16 bytes big-endian per uuid, like `--output-format raw`
+    Raw,
+    // `{"uuid": ..., "name": ...}` lines
+    Ndjson,
+    // `uuid,name` lines under a header
+    Csv,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uuid" => Ok(Self::Uuid),
+            "matdump" => Ok(Self::Matdump),
+            "uuid:name" => Ok(Self::UuidName),
+            "raw" => Ok(Self::Raw),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!(
+                "expected uuid, matdump, uuid:name, raw, ndjson or csv, got `{s}`"
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Record {
+    uuid: Uuid,
+    name: Option<String>,
+}
+
+impl DumpFormat {
+    const fn has_names(self) -> bool {
+        matches!(self, Self::UuidName | Self::Ndjson | Self::Csv)
+    }
+
+    // one record of a text format, none for lines that don't hold one (blank lines, the csv
+    // header) and an error for lines that should but don't. a `--timestamps` column is dropped.
+    fn parse(self, line: &str) -> Result<Option<(Uuid, Option<String>)>, ()> {
+        let line = line.split('\t').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let (uuid, name) = match self {
+            // names after a colon are fine, they just aren't kept
+            Self::Uuid | Self::Matdump => (line.split(':').next().unwrap_or_default(), None),
+            Self::UuidName => line
+                .split_once(':')
+                .map_or((line, None), |(uuid, name)| (uuid, Some(name))),
+            Self::Csv => {
+                let (uuid, name) = line
+                    .split_once(',')
+                    .map_or((line, None), |(uuid, name)| (uuid, Some(name)));
+                if uuid == "uuid" {
+                    return Ok(None);
+                }
+                (uuid, name)
+            }
+            Self::Ndjson => {
+                let record = serde_json::from_str::<Record>(line).map_err(|_| ())?;
+                return Ok(Some((record.uuid, record.name)));
+            }
+            Self::Raw => unreachable!("raw dumps aren't read by line"),
+        };
+        let uuid = Uuid::from_str(uuid).map_err(|_| ())?;
+        let name = name.filter(|name| !name.is_empty()).map(str::to_string);
+        Ok(Some((uuid, name)))
+    }
+
+    fn encode(self, uuid: Uuid, name: Option<&str>) -> Vec<u8> {
+        let name = name.unwrap_or_default();
+        match self {
+            Self::Uuid => format!("{uuid}\n").into_bytes(),
+            Self::Matdump => format!("{}\n", uuid.simple()).into_bytes(),
+            Self::UuidName => format!("{uuid}:{name}\n").into_bytes(),
+            Self::Raw => uuid.as_bytes().to_vec(),
+            Self::Ndjson => format!("{}\n", json!({ "uuid": uuid, "name": name })).into_bytes(),
+            Self::Csv => format!("{uuid},{name}\n").into_bytes(),
+        }
+    }
+}
+
+// how a conversion went, for the summary
+#[derive(Default)]
+struct Counts {
+    written: u64,
+    invalid: u64,
+    first_invalid: Option<u64>,
+    resolved: u64,
+    no_profile: u64,
+}
+
+struct Writer {
+    out: tokio::io::BufWriter<Box<dyn AsyncWrite + Unpin + Send>>,
+    format: DumpFormat,
+    client: reqwest::Client,
+    counts: Counts,
+}
+
+impl Writer {
+    async fn write(&mut self, uuid: Uuid, name: Option<String>) -> eyre::Result<()> {
+        let name = match name {
+            None if self.format.has_names() => {
+                let Some(profile) = session::profile(&self.client, uuid).await? else {
+                    self.counts.no_profile += 1;
+                    return Ok(());
+                };
+                self.counts.resolved += 1;
+                Some(profile.name)
+            }
+            name => name,
+        };
+        self.out
+            .write_all(&self.format.encode(uuid, name.as_deref()))
+            .await?;
+        self.counts.written += 1;
+        Ok(())
+    }
+}
+
+// `uuidump convert`, rewrites a dump in another format. names the input doesn't have are looked
+// up on the session server if the output needs them, uuids without a profile are left out.
+pub async fn run(args: &ConvertArgs) -> eyre::Result<()> {
+    if args.output.as_deref() == Some(args.input.as_str()) {
+        eyre::bail!("-o can't be the input, the input would be overwritten while it's read");
+    }
+    let input = tokio::fs::File::open(&args.input).await?;
+    let output: Box<dyn AsyncWrite + Unpin + Send> = match &args.output {
+        Some(path) => Box::new(tokio::fs::File::create(path).await?),
+        None => Box::new(tokio::io::stdout()),
+    };
+    let resolve = args.to.has_names() && !args.from.has_names();
+    if resolve {
+        eprintln!("{} has no names, looking them up", args.input);
+    }
+    let mut writer = Writer {
+        out: tokio::io::BufWriter::new(output),
+        format: args.to,
+        client: reqwest::Client::builder().timeout(args.timeout).build()?,
+        counts: Counts::default(),
+    };
+    if args.to == DumpFormat::Csv {
+        writer.out.write_all(b"uuid,name\n").await?;
+    }
+
+    if args.from == DumpFormat::Raw {
+        let mut input = BufReader::new(input);
+        let mut buf = [0; 16];
+        loop {
+            let filled = read_full(&mut input, &mut buf).await?;
+            if filled < buf.len() {
+                if filled > 0 {
+                    eprintln!(
+                        "warn: dropped {filled} bytes of a truncated uuid at the end of {}",
+                        args.input
+                    );
+                }
+                break;
+            }
+            writer.write(Uuid::from_bytes(buf), None).await?;
+        }
+    } else {
+        let mut lines = BufReader::new(input).lines();
+        let mut line_no = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_no += 1;
+            match args.from.parse(&line) {
+                Ok(Some((uuid, name))) => writer.write(uuid, name).await?,
+                Ok(None) => {}
+                Err(()) => {
+                    writer.counts.invalid += 1;
+                    writer.counts.first_invalid.get_or_insert(line_no);
+                }
+            }
+        }
+    }
+    writer.out.flush().await?;
+
+    let counts = writer.counts;
+    eprintln!("wrote {} records", counts.written);
+    if resolve {
+        eprintln!(
+            "looked up {} names, {} uuids have no profile any more and were left out",
+            counts.resolved, counts.no_profile
+        );
+    }
+    if let Some(line) = counts.first_invalid {
+        eprintln!(
+            "warn: skipped {} lines that aren't valid records, the first at line {line}",
+            counts.invalid
+        );
+    }
+    Ok(())
+}
+
+// fills `buf` unless the input ends first, returns how much of it was filled
+async fn read_full(input: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = input.read(&mut buf[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
@@ -0,0 +1,293 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use uuid::Uuid;
+
+use crate::term::clear_line;
+
+// requests bigger than this are refused, a batch report is a few kb at most
+const MAX_BODY: usize = 1024 * 1024;
+// longest request or header line, and most headers, the client sends a handful of short ones
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+// how long a client gets to send its request, so stalled connections don't pile up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// what every scraper of a team reported so far. only kept in memory (leaked, it lives as long as
+// the server), so a restart of the server starts over and the scrapers check everything again.
+#[derive(Default)]
+struct Seen {
+    names: HashSet<String>,
+    uuids: HashSet<Uuid>,
+}
+
+// `POST /checked`, the names of a batch that got an answer and the profiles found among them
+#[derive(Deserialize)]
+struct Checked {
+    names: Vec<String>,
+    found: Vec<Profile>,
+}
+
+// the answer to `POST /checked`
+#[derive(Deserialize)]
+struct Fresh {
+    new: Vec<Profile>,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    id: Uuid,
+    name: String,
+}
+
+// `uuidump dedup-server`, answers which names nobody checked yet and which uuids nobody found
+// yet, for scrapers running with `--dedup-server`:
+// - `POST /unchecked` with `["name", ...]` answers with the names nobody reported yet
+// - `POST /checked` with `{"names": [...], "found": [{"id": ..., "name": ...}]}` adds them and
+//   answers with `{"new": [...]}`, the found uuids nobody reported before
+// - `GET /stats` answers with `{"names": ..., "uuids": ...}`
+pub async fn serve(listen: SocketAddr) -> eyre::Result<()> {
+    let listener = TcpListener::bind(listen).await?;
+    eprintln!("dedup server listening on {listen}");
+    let seen: &'static Mutex<Seen> = Box::leak(Box::default());
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            let res = tokio::time::timeout(REQUEST_TIMEOUT, handle(stream, seen))
+                .await
+                .unwrap_or_else(|_| Err(eyre::eyre!("timed out")));
+            if let Err(e) = res {
+                eprintln!("warn: request from {peer} failed: {e}");
+            }
+        });
+    }
+}
+
+// one request per connection, clients reconnect for the next one
+async fn handle(stream: TcpStream, seen: &Mutex<Seen>) -> eyre::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let Some(request_line) = read_line(&mut stream).await? else {
+        return respond(stream.get_mut(), "414 URI Too Long", &json!({})).await;
+    };
+    let mut length = 0;
+    for i in 0.. {
+        let Some(header) = read_line(&mut stream).await?.filter(|_| i < MAX_HEADERS) else {
+            let status = "431 Request Header Fields Too Large";
+            return respond(stream.get_mut(), status, &json!({})).await;
+        };
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse()?;
+        }
+    }
+    if length > MAX_BODY {
+        return respond(stream.get_mut(), "413 Payload Too Large", &json!({})).await;
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await?;
+
+    let mut parts = request_line.split_whitespace();
+    let route = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    let (status, answer) = match route {
+        ("POST", "/unchecked") => match serde_json::from_slice::<Vec<String>>(&body) {
+            Ok(names) => {
+                let seen = seen.lock().unwrap();
+                let unchecked = names
+                    .into_iter()
+                    .filter(|name| !seen.names.contains(&name.to_ascii_lowercase()))
+                    .collect::<Vec<_>>();
+                drop(seen);
+                ("200 OK", json!(unchecked))
+            }
+            Err(e) => ("400 Bad Request", json!({ "error": e.to_string() })),
+        },
+        ("POST", "/checked") => match serde_json::from_slice::<Checked>(&body) {
+            Ok(checked) => {
+                let mut seen = seen.lock().unwrap();
+                seen.names
+                    .extend(checked.names.iter().map(|name| name.to_ascii_lowercase()));
+                let new = checked
+                    .found
+                    .into_iter()
+                    .filter(|pl| seen.uuids.insert(pl.id))
+                    .map(|pl| json!({ "id": pl.id, "name": pl.name }))
+                    .collect::<Vec<_>>();
+                drop(seen);
+                ("200 OK", json!({ "new": new }))
+            }
+            Err(e) => ("400 Bad Request", json!({ "error": e.to_string() })),
+        },
+        ("GET", "/stats") => {
+            let seen = seen.lock().unwrap();
+            let stats = json!({ "names": seen.names.len(), "uuids": seen.uuids.len() });
+            drop(seen);
+            ("200 OK", stats)
+        }
+        _ => ("404 Not Found", json!({})),
+    };
+    respond(stream.get_mut(), status, &answer).await
+}
+
+// a line of at most MAX_LINE bytes, None if it's longer
+async fn read_line(stream: &mut BufReader<TcpStream>) -> eyre::Result<Option<String>> {
+    let mut line = String::new();
+    (&mut *stream)
+        .take(MAX_LINE as u64)
+        .read_line(&mut line)
+        .await?;
+    Ok((line.ends_with('\n') || line.len() < MAX_LINE).then_some(line))
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    answer: &serde_json::Value,
+) -> eyre::Result<()> {
+    let body = answer.to_string();
+    let response = format!(
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+// `--dedup-server` client. if the server can't be reached the run carries on as if it was alone,
+// so a team member restarting the server doesn't stop everyone else.
+pub struct Dedup {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Dedup {
+    pub fn new(url: &str, timeout: Duration) -> eyre::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            url: url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    // the names of a batch nobody in the team checked yet
+    pub async fn unchecked(&self, names: &[String]) -> Vec<String> {
+        match self.post("unchecked", &json!(names)).await {
+            Ok(unchecked) => unchecked,
+            Err(e) => {
+                eprintln!(
+                    "{}warn: failed to ask {} about names: {e}",
+                    clear_line(),
+                    self.url
+                );
+                names.to_vec()
+            }
+        }
+    }
+
+    // reports what a batch was answered with, returns the found profiles nobody reported before
+    pub async fn checked(
+        &self,
+        names: &[String],
+        found: Vec<(Uuid, String)>,
+    ) -> Vec<(Uuid, String)> {
+        let report = json!({
+            "names": names,
+            "found": found.iter().map(|(id, name)| json!({ "id": id, "name": name })).collect::<Vec<_>>(),
+        });
+        match self.post::<Fresh>("checked", &report).await {
+            Ok(Fresh { new }) => new.into_iter().map(|pl| (pl.id, pl.name)).collect(),
+            Err(e) => {
+                eprintln!(
+                    "{}warn: failed to report a batch to {}: {e}",
+                    clear_line(),
+                    self.url
+                );
+                found
+            }
+        }
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Result<T> {
+        self.client
+            .post(format!("{}/{path}", self.url))
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sends `request` to a connection `handle` answers and returns the answer
+    async fn exchange(seen: &'static Mutex<Seen>, request: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle(stream, seen).await
+        });
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(&request).await.unwrap();
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).await.unwrap();
+        server.await.unwrap().unwrap();
+        answer
+    }
+
+    fn post(path: &str, body: &str) -> Vec<u8> {
+        format!(
+            "POST {path} HTTP/1.1\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .into_bytes()
+    }
+
+    #[tokio::test]
+    async fn names_are_only_unchecked_once() {
+        let seen = Box::leak(Box::default());
+        let checked = r#"{"names": ["Notch", "jeb_"], "found": [{"id": "069a79f444e94726a5befca90e38aaf5", "name": "Notch"}]}"#;
+        let answer = exchange(seen, post("/checked", checked)).await;
+        assert!(answer.ends_with(r#""name":"Notch"}]}"#), "{answer}");
+        let answer = exchange(seen, post("/checked", checked)).await;
+        assert!(answer.ends_with(r#"{"new":[]}"#), "{answer}");
+        let answer = exchange(seen, post("/unchecked", r#"["notch", "dinnerbone"]"#)).await;
+        assert!(answer.ends_with(r#"["dinnerbone"]"#), "{answer}");
+    }
+
+    #[tokio::test]
+    async fn long_lines_are_refused() {
+        let seen = Box::leak(Box::default());
+        let mut request = b"GET /stats HTTP/1.1\r\nx-padding: ".to_vec();
+        request.resize(request.len() + MAX_LINE, b'a');
+        request.extend_from_slice(b"\r\n\r\n");
+        let answer = exchange(seen, request).await;
+        assert!(answer.starts_with("HTTP/1.1 431 "), "{answer}");
+        let answer = exchange(
+            seen,
+            format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE)).into_bytes(),
+        )
+        .await;
+        assert!(answer.starts_with("HTTP/1.1 414 "), "{answer}");
+    }
+}
@@ -0,0 +1,113 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+// `--resolve host:ip`, an address to connect to for a host instead of looking it up
+#[derive(Debug, Clone)]
+pub struct HostOverride {
+    host: String,
+    addr: IpAddr,
+}
+
+impl FromStr for HostOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // host names can't have a colon in them, ipv6 addresses can
+        let (host, addr) = s
+            .split_once(':')
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| format!("expected host:ip, got `{s}`"))?;
+        let addr = addr.trim_start_matches('[').trim_end_matches(']');
+        let addr = addr
+            .parse()
+            .map_err(|_| format!("expected an ip address after the host, got `{addr}`"))?;
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            addr,
+        })
+    }
+}
+
+// looked up addresses of a host, empty until the lookup finished
+type Lookup = Arc<OnceCell<Arc<[SocketAddr]>>>;
+
+// resolves the hosts for every backend client, so thousands of threads opening connections ask
+// the system resolver once per host and `--dns-ttl` instead of once per connection. a lookup that
+// is still running is waited for rather than started again.
+pub struct Resolver {
+    overrides: HashMap<String, Arc<[SocketAddr]>>,
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, (Instant, Lookup)>>>,
+}
+
+impl Resolver {
+    pub fn new(overrides: &[HostOverride], ttl: Duration) -> Self {
+        let mut addrs = HashMap::<String, Vec<SocketAddr>>::new();
+        for o in overrides {
+            // the port is the url's, whatever is given here
+            addrs
+                .entry(o.host.clone())
+                .or_default()
+                .push(SocketAddr::new(o.addr, 0));
+        }
+        Self {
+            overrides: addrs
+                .into_iter()
+                .map(|(host, addrs)| (host, addrs.into()))
+                .collect(),
+            ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        if let Some(addrs) = self.overrides.get(&host) {
+            let addrs = addrs.clone();
+            return Box::pin(async move { Ok(addrs_of(&addrs)) });
+        }
+        let ttl = self.ttl;
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            if ttl.is_zero() {
+                return Ok(addrs_of(&lookup(&host).await?));
+            }
+            let entry = cached(&cache, &host, ttl);
+            // a failed lookup leaves the entry empty, the next connection tries again
+            let addrs = entry.get_or_try_init(|| lookup(&host)).await?;
+            Ok(addrs_of(addrs))
+        })
+    }
+}
+
+// the entry for `host`, a new empty one if there is none or it's older than `ttl`
+fn cached(cache: &Mutex<HashMap<String, (Instant, Lookup)>>, host: &str, ttl: Duration) -> Lookup {
+    let now = Instant::now();
+    let mut entries = cache.lock().unwrap();
+    let entry = match entries.get(host) {
+        Some((at, entry)) if now.duration_since(*at) < ttl => entry.clone(),
+        _ => {
+            let entry = Lookup::default();
+            entries.insert(host.to_string(), (now, entry.clone()));
+            entry
+        }
+    };
+    drop(entries);
+    entry
+}
+
+async fn lookup(host: &str) -> std::io::Result<Arc<[SocketAddr]>> {
+    Ok(tokio::net::lookup_host((host, 0)).await?.collect())
+}
+
+fn addrs_of(addrs: &Arc<[SocketAddr]>) -> Addrs {
+    let addrs = addrs.clone();
+    Box::new((0..addrs.len()).map(move |i| addrs[i]))
+}
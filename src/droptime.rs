@@ -0,0 +1,149 @@
+use serde_json::json;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::clock::now_unix;
+use crate::open_output;
+use crate::term::clear_line;
+
+// unclaimed names waiting to be checked. once it's full the request threads wait, so a slow
+// droptime service slows the run down instead of piling up names in memory.
+const QUEUE: usize = 1000;
+// how long a released name is held before anyone can claim it
+const GRACE: Duration = Duration::from_hours(37 * 24);
+
+// checks every name nobody owns against a droptime service (`--droptime-url`) in the background
+// and appends the ones that can be claimed now or soon to `--droptimes` as json lines. a name
+// that isn't mapped to a uuid can still be blocked, or be held for a while after a name change.
+pub struct Droptimes {
+    tx: Sender<String>,
+}
+
+// what the service says about a name nobody owns. how much it can tell depends on the service,
+// the status is as specific as its answer allows.
+enum Status {
+    // nobody ever had it
+    NeverRegistered,
+    // released less than 37 days ago, claimable at the unix time in it
+    Grace(u64),
+    // released and past the grace window
+    Released,
+    // claimable, but the service doesn't say whether anyone had it before
+    Available,
+}
+
+impl Status {
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::NeverRegistered => "never_registered",
+            Self::Grace(_) => "grace",
+            Self::Released => "released",
+            Self::Available => "available",
+        }
+    }
+}
+
+impl Droptimes {
+    // the checker stops once every copy of this is dropped, the handle waits for it
+    pub async fn start(
+        path: &str,
+        url: &str,
+        timeout: Duration,
+    ) -> eyre::Result<(Self, JoinHandle<()>)> {
+        if !url.contains("{name}") {
+            eyre::bail!("--droptime-url needs a `{{name}}` in it, got {url}");
+        }
+        let out = BufWriter::new(open_output(path).await?);
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        let (tx, rx) = channel(QUEUE);
+        let task = tokio::spawn(check(rx, client, url.to_string(), out));
+        Ok((Self { tx }, task))
+    }
+
+    // queues the names of a batch that came back without a profile
+    pub async fn record(&self, queried: &[String], found: &[(Uuid, String)]) {
+        for name in queried {
+            if !found.iter().any(|(_, n)| n.eq_ignore_ascii_case(name)) {
+                let _ = self.tx.send(name.clone()).await;
+            }
+        }
+    }
+}
+
+// waits for the names that are still queued to be checked
+pub async fn finish(task: JoinHandle<()>) {
+    if !task.is_finished() {
+        info!("{}waiting for the remaining droptime checks", clear_line());
+    }
+    let _ = task.await;
+}
+
+async fn check(
+    mut rx: Receiver<String>,
+    client: reqwest::Client,
+    url: String,
+    mut out: BufWriter<tokio::fs::File>,
+) {
+    while let Some(name) = rx.recv().await {
+        let status = match lookup(&client, &url, &name).await {
+            Ok(Some(status)) => status,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!(
+                    "{}warn: failed to check the droptime of {name}: {e}",
+                    clear_line()
+                );
+                continue;
+            }
+        };
+        let record = json!({
+            "name": name,
+            "status": status.name(),
+            "droptime": match status {
+                Status::Grace(droptime) => Some(droptime),
+                _ => None,
+            },
+            "checked_at": now_unix(),
+        });
+        let res = async {
+            out.write_all(format!("{record}\n").as_bytes()).await?;
+            if rx.is_empty() {
+                out.flush().await?;
+            }
+            std::io::Result::Ok(())
+        };
+        if let Err(e) = res.await {
+            eprintln!("{}warn: failed to write droptime: {e}", clear_line());
+        }
+    }
+    let _ = out.flush().await;
+}
+
+// none if the service doesn't know the name as claimable (404). the service can answer with a
+// `droptime` (unix seconds, null for names that can be claimed now), a `released_at` to derive it
+// from, and `registered: false` for names nobody ever had.
+#[allow(clippy::literal_string_with_formatting_args)]
+async fn lookup(client: &reqwest::Client, url: &str, name: &str) -> eyre::Result<Option<Status>> {
+    let res = client.get(url.replace("{name}", name)).send().await?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let json: serde_json::Value = res.error_for_status()?.json().await?;
+    let droptime =
+        match &json["droptime"] {
+            serde_json::Value::Null => None,
+            droptime => Some(droptime.as_u64().ok_or_else(|| {
+                eyre::eyre!("expected `droptime` to be unix seconds, got {json}")
+            })?),
+        };
+    let droptime = droptime.or_else(|| Some(json["released_at"].as_u64()? + GRACE.as_secs()));
+    Ok(Some(match droptime {
+        Some(droptime) if droptime > now_unix() => Status::Grace(droptime),
+        Some(_) => Status::Released,
+        None if json["registered"] == false => Status::NeverRegistered,
+        None => Status::Available,
+    }))
+}
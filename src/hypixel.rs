@@ -0,0 +1,132 @@
+use serde_json::json;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::open_output;
+use crate::term::clear_line;
+
+const PLAYER_URL: &str = "https://api.hypixel.net/v2/player";
+// how long to wait out a rate limit if the api doesn't say
+const DEFAULT_RESET: Duration = Duration::from_mins(1);
+
+// looks every found uuid up on hypixel in the background and appends what it knows about the
+// player as json lines, so a slow or rate limited api doesn't hold up the run
+pub struct Enricher {
+    tx: UnboundedSender<(Uuid, String)>,
+    task: JoinHandle<()>,
+}
+
+impl Enricher {
+    pub async fn start(path: &str, key: &str, timeout: Duration) -> eyre::Result<Self> {
+        let out = BufWriter::new(open_output(path).await?);
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        let (tx, rx) = unbounded_channel();
+        let task = tokio::spawn(enrich(rx, client, key.to_string(), out));
+        Ok(Self { tx, task })
+    }
+
+    pub fn push(&self, uuid: Uuid, name: &str) {
+        let _ = self.tx.send((uuid, name.to_string()));
+    }
+
+    // waits for the uuids that are still queued to be looked up
+    pub async fn finish(self) {
+        drop(self.tx);
+        if !self.task.is_finished() {
+            info!("{}waiting for the remaining hypixel lookups", clear_line());
+        }
+        let _ = self.task.await;
+    }
+}
+
+async fn enrich(
+    mut rx: UnboundedReceiver<(Uuid, String)>,
+    client: reqwest::Client,
+    key: String,
+    mut out: BufWriter<tokio::fs::File>,
+) {
+    while let Some((uuid, name)) = rx.recv().await {
+        let record = match lookup(&client, &key, uuid).await {
+            Ok(player) => record(uuid, &name, &player),
+            Err(e) => {
+                eprintln!(
+                    "{}warn: failed to look up {uuid} on hypixel: {e}",
+                    clear_line()
+                );
+                continue;
+            }
+        };
+        let res = async {
+            out.write_all(format!("{record}\n").as_bytes()).await?;
+            // written right away unless more are waiting, like the other outputs
+            if rx.is_empty() {
+                out.flush().await?;
+            }
+            std::io::Result::Ok(())
+        };
+        if let Err(e) = res.await {
+            eprintln!("{}warn: failed to write hypixel record: {e}", clear_line());
+        }
+    }
+    let _ = out.flush().await;
+}
+
+// the `player` object of the answer, null for players that never joined hypixel. rate limits
+// are waited out.
+async fn lookup(
+    client: &reqwest::Client,
+    key: &str,
+    uuid: Uuid,
+) -> eyre::Result<serde_json::Value> {
+    loop {
+        let res = client
+            .get(PLAYER_URL)
+            .query(&[("uuid", uuid.simple().to_string())])
+            .header("API-Key", key)
+            .send()
+            .await?;
+        // seconds until the limit resets
+        let reset = res
+            .headers()
+            .get("ratelimit-reset")
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .map_or(DEFAULT_RESET, Duration::from_secs);
+        let remaining = res
+            .headers()
+            .get("ratelimit-remaining")
+            .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+        if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            tokio::time::sleep(reset).await;
+            continue;
+        }
+        let mut json: serde_json::Value = res.error_for_status()?.json().await?;
+        if json["success"] != true {
+            eyre::bail!("hypixel answered with {json}");
+        }
+        if remaining == Some(0) {
+            tokio::time::sleep(reset).await;
+        }
+        return Ok(json["player"].take());
+    }
+}
+
+fn record(uuid: Uuid, name: &str, player: &serde_json::Value) -> serde_json::Value {
+    json!({
+        "uuid": uuid,
+        "name": name,
+        "first_login": player["firstLogin"],
+        "last_login": player["lastLogin"],
+        // players start at level 1, the experience is only there once they earned some
+        "network_level": player
+            .is_object()
+            .then(|| network_level(player["networkExp"].as_f64().unwrap_or(0.0))),
+    })
+}
+
+// hypixel's formula for the network level from the network experience
+fn network_level(exp: f64) -> f64 {
+    2.0f64.mul_add(exp, 30625.0).sqrt() / 50.0 - 2.5
+}
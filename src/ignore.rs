@@ -0,0 +1,420 @@
+use rayon::iter::Either;
+use rayon::prelude::*;
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::term::clear_line;
+
+// the uuids to ignore, as sorted, non-overlapping ranges looked up with a binary search. a full
+// uuid is a range of one, a truncated one (e.g. the 8 digit laby hashes) covers every uuid
+// starting with it, so entries of any length can be mixed. with hundreds of millions of entries
+// a hash set spends most of startup hashing and growing, sorting the raw numbers in parallel is
+// a lot quicker and takes less memory. lists too big to fit in memory at all stay in sqlite and
+// are looked up there.
+#[derive(Default)]
+pub struct Ignored {
+    ranges: Vec<(u128, u128)>,
+    dbs: Vec<Arc<IgnoreDb>>,
+}
+
+// `path`, `path:truncation` or `sqlite:path` from `-i`
+#[derive(Debug, Clone)]
+pub struct IgnoreSource {
+    pub path: String,
+    pub truncation: Option<usize>,
+    pub sqlite: bool,
+}
+
+impl FromStr for IgnoreSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, sqlite) = s.strip_prefix("sqlite:").map_or((s, false), |s| (s, true));
+        if let Some((path, truncation)) = s.rsplit_once(':')
+            && let Ok(truncation) = truncation.parse()
+        {
+            if sqlite {
+                return Err(format!(
+                    "sqlite ignore lists hold full uuids and can't be truncated, got `{s}`"
+                ));
+            }
+            return Ok(Self {
+                path: path.to_string(),
+                truncation: Some(truncation),
+                sqlite,
+            });
+        }
+        Ok(Self {
+            path: s.to_string(),
+            truncation: None,
+            sqlite,
+        })
+    }
+}
+
+// a `uuids` table with a `uuid` column, like `--sink sqlite:` writes, queried for every hit instead
+// of being loaded. the column should be indexed (it's the primary key in the sink's table), or
+// every lookup scans the whole table.
+struct IgnoreDb {
+    path: String,
+    db: Mutex<rusqlite::Connection>,
+}
+
+impl IgnoreDb {
+    fn open(path: &str) -> eyre::Result<Self> {
+        let db = rusqlite::Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| eyre::eyre!("failed to open ignored uuids {path}: {e}"))?;
+        // fails right away on a database without the table rather than on the first hit
+        db.prepare_cached(LOOKUP)
+            .map_err(|e| eyre::eyre!("failed to read ignored uuids from {path}: {e}"))?;
+        Ok(Self {
+            path: path.to_string(),
+            db: Mutex::new(db),
+        })
+    }
+
+    // uuids are looked up dashed and undashed, so tables imported from the undashed dumps work too
+    fn contains(&self, uuid: Uuid) -> rusqlite::Result<bool> {
+        let db = self.db.lock().unwrap();
+        let mut lookup = db.prepare_cached(LOOKUP)?;
+        let found = lookup.exists((uuid.to_string(), uuid.simple().to_string()))?;
+        drop(lookup);
+        drop(db);
+        Ok(found)
+    }
+}
+
+// what happens to lines of an ignore list that aren't uuids: they're skipped, written to
+// `rejects` if given, and the load fails once their share of the lines goes over `max_share`
+#[derive(Debug, Clone)]
+pub struct BadLines {
+    pub max_share: f64,
+    pub rejects: Option<String>,
+}
+
+// how many of the skipped lines are shown in the warning
+const SHOWN_BAD_LINES: usize = 3;
+
+const LOOKUP: &str = "SELECT 1 FROM uuids WHERE uuid IN (?1, ?2)";
+
+impl Ignored {
+    // merges every source into one set, sources without their own truncation use the default
+    pub async fn load(
+        sources: &[IgnoreSource],
+        default_truncation: Option<usize>,
+        bad_lines: &BadLines,
+    ) -> eyre::Result<Self> {
+        let mut files = vec![];
+        let mut dbs = vec![];
+        for source in sources {
+            if source.sqlite {
+                let path = source.path.clone();
+                let db = tokio::task::spawn_blocking(move || IgnoreDb::open(&path)).await??;
+                dbs.push(Arc::new(db));
+                continue;
+            }
+            let truncation = source.truncation.or(default_truncation);
+            files.push((
+                source.path.clone(),
+                tokio::fs::read_to_string(&source.path).await?,
+                truncation,
+            ));
+        }
+        let bad_lines = bad_lines.clone();
+        let mut ignored = tokio::task::spawn_blocking(move || {
+            let (ranges, bad) = files
+                .par_iter()
+                .flat_map(|(path, f, truncation)| {
+                    f.par_lines()
+                        .filter(|u| !u.trim().is_empty())
+                        .map(move |u| (path, f, u, parse_range(u, *truncation)))
+                })
+                .partition_map::<Vec<_>, Vec<_>, _, _, _>(|(path, f, u, range)| match range {
+                    Ok(range) => Either::Left(range),
+                    Err(e) => Either::Right((path, f, u, e)),
+                });
+            if !bad.is_empty() {
+                report_bad_lines(&bad, ranges.len() + bad.len(), &bad_lines)?;
+            }
+            eyre::Ok(Self::from_ranges(ranges))
+        })
+        .await??;
+        ignored.dbs = dbs;
+        Ok(ignored)
+    }
+
+    fn from_ranges(mut ranges: Vec<(u128, u128)>) -> Self {
+        ranges.par_sort_unstable();
+        // merge overlapping (and adjacent) ranges, so the only candidate for a uuid is the last
+        // range starting at or before it
+        let mut merged: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some((_, last_end)) if start <= last_end.saturating_add(1) => {
+                    *last_end = end.max(*last_end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        Self {
+            ranges: merged,
+            dbs: vec![],
+        }
+    }
+
+    // everything ignored by either set
+    fn union(&self, other: &Self) -> Self {
+        let mut union = Self::from_ranges([&self.ranges[..], &other.ranges[..]].concat());
+        union.dbs.clone_from(&self.dbs);
+        union
+    }
+
+    // the entries held in memory, sqlite lists aren't counted
+    pub const fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn db_paths(&self) -> impl Iterator<Item = &str> {
+        self.dbs.iter().map(|db| db.path.as_str())
+    }
+
+    pub fn contains(&self, uuid: Uuid) -> bool {
+        let n = uuid.as_u128();
+        let i = self.ranges.partition_point(|(start, _)| *start <= n);
+        if i > 0 && n <= self.ranges[i - 1].1 {
+            return true;
+        }
+        self.dbs.iter().any(|db| {
+            db.contains(uuid).unwrap_or_else(|e| {
+                eprintln!(
+                    "{}warn: failed to look up {uuid} in {}: {e}",
+                    clear_line(),
+                    db.path
+                );
+                false
+            })
+        })
+    }
+}
+
+// warns about the lines that were skipped and writes them to the reject file, fails if there are
+// too many of them to trust the rest
+#[allow(clippy::cast_precision_loss)]
+fn report_bad_lines(
+    bad: &[(&String, &String, &str, eyre::Report)],
+    lines: usize,
+    bad_lines: &BadLines,
+) -> eyre::Result<()> {
+    let mut shown = String::new();
+    for (path, f, line, e) in bad.iter().take(SHOWN_BAD_LINES) {
+        // only worked out for the few that are shown, the lines are parsed without counting
+        let offset = line.as_ptr() as usize - f.as_ptr() as usize;
+        let number = f[..offset].lines().count() + 1;
+        let _ = write!(shown, "\n  {path}:{number}: {e}");
+    }
+    if bad.len() > SHOWN_BAD_LINES {
+        let _ = write!(shown, "\n  and {} more", bad.len() - SHOWN_BAD_LINES);
+    }
+    if let Some(rejects) = &bad_lines.rejects {
+        let mut out = String::new();
+        for (_, _, line, _) in bad {
+            out.push_str(line);
+            out.push('\n');
+        }
+        std::fs::write(rejects, out)
+            .map_err(|e| eyre::eyre!("failed to write rejected lines to {rejects}: {e}"))?;
+    }
+    let share = bad.len() as f64 / lines as f64;
+    if share > bad_lines.max_share {
+        eyre::bail!(
+            "{} of {lines} lines in the ignore lists aren't uuids, more than --max-bad-ignored allows:{shown}",
+            bad.len()
+        );
+    }
+    eprintln!(
+        "{}warn: skipped {} lines in the ignore lists that aren't uuids:{shown}",
+        clear_line(),
+        bad.len()
+    );
+    Ok(())
+}
+
+// full uuids (with or without dashes) have to parse as one. with a truncation, any hex prefix is
+// accepted and longer entries are cut down to the truncation.
+fn parse_range(line: &str, truncation: Option<usize>) -> eyre::Result<(u128, u128)> {
+    let line = line.trim();
+    let Some(truncation) = truncation else {
+        let uuid = Uuid::from_str(line)
+            .map_err(|e| eyre::eyre!("failed to parse ignored uuid `{line}`: {e}"))?
+            .as_u128();
+        return Ok((uuid, uuid));
+    };
+    let digits = line.replace('-', "");
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        eyre::bail!("failed to parse ignored uuid prefix `{line}`: not hex");
+    }
+    let digits = &digits[..digits.len().min(truncation).min(32)];
+    if digits.is_empty() {
+        return Ok((0, u128::MAX));
+    }
+    let prefix = u128::from_str_radix(digits, 16)?;
+    if digits.len() == 32 {
+        return Ok((prefix, prefix));
+    }
+    let free_bits = 128 - digits.len() * 4;
+    let start = prefix << free_bits;
+    Ok((start, start | (u128::MAX >> (digits.len() * 4))))
+}
+
+// reloads the ignore files on sighup, or whenever one of them changes if `poll` is set, and merges
+// their entries into the live set. entries removed from a file stay ignored until the next run.
+// sqlite lists are always up to date, rows added to them while running are seen by the next lookup.
+pub async fn reload(
+    sources: Vec<IgnoreSource>,
+    default_truncation: Option<usize>,
+    bad_lines: BadLines,
+    poll: bool,
+    live: watch::Sender<Arc<Ignored>>,
+) -> eyre::Result<()> {
+    let sources = sources
+        .into_iter()
+        .filter(|source| !source.sqlite)
+        .collect::<Vec<_>>();
+    if sources.is_empty() {
+        return Ok(());
+    }
+    let mut hangup = Hangup::new()?;
+    let mut modified = modified_times(&sources).await;
+    loop {
+        if poll {
+            tokio::select! {
+                () = hangup.recv() => {}
+                () = tokio::time::sleep(Duration::from_secs(10)) => {
+                    let now = modified_times(&sources).await;
+                    if now == modified {
+                        continue;
+                    }
+                }
+            }
+        } else {
+            hangup.recv().await;
+        }
+        modified = modified_times(&sources).await;
+
+        let reloaded = match Ignored::load(&sources, default_truncation, &bad_lines).await {
+            Ok(reloaded) => reloaded,
+            Err(e) => {
+                eprintln!("{}warn: failed to reload ignored uuids: {e}", clear_line());
+                continue;
+            }
+        };
+        let current = live.borrow().clone();
+        let merged = tokio::task::spawn_blocking(move || current.union(&reloaded)).await?;
+        info!(
+            "{}reloaded ignored uuids, {} uuids ignored",
+            clear_line(),
+            merged.len()
+        );
+        live.send_replace(Arc::new(merged));
+    }
+}
+
+async fn modified_times(sources: &[IgnoreSource]) -> Vec<Option<SystemTime>> {
+    let mut times = vec![];
+    for source in sources {
+        let modified = tokio::fs::metadata(&source.path)
+            .await
+            .and_then(|m| m.modified());
+        times.push(modified.ok());
+    }
+    times
+}
+
+// sighup, which never arrives on platforms without it
+struct Hangup(#[cfg(unix)] tokio::signal::unix::Signal);
+
+impl Hangup {
+    #[allow(clippy::unnecessary_wraps)]
+    fn new() -> std::io::Result<Self> {
+        #[cfg(unix)]
+        return Ok(Self(tokio::signal::unix::signal(
+            tokio::signal::unix::SignalKind::hangup(),
+        )?));
+        #[cfg(not(unix))]
+        Ok(Self())
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.0.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn overlapping_and_adjacent_ranges_are_merged() {
+        let (a, b) = (
+            tempfile::NamedTempFile::new().unwrap(),
+            tempfile::NamedTempFile::new().unwrap(),
+        );
+        std::fs::write(a.path(), "1\n0\n\n").unwrap();
+        std::fs::write(b.path(), "12\nf\n").unwrap();
+        let sources = [
+            a.path().display().to_string(),
+            format!("{}:1", b.path().display()),
+        ]
+        .map(|s| s.parse::<IgnoreSource>().unwrap());
+        let strict = BadLines {
+            max_share: 0.0,
+            rejects: None,
+        };
+        let ignored = Ignored::load(&sources, Some(2), &strict).await.unwrap();
+        // 0 and 1 touch, 12 is cut down to 1 by its own truncation
+        let end = u128::MAX >> 3;
+        assert_eq!(ignored.ranges, [(0, end), (0xf << 124, u128::MAX)]);
+        for (n, expected) in [
+            (0, true),
+            (end, true),
+            (end + 1, false),
+            ((0xf << 124) - 1, false),
+        ] {
+            assert_eq!(ignored.contains(Uuid::from_u128(n)), expected, "{n:x}");
+        }
+        assert!(ignored.contains(Uuid::max()));
+    }
+
+    #[test]
+    fn truncated_entries_cover_their_prefix() {
+        let full = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+        let notch = Uuid::from_str(full).unwrap().as_u128();
+        assert_eq!(parse_range(full, None).unwrap(), (notch, notch));
+        let (start, end) = parse_range("069a79f4", Some(8)).unwrap();
+        assert_eq!(start, 0x069a_79f4 << 96);
+        assert_eq!(end, start | (u128::MAX >> 32));
+        assert!((start..=end).contains(&notch));
+        // longer entries are cut down to the truncation
+        assert_eq!(parse_range(full, Some(8)).unwrap(), (start, end));
+        assert_eq!(parse_range(full, Some(32)).unwrap(), (notch, notch));
+        assert!(parse_range("069a79f4", None).is_err());
+        assert!(parse_range("notch", Some(8)).is_err());
+    }
+
+    #[test]
+    fn unions_merge_both_sets() {
+        let a = Ignored::from_ranges(vec![(0, 9), (20, 29)]);
+        let b = Ignored::from_ranges(vec![(10, 15), (40, 49)]);
+        assert_eq!(a.union(&b).ranges, [(0, 15), (20, 29), (40, 49)]);
+    }
+}
@@ -0,0 +1,309 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufWriter, Write};
+use std::sync::{Mutex, OnceLock};
+
+use crate::term::clear_line;
+
+// `--journal`, set once before the wordlist is gone through
+static JOURNAL: OnceLock<Journal> = OnceLock::new();
+
+// the words every candidate of which got an answer that made it into the output, so `--resume`
+// can skip exactly those. the file starts with 8 bytes identifying the settings that turn words
+// into candidates, followed by the first 8 bytes of every done word's sha256: it doesn't matter
+// where in the wordlist a word is, so a reordered or grown wordlist still lines up.
+struct Journal {
+    // words done by earlier runs
+    done: HashSet<u64>,
+    state: Mutex<State>,
+}
+
+struct State {
+    file: BufWriter<std::fs::File>,
+    next: u64,
+    open: HashMap<u64, Word>,
+    // done words whose hits may not be written out yet, and those whose hits are since the last
+    // sync
+    pending: Vec<u64>,
+    ready: Vec<u64>,
+    // a journal that can't be written to is only complained about once
+    warned: bool,
+}
+
+// a word that's still being queried
+struct Word {
+    hash: u64,
+    // batches with candidates of it that didn't get an answer yet
+    batches: usize,
+    // whether all of its candidates were handed out
+    expanded: bool,
+}
+
+fn hash(data: &str) -> u64 {
+    let digest = Sha256::digest(data.as_bytes());
+    u64::from_le_bytes(
+        digest[..8]
+            .try_into()
+            .expect("sha256 is longer than 8 bytes"),
+    )
+}
+
+// opens the journal at `path`. with `resume` the words it has are skipped and new ones added,
+// otherwise it starts over.
+pub async fn open(path: &str, resume: bool, settings: &str) -> eyre::Result<()> {
+    let (done, file) = load(path, resume, settings).await?;
+    if !done.is_empty() {
+        info!(
+            "{} words in {path} were done before, skipping them",
+            done.len()
+        );
+    }
+    let _ = JOURNAL.set(Journal {
+        done,
+        state: Mutex::new(State::new(file)),
+    });
+    Ok(())
+}
+
+// the words done by earlier runs, and the file to add more to
+async fn load(
+    path: &str,
+    resume: bool,
+    settings: &str,
+) -> eyre::Result<(HashSet<u64>, std::fs::File)> {
+    let header = hash(settings).to_le_bytes();
+    let existing = if resume {
+        match tokio::fs::read(path).await {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => eyre::bail!("failed to read journal {path}: {e}"),
+        }
+    } else {
+        vec![]
+    };
+    let fresh = existing.len() < 8;
+    if !fresh && existing[..8] != header {
+        eyre::bail!(
+            "journal {path} was written with other patterns, suffixes or --mutate, remove it to start over"
+        );
+    }
+    let entries = if fresh { &[][..] } else { &existing[8..] };
+    let done = entries
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunks are 8 bytes")))
+        .collect::<HashSet<_>>();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| eyre::eyre!("failed to open journal {path}: {e}"))?;
+    if fresh {
+        file.set_len(0)?;
+        file.write_all(&header)?;
+    } else {
+        // a word cut off by a crash is dropped, the ones after it would be misaligned otherwise
+        file.set_len(8 + (entries.len() / 8 * 8) as u64)?;
+    }
+    Ok((done, file))
+}
+
+pub fn enabled() -> bool {
+    JOURNAL.get().is_some()
+}
+
+// whether an earlier run did `word` already
+pub fn skips(word: &str) -> bool {
+    JOURNAL
+        .get()
+        .is_some_and(|journal| journal.done.contains(&hash(word)))
+}
+
+// starts keeping track of `word`, the id it gets is put on every batch with candidates of it
+pub fn begin(word: &str) -> Option<u64> {
+    let journal = JOURNAL.get()?;
+    let mut state = journal.state.lock().unwrap();
+    let id = state.next;
+    state.next += 1;
+    state.open.insert(
+        id,
+        Word {
+            hash: hash(word),
+            batches: 0,
+            expanded: false,
+        },
+    );
+    drop(state);
+    Some(id)
+}
+
+// a batch got candidates of word `id`
+pub fn batched(id: u64) {
+    if let Some(journal) = JOURNAL.get()
+        && let Some(word) = journal.state.lock().unwrap().open.get_mut(&id)
+    {
+        word.batches += 1;
+    }
+}
+
+// every candidate of word `id` was handed out
+pub fn expanded(id: u64) {
+    if let Some(journal) = JOURNAL.get() {
+        let mut state = journal.state.lock().unwrap();
+        if let Some(word) = state.open.get_mut(&id) {
+            word.expanded = true;
+        }
+        state.finish_word(id);
+    }
+}
+
+// a batch with candidates of `words` got its answer and its hits were sent to the handler
+pub fn answered(words: &[u64]) {
+    if let Some(journal) = JOURNAL.get() {
+        let mut state = journal.state.lock().unwrap();
+        for id in words {
+            if let Some(word) = state.open.get_mut(id) {
+                word.batches -= 1;
+            }
+            state.finish_word(*id);
+        }
+    }
+}
+
+// called by the handler right after writing out the hits it has. the words that were done at the
+// last sync had their hits sent before then, so they're written out now and can be written down.
+pub fn sync() {
+    if let Some(journal) = JOURNAL.get() {
+        journal.state.lock().unwrap().sync();
+    }
+}
+
+// writes down every word that's done, once no more hits can come in
+pub fn finish() {
+    if let Some(journal) = JOURNAL.get() {
+        let mut state = journal.state.lock().unwrap();
+        let mut done = std::mem::take(&mut state.ready);
+        done.append(&mut state.pending);
+        state.write(&done);
+    }
+}
+
+impl State {
+    fn new(file: std::fs::File) -> Self {
+        Self {
+            file: BufWriter::new(file),
+            next: 0,
+            open: HashMap::new(),
+            pending: vec![],
+            ready: vec![],
+            warned: false,
+        }
+    }
+
+    fn sync(&mut self) {
+        let ready = std::mem::replace(&mut self.ready, std::mem::take(&mut self.pending));
+        self.write(&ready);
+    }
+
+    fn finish_word(&mut self, id: u64) {
+        if self
+            .open
+            .get(&id)
+            .is_some_and(|word| word.expanded && word.batches == 0)
+        {
+            let word = self.open.remove(&id).expect("word was just looked up");
+            self.pending.push(word.hash);
+        }
+    }
+
+    fn write(&mut self, hashes: &[u64]) {
+        if hashes.is_empty() {
+            return;
+        }
+        let res = hashes
+            .iter()
+            .try_for_each(|hash| self.file.write_all(&hash.to_le_bytes()))
+            .and_then(|()| self.file.flush());
+        if let Err(e) = res
+            && !self.warned
+        {
+            self.warned = true;
+            eprintln!(
+                "{}warn: failed to write to the journal, resuming will query words again: {e}",
+                clear_line()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: &str = "pattern {word}";
+
+    fn entries(path: &std::path::Path) -> Vec<u64> {
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(data[..8], hash(SETTINGS).to_le_bytes());
+        data[8..]
+            .chunks(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn journals_of_other_settings_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+        let path_str = path.to_str().unwrap();
+        load(path_str, true, SETTINGS).await.unwrap();
+        let e = load(path_str, true, "pattern {word}{yy}")
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("written with other patterns"), "{e}");
+        // unless it starts over
+        load(path_str, false, "pattern {word}{yy}").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_torn_last_entry_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+        let mut data = hash(SETTINGS).to_le_bytes().to_vec();
+        data.extend(hash("notch").to_le_bytes());
+        data.extend(&hash("jeb_").to_le_bytes()[..5]);
+        std::fs::write(&path, data).unwrap();
+
+        let (done, file) = load(path.to_str().unwrap(), true, SETTINGS).await.unwrap();
+        assert_eq!(done, HashSet::from([hash("notch")]));
+        let mut state = State::new(file);
+        state.write(&[hash("dinnerbone")]);
+        assert_eq!(entries(&path), [hash("notch"), hash("dinnerbone")]);
+    }
+
+    #[tokio::test]
+    async fn words_are_written_down_a_sync_after_they_are_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+        let (_, file) = load(path.to_str().unwrap(), false, SETTINGS).await.unwrap();
+        let mut state = State::new(file);
+        state.open.insert(
+            0,
+            Word {
+                hash: hash("notch"),
+                batches: 1,
+                expanded: true,
+            },
+        );
+        state.finish_word(0);
+        assert!(state.pending.is_empty(), "a batch of it is still out");
+        state.open.get_mut(&0).unwrap().batches -= 1;
+        state.finish_word(0);
+        state.sync();
+        assert!(
+            entries(&path).is_empty(),
+            "its hits may not be written out yet"
+        );
+        state.sync();
+        assert_eq!(entries(&path), [hash("notch")]);
+    }
+}
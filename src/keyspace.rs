@@ -0,0 +1,145 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::pattern::{self, Pattern};
+use crate::wordlist::{self, Words};
+use crate::{KeyspaceArgs, Shard, clean_options, load_suffixes};
+
+// `uuidump keyspace`, prints how many candidates and requests a run with these options makes.
+// filters like --exclude or --ignored-names aren't taken into account.
+// shard indices are below the shard count, which has to fit in a usize for the counts
+#[allow(clippy::cast_possible_truncation)]
+pub async fn run(args: &KeyspaceArgs) -> eyre::Result<()> {
+    let cleaning = clean_options(
+        args.allow_legacy_names,
+        args.transliterate,
+        args.transform_rules.as_deref(),
+        false,
+    )
+    .await?;
+    let mut size = 0;
+    for spec in &args.wordlists {
+        size += tokio::fs::metadata(&spec.path).await?.len();
+    }
+    let words = if size > wordlist::EXTERNAL_THRESHOLD {
+        if args.wordlists.len() > 1 {
+            eyre::bail!("multiple wordlists are combined in memory, they're too big for that");
+        }
+        let path = args.wordlists[0].path.clone();
+        let sorted = tokio::task::spawn_blocking(move || {
+            wordlist::external_dedup(&path, &std::env::temp_dir(), &cleaning)
+        })
+        .await??;
+        Words::Disk(sorted)
+    } else {
+        Words::Memory(
+            wordlist::load_mixed(&args.wordlists, wordlist::Order::Priority, &cleaning)
+                .await?
+                .0,
+        )
+    };
+    let suffixes = load_suffixes(args.suffixes.as_deref()).await?;
+    let patterns = pattern::or_word(args.patterns.clone());
+
+    let per_word = per_word(&patterns, &suffixes);
+    let candidates = words.len() as u64 * per_word;
+    println!("words: {}", words.len());
+    println!(
+        "candidates per word: {per_word} ({} patterns, {} suffixes)",
+        patterns.len(),
+        suffixes.len()
+    );
+    println!("candidates: {candidates}");
+    println!("requests: {}", candidates.div_ceil(10));
+
+    let Some(count) = args.shards else {
+        return Ok(());
+    };
+    if count == 0 {
+        eyre::bail!("--shards needs at least one shard");
+    }
+    let mut per_shard = vec![0_u64; usize::try_from(count)?];
+    let mut count_word = |word: &str| {
+        for pattern in &patterns {
+            for expanded in pattern.expand(word) {
+                for suf in &suffixes {
+                    let candidate = format!("{expanded}{suf}").to_ascii_lowercase();
+                    per_shard[Shard::index_of(&candidate, count) as usize] += 1;
+                }
+            }
+        }
+    };
+    match &words {
+        Words::Memory(words) => words.iter().for_each(|w| count_word(w)),
+        Words::Disk(sorted) => {
+            let mut lines = BufReader::new(tokio::fs::File::open(&sorted.path).await?).lines();
+            while let Some(word) = lines.next_line().await? {
+                count_word(&word);
+            }
+        }
+    }
+    for (index, n) in per_shard.iter().enumerate() {
+        println!(
+            "shard {index}/{count}: {n} candidates, {} requests",
+            n.div_ceil(10)
+        );
+    }
+    Ok(())
+}
+
+// how many candidates every word expands to
+pub fn per_word(patterns: &[Pattern], suffixes: &[String]) -> u64 {
+    patterns.iter().map(Pattern::count).sum::<u64>() * suffixes.len() as u64
+}
+
+// how far into the keyspace a run got, counting candidates in the order they're generated
+// (skipped and filtered ones included). every candidate before `current()` has been answered, so
+// a run can be resumed from it with `--skip-keyspace`.
+pub struct Position(Mutex<PositionState>);
+
+struct PositionState {
+    generated: u64,
+    // first candidate of every batch that's still being filled or waiting for an answer
+    pending: BTreeSet<u64>,
+}
+
+impl Position {
+    pub const fn new() -> Self {
+        Self(Mutex::new(PositionState {
+            generated: 0,
+            pending: BTreeSet::new(),
+        }))
+    }
+
+    // the position the next candidate will get
+    pub fn peek(&self) -> u64 {
+        self.0.lock().unwrap().generated
+    }
+
+    // moves past `n` candidates that don't need an answer
+    pub fn advance(&self, n: u64) {
+        self.0.lock().unwrap().generated += n;
+    }
+
+    // takes the next position, keeping it pending until `close` if it starts a batch
+    pub fn take(&self, opens_batch: bool) -> u64 {
+        let mut state = self.0.lock().unwrap();
+        let at = state.generated;
+        state.generated += 1;
+        if opens_batch {
+            state.pending.insert(at);
+        }
+        at
+    }
+
+    // marks the batch starting at `start` as answered
+    pub fn close(&self, start: u64) {
+        self.0.lock().unwrap().pending.remove(&start);
+    }
+
+    pub fn current(&self) -> u64 {
+        let state = self.0.lock().unwrap();
+        state.pending.first().copied().unwrap_or(state.generated)
+    }
+}
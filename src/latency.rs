@@ -0,0 +1,84 @@
+use hdrhistogram::Histogram;
+use serde_json::{Value, json};
+use std::io::{LineWriter, Write};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::open_output;
+use crate::term::clear_line;
+
+// request latencies in microseconds, from 1us up to 10 minutes at 3 significant digits
+static LATENCY: LazyLock<Mutex<Histogram<u64>>> =
+    LazyLock::new(|| Mutex::new(Histogram::new_with_bounds(1, 10 * 60 * 1_000_000, 3).unwrap()));
+
+// `--latency-log` file, one `unix ms,batch size,status,duration ms` line per request
+static LOG: OnceLock<Mutex<LineWriter<std::fs::File>>> = OnceLock::new();
+
+pub async fn open_log(path: &str) -> eyre::Result<()> {
+    let file = open_output(path).await?.into_std().await;
+    let _ = LOG.set(Mutex::new(LineWriter::new(file)));
+    Ok(())
+}
+
+// `status` is `ok` or the class of the error the request failed with
+#[allow(clippy::cast_possible_truncation)]
+pub fn record(latency: Duration, batch: usize, status: &str) {
+    LATENCY
+        .lock()
+        .unwrap()
+        .saturating_record(latency.as_micros() as u64);
+    if let Some(log) = LOG.get() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let line = format!("{now},{batch},{status},{}\n", latency.as_millis());
+        let res = log.lock().unwrap().write_all(line.as_bytes());
+        if let Err(e) = res {
+            eprintln!("{}warn: failed to write latency log: {e}", clear_line());
+        }
+    }
+}
+
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+pub fn percentiles() -> Option<Percentiles> {
+    let latency = LATENCY.lock().unwrap();
+    if latency.is_empty() {
+        return None;
+    }
+    let [p50, p95, p99] =
+        [0.5, 0.95, 0.99].map(|q| Duration::from_micros(latency.value_at_quantile(q)));
+    drop(latency);
+    Some(Percentiles { p50, p95, p99 })
+}
+
+// for the status snapshots and the meta file, null before the first request
+pub fn json() -> Value {
+    percentiles().map_or(Value::Null, |p| {
+        json!({
+            "p50_ms": p.p50.as_millis(),
+            "p95_ms": p.p95.as_millis(),
+            "p99_ms": p.p99.as_millis(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_of_the_recorded_requests() {
+        assert!(json().is_null());
+        for ms in 1..=100 {
+            record(Duration::from_millis(ms), 10, "ok");
+        }
+        let p = percentiles().unwrap();
+        assert_eq!([p.p50, p.p95, p.p99].map(|d| d.as_millis()), [50, 95, 99]);
+        assert_eq!(json()["p99_ms"], 99);
+    }
+}
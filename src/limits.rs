@@ -0,0 +1,103 @@
+use std::sync::OnceLock;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::term::clear_line;
+
+// file descriptors kept free of connections for the output, the sinks, the wordlist and stdio
+const FD_RESERVE: u64 = 64;
+
+// how many requests may be in flight at once, across every client. unset doesn't limit them.
+static CONNECTIONS: OnceLock<(usize, Semaphore)> = OnceLock::new();
+
+// sets the connection limit from `--max-connections` and the open file limit, which is raised as
+// far as it goes first. every connection is a file descriptor, so more of them than the limit
+// allows fail with "too many open files" instead of being held back.
+pub fn init(max_connections: Option<usize>, threads: usize) {
+    let available = raise_fd_limit().map(|limit| {
+        usize::try_from(limit.saturating_sub(FD_RESERVE).max(1)).unwrap_or(usize::MAX)
+    });
+    let limit = match (max_connections, available) {
+        (Some(max), Some(available)) if max > available => {
+            eprintln!(
+                "warn: --max-connections {max} is more than the open file limit allows, using {available}"
+            );
+            available
+        }
+        (Some(max), _) => max,
+        (None, Some(available)) => {
+            if threads > available {
+                info!(
+                    "the open file limit only allows {available} connections, the {threads} threads share them"
+                );
+            }
+            available
+        }
+        (None, None) => return,
+    };
+    let _ = CONNECTIONS.set((limit, Semaphore::new(limit.max(1))));
+}
+
+pub fn max_connections() -> Option<usize> {
+    CONNECTIONS.get().map(|(limit, _)| *limit)
+}
+
+// waits for a free connection, held until the response is read
+pub async fn connection() -> Option<SemaphorePermit<'static>> {
+    let (_, semaphore) = CONNECTIONS.get()?;
+    match semaphore.acquire().await {
+        Ok(permit) => Some(permit),
+        Err(e) => {
+            eprintln!("{}warn: connection limit is gone: {e}", clear_line());
+            None
+        }
+    }
+}
+
+// the soft limit on open files after raising it to the hard limit, none where it can't be read
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn raise_fd_limit() -> Option<u64> {
+    #[repr(C)]
+    struct Rlimit {
+        cur: u64,
+        max: u64,
+    }
+    #[cfg(target_os = "linux")]
+    const RLIMIT_NOFILE: i32 = 7;
+    #[cfg(target_os = "macos")]
+    const RLIMIT_NOFILE: i32 = 8;
+    // macos refuses a soft limit above this, even with an unlimited hard limit
+    #[cfg(target_os = "macos")]
+    const OPEN_MAX: u64 = 10240;
+    unsafe extern "C" {
+        fn getrlimit(resource: i32, rlim: *mut Rlimit) -> i32;
+        fn setrlimit(resource: i32, rlim: *const Rlimit) -> i32;
+    }
+
+    let mut limit = Rlimit { cur: 0, max: 0 };
+    // SAFETY: `limit` has the layout of `struct rlimit` (two `rlim_t`, which are 64 bits on both
+    // platforms) and outlives the calls using it
+    unsafe {
+        if getrlimit(RLIMIT_NOFILE, &raw mut limit) != 0 {
+            return None;
+        }
+        #[cfg(target_os = "macos")]
+        let max = limit.max.min(OPEN_MAX);
+        #[cfg(not(target_os = "macos"))]
+        let max = limit.max;
+        if limit.cur < max {
+            let raised = Rlimit {
+                cur: max,
+                max: limit.max,
+            };
+            if setrlimit(RLIMIT_NOFILE, &raw const raised) == 0 {
+                limit.cur = max;
+            }
+        }
+    }
+    Some(limit.cur)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const fn raise_fd_limit() -> Option<u64> {
+    None
+}
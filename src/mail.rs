@@ -0,0 +1,486 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::{self, pki_types::ServerName};
+
+use crate::clock::{DateTime, now_unix};
+use crate::retry::error_summary;
+use crate::term::clear_line;
+use crate::{
+    ABORTED, CACHED_COUNTER, Cli, FAILED_BATCHES, KEYSPACE, REQ_COUNTER, STOPPED, UUID_ALL_COUNTER,
+    UUID_COUNTER, failure, format_duration, latency, proxy, s3, upload,
+};
+
+// outputs bigger than this are only named in the report, mail servers turn down big messages
+const ATTACH_LIMIT: u64 = 10 << 20;
+
+// `--mail-to`, the end-of-run report sent by mail, for runs nobody watches the whole time
+pub struct Mailer {
+    host: String,
+    port: u16,
+    // `smtps://`, tls from the start instead of upgrading with STARTTLS
+    implicit_tls: bool,
+    login: Option<(String, String)>,
+    from: String,
+    to: Vec<String>,
+    output: String,
+    upload: Option<String>,
+    timeout: Duration,
+}
+
+impl Mailer {
+    // checked before the run starts, like --upload
+    pub fn new(args: &Cli) -> eyre::Result<Self> {
+        let url = reqwest::Url::parse(&args.smtp)
+            .map_err(|e| eyre::eyre!("invalid --smtp url {}: {e}", args.smtp))?;
+        let implicit_tls = match url.scheme() {
+            "smtp" => false,
+            "smtps" => true,
+            scheme => {
+                eyre::bail!("expected an smtp:// or smtps:// url for --smtp, got {scheme}://")
+            }
+        };
+        let host = url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| eyre::eyre!("--smtp {} has no host", args.smtp))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let decode = |s| {
+            proxy::percent_decode(s)
+                .ok_or_else(|| eyre::eyre!("invalid %-escape in the --smtp credentials"))
+        };
+        let user = decode(url.username())?;
+        let password = match url.password() {
+            Some(password) => Some(decode(password)?),
+            None => args.smtp_password.clone(),
+        };
+        let login = match password {
+            _ if user.is_empty() => None,
+            Some(password) => Some((user.clone(), password)),
+            None => eyre::bail!("--smtp logs in as {user}, but no --smtp-password was given"),
+        };
+        let from = match &args.mail_from {
+            Some(from) => from.clone(),
+            None if user.contains('@') => user,
+            None => eyre::bail!("--mail-to needs --mail-from, the --smtp user isn't an address"),
+        };
+        Ok(Self {
+            host,
+            port: url.port().unwrap_or(if implicit_tls { 465 } else { 25 }),
+            implicit_tls,
+            login,
+            from,
+            to: args.mail_to.clone(),
+            output: args.output_path.clone(),
+            upload: args.upload.clone(),
+            timeout: args.timeout,
+        })
+    }
+
+    // sends the report for a run that took `elapsed` and ended with `outcome`. a report that
+    // can't be sent is only warned about, the run's result stays what it was.
+    pub async fn report(&self, elapsed: Duration, outcome: &eyre::Result<ExitCode>) {
+        let message = self.message(elapsed, outcome).await;
+        if let Err(e) = self.send(&message).await {
+            eprintln!(
+                "{}warn: failed to mail the report to {}: {e}",
+                clear_line(),
+                self.to.join(", ")
+            );
+        } else {
+            info!("mailed the report to {}", self.to.join(", "));
+        }
+    }
+
+    async fn message(&self, elapsed: Duration, outcome: &eyre::Result<ExitCode>) -> Vec<u8> {
+        let found = UUID_COUNTER.load(Ordering::SeqCst);
+        let state = match outcome {
+            Err(_) => "failed",
+            Ok(_) if STOPPED.load(Ordering::SeqCst) => "was stopped",
+            Ok(_) if ABORTED.load(Ordering::SeqCst) => "was aborted",
+            Ok(_) if failure().is_some() => "finished with dropped batches",
+            Ok(_) => "finished",
+        };
+        let mut text = format!("the run writing to {} {state}.\r\n\r\n", self.output);
+        if let Err(e) = outcome {
+            let _ = write!(text, "error: {e:#}\r\n\r\n");
+        }
+        let _ = write!(
+            text,
+            "took: {}\r\nrequests: {}\r\nuuids found: {found} ({} total), {} cached\r\nerrors: {}\r\ndropped batches: {}\r\nkeyspace position: {}\r\n",
+            format_duration(elapsed),
+            REQ_COUNTER.load(Ordering::SeqCst),
+            UUID_ALL_COUNTER.load(Ordering::SeqCst),
+            CACHED_COUNTER.load(Ordering::SeqCst),
+            error_summary().unwrap_or_else(|| "none".to_string()),
+            FAILED_BATCHES.load(Ordering::SeqCst),
+            KEYSPACE.current(),
+        );
+        if let Some(p) = latency::percentiles() {
+            let _ = write!(
+                text,
+                "latency p50/95/99: {}/{}/{}ms\r\n",
+                p.p50.as_millis(),
+                p.p95.as_millis(),
+                p.p99.as_millis()
+            );
+        }
+
+        let (where_, attachment) = self.output_note(outcome).await;
+        let _ = write!(text, "output: {where_}\r\n");
+        let subject = format!("uuidump: {} {state}, {found} uuids found", self.output);
+        let boundary = format!("uuidump-{:016x}", crate::random_seed());
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {subject}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=\"{boundary}\"\r\n\r\n--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{text}",
+            self.from,
+            self.to.join(", "),
+            DateTime::from_unix(now_unix()).rfc2822(),
+        );
+        if let Some(data) = attachment {
+            let name = Path::new(&self.output)
+                .file_name()
+                .map_or_else(|| "output".into(), |f| f.to_string_lossy());
+            let _ = write!(
+                message,
+                "\r\n--{boundary}\r\nContent-Type: application/octet-stream\r\nContent-Disposition: attachment; filename=\"{name}\"\r\nContent-Transfer-Encoding: base64\r\n\r\n"
+            );
+            for line in BASE64.encode(data).as_bytes().chunks(76) {
+                message.push_str(&String::from_utf8_lossy(line));
+                message.push_str("\r\n");
+            }
+        }
+        let _ = write!(message, "\r\n--{boundary}--\r\n");
+        message.into_bytes()
+    }
+
+    // where the output is, and its contents if it's small enough to attach
+    async fn output_note(&self, outcome: &eyre::Result<ExitCode>) -> (String, Option<Vec<u8>>) {
+        if s3::is_s3(&self.output) {
+            return (self.output.clone(), None);
+        }
+        // --upload only runs for runs that succeeded
+        if let Some(url) = &self.upload
+            && outcome.is_ok()
+            && failure().is_none()
+        {
+            let url = upload::redacted(&upload::target(url, &self.output));
+            return (format!("uploaded to {url}"), None);
+        }
+        let len = match tokio::fs::metadata(&self.output).await {
+            Ok(meta) => meta.len(),
+            Err(e) => return (format!("{} can't be read: {e}", self.output), None),
+        };
+        if len > ATTACH_LIMIT {
+            return (
+                format!("{} ({len} bytes, too big to attach)", self.output),
+                None,
+            );
+        }
+        match tokio::fs::read(&self.output).await {
+            Ok(data) => (format!("attached ({len} bytes)"), Some(data)),
+            Err(e) => (format!("{} can't be read: {e}", self.output), None),
+        }
+    }
+
+    async fn send(&self, message: &[u8]) -> eyre::Result<()> {
+        let tcp = tokio::time::timeout(
+            self.timeout,
+            TcpStream::connect((self.host.as_str(), self.port)),
+        )
+        .await
+        .map_err(|_| eyre::eyre!("timed out connecting to {}:{}", self.host, self.port))??;
+        let local = tcp.local_addr()?;
+        if self.implicit_tls {
+            let tls = self.tls(tcp).await?;
+            return self
+                .session(&mut BufReader::new(tls), local, true, message)
+                .await;
+        }
+        let mut conn = BufReader::new(tcp);
+        expect(&mut conn, self.timeout, "the greeting", 220).await?;
+        let extensions = command(&mut conn, self.timeout, &ehlo(local), "EHLO", 250).await?;
+        if extensions
+            .iter()
+            .any(|ext| ext.eq_ignore_ascii_case("STARTTLS"))
+        {
+            command(&mut conn, self.timeout, "STARTTLS", "STARTTLS", 220).await?;
+            let tls = self.tls(conn.into_inner()).await?;
+            return self
+                .session(&mut BufReader::new(tls), local, false, message)
+                .await;
+        }
+        if self.login.is_some() {
+            eyre::bail!(
+                "{} doesn't offer STARTTLS, not sending the password in the clear",
+                self.host
+            );
+        }
+        self.session(&mut conn, local, false, message).await
+    }
+
+    async fn tls(
+        &self,
+        tcp: TcpStream,
+    ) -> eyre::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let name = ServerName::try_from(self.host.clone())?;
+        let tls = TlsConnector::from(Arc::new(config)).connect(name, tcp);
+        Ok(tokio::time::timeout(self.timeout, tls)
+            .await
+            .map_err(|_| eyre::eyre!("timed out starting tls with {}", self.host))??)
+    }
+
+    // everything after the connection is set up: logging in and handing over the message
+    async fn session<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        conn: &mut BufReader<S>,
+        local: SocketAddr,
+        greeting: bool,
+        message: &[u8],
+    ) -> eyre::Result<()> {
+        let timeout = self.timeout;
+        if greeting {
+            expect(conn, timeout, "the greeting", 220).await?;
+        }
+        // after STARTTLS everything said before is forgotten
+        command(conn, timeout, &ehlo(local), "EHLO", 250).await?;
+        if let Some((user, password)) = &self.login {
+            let plain = BASE64.encode(format!("\0{user}\0{password}"));
+            command(conn, timeout, &format!("AUTH PLAIN {plain}"), "AUTH", 235).await?;
+        }
+        let from = format!("MAIL FROM:<{}>", self.from);
+        command(conn, timeout, &from, "MAIL FROM", 250).await?;
+        for to in &self.to {
+            command(conn, timeout, &format!("RCPT TO:<{to}>"), "RCPT TO", 250).await?;
+        }
+        command(conn, timeout, "DATA", "DATA", 354).await?;
+        // lines starting with a dot get another one, a lone dot would end the message
+        let mut data = Vec::with_capacity(message.len() + 5);
+        for line in message.split_inclusive(|b| *b == b'\n') {
+            if line.starts_with(b".") {
+                data.push(b'.');
+            }
+            data.extend(line);
+        }
+        data.extend(b".\r\n");
+        conn.get_mut().write_all(&data).await?;
+        expect(conn, timeout, "the message", 250).await?;
+        // the message is accepted at this point, an answer to QUIT doesn't matter
+        let _ = command(conn, timeout, "QUIT", "QUIT", 221).await;
+        Ok(())
+    }
+}
+
+// `EHLO` with the address the connection comes from, there's no reliable host name to give
+fn ehlo(local: SocketAddr) -> String {
+    match local {
+        SocketAddr::V4(addr) => format!("EHLO [{}]", addr.ip()),
+        SocketAddr::V6(addr) => format!("EHLO [IPv6:{}]", addr.ip()),
+    }
+}
+
+// sends a command and waits for its answer, `name` stands for it in errors so no password ends
+// up in one
+async fn command<S: AsyncRead + AsyncWrite + Unpin>(
+    conn: &mut BufReader<S>,
+    timeout: Duration,
+    line: &str,
+    name: &str,
+    code: u16,
+) -> eyre::Result<Vec<String>> {
+    conn.get_mut()
+        .write_all(format!("{line}\r\n").as_bytes())
+        .await?;
+    conn.get_mut().flush().await?;
+    expect(conn, timeout, name, code).await
+}
+
+// reads an answer, which may go over several `250-` lines, and fails unless it has `code`
+// (or, for 250, 251). returns the text of its lines.
+async fn expect<S: AsyncRead + Unpin>(
+    conn: &mut BufReader<S>,
+    timeout: Duration,
+    name: &str,
+    code: u16,
+) -> eyre::Result<Vec<String>> {
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        let n = tokio::time::timeout(timeout, conn.read_line(&mut line))
+            .await
+            .map_err(|_| eyre::eyre!("smtp server didn't answer {name} in time"))??;
+        if n == 0 {
+            eyre::bail!("smtp server hung up after {name}");
+        }
+        let line = line.trim_end();
+        let answered = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+        if answered != Some(code) && !(code == 250 && answered == Some(251)) {
+            eyre::bail!("smtp server answered `{line}` to {name}");
+        }
+        lines.push(line.get(4..).unwrap_or_default().to_string());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(lines);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    // greets, then answers every command with the next of `replies` and returns what it got.
+    // after a 354 the message up to the lone dot counts as one command.
+    async fn scripted(
+        replies: &'static [&'static str],
+    ) -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = BufReader::new(stream);
+            conn.get_mut()
+                .write_all(b"220 mock ready\r\n")
+                .await
+                .unwrap();
+            let mut got = vec![];
+            let mut data = false;
+            for reply in replies {
+                let mut received = String::new();
+                loop {
+                    let mut line = String::new();
+                    if conn.read_line(&mut line).await.unwrap() == 0 {
+                        return got;
+                    }
+                    received.push_str(&line);
+                    if !data || line == ".\r\n" {
+                        break;
+                    }
+                }
+                got.push(received);
+                data = reply.starts_with("354");
+                conn.get_mut().write_all(reply.as_bytes()).await.unwrap();
+            }
+            let mut rest = String::new();
+            let _ = conn.read_to_string(&mut rest).await;
+            got
+        });
+        (port, server)
+    }
+
+    fn mailer(port: u16, login: Option<(&str, &str)>) -> Mailer {
+        Mailer {
+            host: "127.0.0.1".into(),
+            port,
+            implicit_tls: false,
+            login: login.map(|(user, password)| (user.into(), password.into())),
+            from: "uuidump@example.com".into(),
+            to: vec!["a@example.com".into(), "b@example.com".into()],
+            output: "dump.txt".into(),
+            upload: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn message_is_handed_over_with_its_dots_stuffed() {
+        let (port, server) = scripted(&[
+            "250-mock\r\n250-SIZE 1000000\r\n250 8BITMIME\r\n",
+            "250 mock\r\n",
+            "250 ok\r\n",
+            "250 ok\r\n",
+            "251 will forward\r\n",
+            "354 go on\r\n",
+            "250 queued\r\n",
+            "221 bye\r\n",
+        ])
+        .await;
+        let message = b"Subject: x\r\n\r\n.hidden\r\n.\r\nend\r\n";
+        mailer(port, None).send(message).await.unwrap();
+        let got = server.await.unwrap();
+        // greeted again before the session like after STARTTLS
+        assert_eq!(got[..2], ["EHLO [127.0.0.1]\r\n"; 2]);
+        assert_eq!(
+            &got[2..5],
+            [
+                "MAIL FROM:<uuidump@example.com>\r\n",
+                "RCPT TO:<a@example.com>\r\n",
+                "RCPT TO:<b@example.com>\r\n"
+            ]
+        );
+        assert_eq!(got[5], "DATA\r\n");
+        assert_eq!(got[6], "Subject: x\r\n\r\n..hidden\r\n..\r\nend\r\n.\r\n");
+        assert_eq!(got[7], "QUIT\r\n");
+    }
+
+    #[tokio::test]
+    async fn answers_over_several_lines_are_read_whole() {
+        let (port, server) = scripted(&["250-mock\r\n250-PIPELINING\r\n250 AUTH PLAIN\r\n"]).await;
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut conn = BufReader::new(stream);
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            expect(&mut conn, timeout, "the greeting", 220)
+                .await
+                .unwrap(),
+            ["mock ready"]
+        );
+        let extensions = command(&mut conn, timeout, "EHLO x", "EHLO", 250)
+            .await
+            .unwrap();
+        assert_eq!(extensions, ["mock", "PIPELINING", "AUTH PLAIN"]);
+        drop(conn);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refusals_name_the_command_but_not_the_password() {
+        let (port, server) = scripted(&["250 mock\r\n", "535 5.7.8 bad credentials\r\n"]).await;
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let local = stream.local_addr().unwrap();
+        let e = mailer(port, Some(("user", "hunter2")))
+            .session(&mut BufReader::new(stream), local, true, b"")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            e,
+            "smtp server answered `535 5.7.8 bad credentials` to AUTH"
+        );
+        let got = server.await.unwrap();
+        assert_eq!(
+            got[1],
+            format!("AUTH PLAIN {}\r\n", BASE64.encode("\0user\0hunter2"))
+        );
+    }
+
+    #[tokio::test]
+    async fn passwords_are_not_sent_without_tls() {
+        let (port, _server) = scripted(&["250-mock\r\n250 AUTH PLAIN\r\n"]).await;
+        let e = mailer(port, Some(("user", "hunter2")))
+            .send(b"")
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("doesn't offer STARTTLS"), "{e}");
+    }
+}
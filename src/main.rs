@@ -1,30 +1,402 @@
-#![feature(iter_array_chunks)]
 #![warn(clippy::nursery, clippy::pedantic)]
 
-use bpaf::Bpaf;
-use lazy_static::lazy_static;
-use serde_json::json;
-use std::collections::HashSet;
-use std::io::{Write, stdout};
+use bpaf::{Bpaf, Parser, construct, long};
+use eyre::WrapErr;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::io::{Write, stderr};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::thread::{sleep, spawn};
-use std::time::Duration;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::sync::{Mutex, watch};
 use uuid::Uuid;
 
+// progress messages that `--quiet` leaves out, unlike warnings and errors
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !crate::term::is_quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+mod arrow;
+
+mod backend;
+use backend::{Backend, BackendSpec, Group, build_backends, health_check, request, request_single};
+
+mod bloom;
+use bloom::Bloom;
+
+mod cache;
+use cache::{Answer, Cache};
+
+mod state;
+use state::State;
+
+mod pairs;
+use pairs::Pairs;
+
+mod capes;
+
+mod clock;
+
+mod control;
+
+mod convert;
+use control::Control;
+use convert::DumpFormat;
+
+mod dedup;
+use dedup::Dedup;
+
+mod dns;
+use dns::HostOverride;
+
+mod droptime;
+use droptime::Droptimes;
+
+mod generate;
+use generate::{Count, Model};
+
+mod hook;
+use hook::{Exec, Mutator, ResultFilter, Verdict};
+
+mod hypixel;
+
+mod ignore;
+use ignore::{BadLines, IgnoreSource, Ignored};
+
+mod journal;
+
+mod keyspace;
+use keyspace::Position;
+
+mod latency;
+
+mod limits;
+
+mod mail;
+use mail::Mailer;
+
+mod meta;
+use meta::RunMeta;
+
+mod msgpack;
+
+mod pattern;
+use pattern::Pattern;
+
+mod probe;
+
+mod progress;
+use progress::{Progress, StatusStream, StatusTarget};
+
+mod proxy;
+use proxy::ProxySpec;
+
+mod rank;
+
+mod recording;
+
+mod s3;
+
+mod retry;
+use retry::{ErrorClass, RetryOverride, RetryPolicies, error_count, error_summary};
+
+mod trace;
+
+mod session;
+
+mod sink;
+use sink::{OutputFormat, Sink, SinkSpec, Timestamps};
+
+mod systemd;
+
+mod term;
+
+mod tor;
+use term::{Color, ProgressStyle, ResultStream, clear_line};
+
+mod stats;
+mod throttle;
+use throttle::{Breaker, Throttle};
+
+mod transliterate;
+use transliterate::{Rules, Scheme};
+
+mod upload;
+use upload::{UploadMethod, Uploader};
+
+mod wordlist;
+use wordlist::{CleanOptions, Follower, Order, WordlistSpec, Words};
+
+// `uuidump stats <dump>` looks at an existing dump and `convert` rewrites it, `keyspace`,
+// `generate` and `rank` help with the wordlist, anything else is a run
 #[derive(Debug, Clone, Bpaf)]
 #[bpaf(options)]
+enum Command {
+    /// report counts, duplicates, uuid versions and name statistics for a dump.
+    #[bpaf(command("stats"))]
+    Stats {
+        #[bpaf(
+            positional("DUMP"),
+            help("[path] text dump of uuids, one per line, optionally followed by `:name`.")
+        )]
+        dump: String,
+    },
+    /// count the candidates and requests a run would make, without making any.
+    #[bpaf(command("keyspace"))]
+    Keyspace(#[bpaf(external(keyspace_args))] KeyspaceArgs),
+    /// write names that look like the ones in a training set to stdout, e.g. to pipe into a run
+    /// with `-w /dev/stdin`.
+    #[bpaf(command("generate"))]
+    Generate(#[bpaf(external(generate_args))] GenerateArgs),
+    /// write a wordlist to stdout sorted by how much its names look like the ones in a training
+    /// set, so a run gets to the likely ones first.
+    #[bpaf(command("rank"))]
+    Rank(#[bpaf(external(rank_args))] RankArgs),
+    /// keep track of the names and uuids several scrapers running with `--dedup-server` checked
+    /// and found, so they don't repeat each other's work. it's all kept in memory, restarting the
+    /// server forgets it.
+    #[bpaf(command("dedup-server"))]
+    DedupServer {
+        #[bpaf(
+            argument("LISTEN"),
+            long("listen"),
+            fallback("127.0.0.1:8787".parse().unwrap()),
+            help("[addr:port] where to listen, e.g. `0.0.0.0:8787` to be reachable from other machines. 127.0.0.1:8787 if not given.")
+        )]
+        listen: SocketAddr,
+    },
+    /// rewrite a dump in another format, looking up names the input doesn't have if the output
+    /// needs them.
+    #[bpaf(command("convert"))]
+    Convert(#[bpaf(external(convert_args))] ConvertArgs),
+    Run(#[bpaf(external(cli), map(Box::new))] Box<Cli>),
+}
+
+// the options of a run that decide which candidates it queries
+#[derive(Debug, Clone, Bpaf)]
+struct KeyspaceArgs {
+    #[bpaf(
+        argument("WORDLIST"),
+        short('w'),
+        long("wordlist-path"),
+        some("a wordlist is needed"),
+        help("[path] the wordlist, cleaned like for a run. can be given multiple times.")
+    )]
+    wordlists: Vec<WordlistSpec>,
+    #[bpaf(
+        argument("SUFFIXES"),
+        short('s'),
+        long("suffixes"),
+        optional,
+        help("[path] list of suffixes, like for a run.")
+    )]
+    suffixes: Option<String>,
+    #[bpaf(
+        argument("PATTERN"),
+        long("pattern"),
+        many,
+        help("[template] candidate templates, like for a run. can be given multiple times.")
+    )]
+    patterns: Vec<Pattern>,
+    #[bpaf(long("allow-legacy-names"), switch, help("like for a run."))]
+    allow_legacy_names: bool,
+    #[bpaf(
+        argument("SCHEME"),
+        long("transliterate"),
+        fallback(Scheme::None),
+        help("[none|basic|german] like for a run.")
+    )]
+    transliterate: Scheme,
+    #[bpaf(
+        argument("TRANSFORM_RULES"),
+        long("transform-rules"),
+        optional,
+        help("[path] like for a run.")
+    )]
+    transform_rules: Option<String>,
+    #[bpaf(
+        argument("SHARDS"),
+        long("shards"),
+        optional,
+        help(
+            "[num] also count how many candidates land in each of this many `--shard`s. goes through every candidate, so it takes a while for big keyspaces."
+        )
+    )]
+    shards: Option<u64>,
+}
+
+// the options of `uuidump generate`
+#[derive(Debug, Clone, Bpaf)]
+struct GenerateArgs {
+    #[bpaf(
+        argument("MODEL"),
+        long("model"),
+        fallback(Model::Markov),
+        help("[markov] how the names are generated. markov if not given.")
+    )]
+    model: Model,
+    #[bpaf(
+        argument("TRAIN"),
+        long("train"),
+        help("[path] names to learn from, e.g. an existing name list. cleaned like a wordlist.")
+    )]
+    train: String,
+    #[bpaf(
+        argument("COUNT"),
+        long("count"),
+        help("[count] how many names to generate, with an optional k, m or b suffix like `10M`.")
+    )]
+    count: Count,
+    #[bpaf(
+        argument("ORDER"),
+        long("order"),
+        fallback(3),
+        help(
+            "[num] how many previous characters the markov model looks at to pick the next one. higher sticks closer to the training names. 3 if not given."
+        )
+    )]
+    order: usize,
+    #[bpaf(
+        argument("SEED"),
+        long("seed"),
+        optional,
+        help(
+            "[seed] generate the same names as an earlier run. a random seed is picked (and printed) if not given."
+        )
+    )]
+    seed: Option<u64>,
+}
+
+// the options of `uuidump rank`
+#[derive(Debug, Clone, Bpaf)]
+struct RankArgs {
+    #[bpaf(
+        argument("TRAIN"),
+        long("train"),
+        help("[path] known real names to compare against. cleaned like a wordlist.")
+    )]
+    train: String,
+    #[bpaf(
+        argument("ORDER"),
+        long("order"),
+        fallback(3),
+        help("[num] length of the character n-grams compared, minus one. 3 if not given.")
+    )]
+    order: usize,
+    #[bpaf(
+        long("with-scores"),
+        switch,
+        help(
+            "write `word<tab>score` lines that can be passed to --scores instead of just the words."
+        )
+    )]
+    with_scores: bool,
+    #[bpaf(
+        positional("WORDLIST"),
+        help("[path] the wordlist to rank, cleaned like for a run.")
+    )]
+    wordlist: String,
+}
+
+// the options of `uuidump convert`
+#[derive(Debug, Clone, Bpaf)]
+struct ConvertArgs {
+    #[bpaf(
+        argument("FROM"),
+        long("from"),
+        fallback(DumpFormat::Uuid),
+        help(
+            "[uuid|matdump|uuid:name|raw|ndjson|csv] the format of the input. `uuid` reads dashed and undashed uuids alike. uuid if not given."
+        )
+    )]
+    from: DumpFormat,
+    #[bpaf(
+        argument("TO"),
+        long("to"),
+        help(
+            "[uuid|matdump|uuid:name|raw|ndjson|csv] the format to write. `uuid` writes them dashed, `matdump` undashed, `raw` 16 bytes each. `uuid:name`, `ndjson` and `csv` have names, which are looked up on the session server if the input has none."
+        )
+    )]
+    to: DumpFormat,
+    #[bpaf(
+        argument("OUTPUT"),
+        short('o'),
+        long("output"),
+        optional,
+        help("[path] where to write the converted dump. stdout if not given.")
+    )]
+    output: Option<String>,
+    #[bpaf(
+        argument::<String>("TIMEOUT"),
+        long("timeout"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_secs(30)),
+        help("[duration] how long looking up a name may take. 30s by default.")
+    )]
+    timeout: Duration,
+    #[bpaf(positional("INPUT"), help("[path] the dump to convert."))]
+    input: String,
+}
+
+#[derive(Debug, Clone, Bpaf)]
+#[allow(clippy::struct_excessive_bools)]
 struct Cli {
     #[bpaf(
         argument("WORDLIST"),
         short('w'),
         long("wordlist-path"),
-        help("[path] the file to pull the names from. all non-mc-name characters will be nuked.")
+        help(
+            "[path] the file to pull the names from. all non-mc-name characters will be nuked. lines can be `word<tab>count`, then the most common words are queried first and the summary says how much of the total count was found. can be given multiple times, a `@weight` after the path (like `common.txt@3`) decides how the lists are combined, see --wordlist-order."
+        )
+    )]
+    wordlists: Vec<WordlistSpec>,
+    #[bpaf(
+        argument("ORDER"),
+        long("wordlist-order"),
+        fallback(Order::Priority),
+        help(
+            "[priority|interleave] how multiple wordlists are combined. `priority` goes through them by descending weight, so high-value lists are done first. `interleave` takes words from every list in proportion to its weight, e.g. three from a `@3` list for every one from a list without a weight. words in several lists are only queried once. priority (which concatenates lists with the same weight in the order they were given) if not given."
+        )
+    )]
+    wordlist_order: Order,
+    #[bpaf(
+        long("allow-legacy-names"),
+        switch,
+        help(
+            "also keep 1 and 2 character names and dashes from the wordlist, which a handful of grandfathered accounts still have."
+        )
+    )]
+    allow_legacy_names: bool,
+    #[bpaf(
+        argument("SCHEME"),
+        long("transliterate"),
+        fallback(Scheme::None),
+        help(
+            "[none|basic|german] how to turn non-ascii letters into name characters instead of deleting them. `basic` strips accents (müller -> muller, josé -> jose), `german` writes umlauts out (müller -> mueller). none if not given."
+        )
+    )]
+    transliterate: Scheme,
+    #[bpaf(
+        argument("TRANSFORM_RULES"),
+        long("transform-rules"),
+        optional,
+        help(
+            "[path] `from to` substitutions (one per line, `#` for comments) applied (ignoring case) to every word before anything else is cleaned out, e.g. `ж zh` to romanize cyrillic wordlists. none if not given."
+        )
     )]
-    wordlist_path: String,
+    transform_rules: Option<String>,
     #[bpaf(
         argument("THREADS"),
         short('t'),
@@ -33,258 +405,2711 @@ struct Cli {
         help("[num] how many threads to spawn for making requests.")
     )]
     threads: usize,
+    #[bpaf(
+        argument("MAX_CONNECTIONS"),
+        long("max-connections"),
+        optional,
+        help(
+            "[num] how many requests may be open at once across all threads, the rest wait for one to finish. capped by the open file limit (which is raised as far as allowed), like it is if not given."
+        )
+    )]
+    max_connections: Option<usize>,
     #[bpaf(
         argument("OUTPUT"),
         short('o'),
         long("output"),
-        help("[path] where to output uuids to.")
+        help(
+            "[path] where to output uuids to, a file or an `s3://<bucket>/<key>` object (see --s3-endpoint) that the output is uploaded to as it's found. `{date}`, `{time}` and `{unix}` get replaced with the (utc) start time of the run."
+        )
     )]
     output_path: String,
+    #[bpaf(
+        argument("ON_EXISTING"),
+        long("on-existing"),
+        optional,
+        help(
+            "[append|overwrite|abort] what to do if the output file already has something in it. `append` adds to it, e.g. to resume a run with --skip-keyspace. asks when run in a terminal and aborts otherwise if not given."
+        )
+    )]
+    on_existing: Option<OnExisting>,
+    #[bpaf(
+        argument("S3_ENDPOINT"),
+        long("s3-endpoint"),
+        env("AWS_ENDPOINT_URL"),
+        optional,
+        help(
+            "[url] s3 compatible store to upload an `s3://` output to, e.g. `http://localhost:9000` for minio. also read from AWS_ENDPOINT_URL. aws in --s3-region if not given."
+        )
+    )]
+    s3_endpoint: Option<String>,
+    #[bpaf(
+        argument("S3_REGION"),
+        long("s3-region"),
+        env("AWS_REGION"),
+        fallback("us-east-1".to_string()),
+        help("[region] region requests to the store are signed for, also read from AWS_REGION. us-east-1 if not given.")
+    )]
+    s3_region: String,
+    #[bpaf(
+        argument("S3_ACCESS_KEY"),
+        long("s3-access-key"),
+        env("AWS_ACCESS_KEY_ID"),
+        optional,
+        help("[key] access key id for the store, also read from AWS_ACCESS_KEY_ID.")
+    )]
+    s3_access_key: Option<String>,
+    #[bpaf(
+        argument("S3_SECRET_KEY"),
+        long("s3-secret-key"),
+        env("AWS_SECRET_ACCESS_KEY"),
+        optional,
+        help("[key] secret access key for the store, also read from AWS_SECRET_ACCESS_KEY.")
+    )]
+    s3_secret_key: Option<String>,
+    #[bpaf(
+        argument("S3_SESSION_TOKEN"),
+        long("s3-session-token"),
+        env("AWS_SESSION_TOKEN"),
+        optional,
+        help("[token] session token for temporary credentials, also read from AWS_SESSION_TOKEN.")
+    )]
+    s3_session_token: Option<String>,
+    #[bpaf(
+        argument("UPLOAD"),
+        long("upload"),
+        optional,
+        help(
+            "[url] once the run succeeded, send the output and its .meta.json to this http(s) url, e.g. for workers that are gone right after. `{file}` in the url is replaced by the file's name, otherwise the name is appended to it, and credentials in the url are sent as basic auth. nothing is uploaded if not given."
+        )
+    )]
+    upload: Option<String>,
+    #[bpaf(
+        argument("UPLOAD_METHOD"),
+        long("upload-method"),
+        fallback(UploadMethod::Put),
+        help(
+            "[put|post] how --upload sends the files, the body is the file either way. put if not given."
+        )
+    )]
+    upload_method: UploadMethod,
+    #[bpaf(
+        argument("UPLOAD_RETRIES"),
+        long("upload-retries"),
+        fallback(5),
+        help(
+            "[num] how often an --upload that failed (a network error or the server answering 408, 429 or 5xx) is tried again, waiting longer each time. the run fails if it still doesn't go through. 5 if not given."
+        )
+    )]
+    upload_retries: u32,
+    #[bpaf(
+        argument("MAIL_TO"),
+        long("mail-to"),
+        many,
+        help(
+            "[address] mail the report of the run (how long it took, what was found, errors) to this address once it finishes, is stopped or fails, with the output attached if it's at most 10 MiB or a link to where --upload put it. can be given multiple times. no mail if not given."
+        )
+    )]
+    mail_to: Vec<String>,
+    #[bpaf(
+        argument("MAIL_FROM"),
+        long("mail-from"),
+        optional,
+        help(
+            "[address] who the --mail-to report is from. the --smtp user if not given, if that is an address."
+        )
+    )]
+    mail_from: Option<String>,
+    #[bpaf(
+        argument("SMTP"),
+        long("smtp"),
+        fallback("smtp://localhost".to_string()),
+        help(
+            "[url] the mail server --mail-to goes through. `smtp://host[:port]` (25 by default) switches to tls if the server offers it, `smtps://host[:port]` (465 by default) uses tls from the start, and `smtp://user@host` logs in with --smtp-password (only over tls). smtp://localhost if not given."
+        )
+    )]
+    smtp: String,
+    #[bpaf(
+        argument("SMTP_PASSWORD"),
+        long("smtp-password"),
+        env("UUIDUMP_SMTP_PASSWORD"),
+        optional,
+        help("[password] the password for the --smtp user, also read from UUIDUMP_SMTP_PASSWORD.")
+    )]
+    smtp_password: Option<String>,
+    #[bpaf(
+        argument("OUTPUT_FORMAT"),
+        long("output-format"),
+        fallback(OutputFormat::Plain),
+        help(
+            "[plain|matdump|raw|names|msgpack|arrow] how uuids are written to the output and `file:` sinks. `matdump` writes them undashed like the public uuid dumps, so results can be merged into them. `raw` writes 16 bytes per uuid (big-endian) with no separators, half the size of text. `names` writes the found names as the api spells them instead, e.g. for a wordlist of names that are taken. `msgpack` writes a map of `uuid` (16 bytes), `name` and with --timestamps `found_at` per hit, back to back, for tools that want records without parsing json. `arrow` writes an arrow ipc stream with `uuid`, `name` and with --timestamps `found_at` columns and a record batch whenever hits are written out, so polars and the like can read it, even while the run is going. plain (dashed) if not given."
+        )
+    )]
+    output_format: OutputFormat,
+    #[bpaf(
+        argument("TIMESTAMPS"),
+        long("timestamps"),
+        optional,
+        help(
+            "[iso|unix] add the time each uuid was found to its line in the output and `file:` sinks, after a tab, as `2024-05-17T13:37:00Z` or unix seconds. can't be combined with --output-format raw. no timestamps if not given."
+        )
+    )]
+    timestamps: Option<Timestamps>,
+    #[bpaf(
+        long("sort-output"),
+        switch,
+        help("sort and deduplicate the output file once the run is done.")
+    )]
+    sort_output: bool,
+    #[bpaf(
+        argument("SINK"),
+        long("sink"),
+        many,
+        help(
+            "[file:<path>|sqlite:<path>|webhook:<url>|hypixel:<path>|capes:<path>|map:<path>] somewhere else to send every found uuid as well, e.g. `sqlite:found.db` (a `uuids` table with uuid, name and found_at), a webhook that gets json arrays of `{uuid, name}` posted a json lines file of every player's hypixel first and last login and network level, a json lines file of the cape texture url of every player (null without one), or `map:found.tsv`, `uuid<tab>name` lines sorted by uuid once the run is done plus `found.by-name.tsv` with `name<tab>uuid` sorted by name, for lookups both ways. can be given multiple times."
+        )
+    )]
+    sinks: Vec<SinkSpec>,
+    #[bpaf(
+        argument("HYPIXEL_KEY"),
+        long("hypixel-key"),
+        env("HYPIXEL_API_KEY"),
+        optional,
+        help("[key] hypixel api key for `--sink hypixel:<path>`, also read from HYPIXEL_API_KEY.")
+    )]
+    hypixel_key: Option<String>,
+    #[bpaf(
+        long("only-caped"),
+        switch,
+        help("only write players that wear a cape to `--sink capes:<path>`.")
+    )]
+    only_caped: bool,
+    #[bpaf(
+        argument("PAIRS"),
+        long("pairs"),
+        optional,
+        help(
+            "[path] also write `name,offline uuid,premium uuid` for every name that got an answer, with the premium uuid left empty for names nobody owns. meant for moving an offline-mode server's player data to online mode."
+        )
+    )]
+    pairs: Option<String>,
+    #[bpaf(
+        argument("DROPTIMES"),
+        long("droptimes"),
+        optional,
+        help(
+            "[path] check every name that came back without an owner with --droptime-url and append `{name, status, droptime, checked_at}` json lines for the ones that can be claimed. the status is `never_registered`, `grace` (released less than 37 days ago, claimable at the unix time in droptime), `released` (past the grace window) or just `available` if the service doesn't tell. not checked if not given."
+        )
+    )]
+    droptimes: Option<String>,
+    #[bpaf(
+        argument("DROPTIME_URL"),
+        long("droptime-url"),
+        optional,
+        help(
+            "[url] droptime service for --droptimes, with `{name}` in place of the name. it should answer with json with a `droptime` in unix seconds (null if the name can be claimed now) and optionally `released_at` and `registered`, or 404 if it can't be claimed."
+        )
+    )]
+    droptime_url: Option<String>,
     #[bpaf(
         argument("IGNORED"),
         short('i'),
         long("ignored-uuids"),
-        optional,
+        many,
         help(
-            "[path] which uuids to ignore if found. useful in combination with one of mats uuid dumps. if not given, don't ignore any uuids."
+            "[path[:truncation]|sqlite:<path>] which uuids to ignore if found. useful in combination with one of mats uuid dumps. can be given multiple times, each with its own truncation (e.g. `laby.txt:8`) or -r otherwise. `sqlite:known.db` looks every hit up in the `uuids` table of a database (like `--sink sqlite:` writes) instead of loading it, for lists too big for memory. if not given, don't ignore any uuids."
         )
     )]
-    ignored: Option<String>,
+    ignored: Vec<IgnoreSource>,
     #[bpaf(
         argument("IGNORED_TRUNCATION"),
         short('r'),
         long("ignored-truncation"),
         optional,
         help(
-            "[num] amount of hex digits to keep from from the ignored uuids (8 for laby). no truncation if not given."
+            "[num] amount of hex digits to keep from from the ignored uuids (8 for laby), for ignore files without their own. shorter entries match every uuid starting with them. no truncation if not given."
         )
     )]
     ignored_truncation: Option<usize>,
     #[bpaf(
-        argument("SUFFIXES"),
-        short('s'),
-        long("suffixes"),
+        argument("MAX_BAD_IGNORED"),
+        long("max-bad-ignored"),
+        fallback(0.01),
+        help(
+            "[fraction] share of lines in the ignore lists that may not be uuids. they're skipped with a warning, more of them abort the run since the list is probably not what it should be. 0 aborts on the first one. 0.01 if not given."
+        )
+    )]
+    max_bad_ignored: f64,
+    #[bpaf(
+        argument("IGNORED_REJECTS"),
+        long("ignored-rejects"),
         optional,
         help(
-            "[path] list of suffixes to append to each word in the wordlist. words with no suffixes will not be kept. no suffixing if not given."
+            "[path] file to write the lines of the ignore lists that aren't uuids to, to fix them up. not written if not given."
         )
     )]
-    suffixes: Option<String>,
+    ignored_rejects: Option<String>,
     #[bpaf(
-        short('a'),
-        long("print-ignored"),
+        long("reload-ignored"),
         switch,
-        help("whether to print ignored uuids in a gray color.")
+        help(
+            "check the ignored uuid files for changes every 10s and merge new entries in while running. they're always reloaded on sighup."
+        )
     )]
-    print_ignored: bool,
+    reload_ignored: bool,
+    #[bpaf(
+        argument("IGNORED_NAMES"),
+        short('n'),
+        long("ignored-names"),
+        optional,
+        help(
+            "[path] names to skip querying entirely, matched case-insensitively against the suffixed candidates. if not given, query every candidate."
+        )
+    )]
+    ignored_names: Option<String>,
+    #[bpaf(
+        argument::<String>("EXCLUDE"),
+        long("exclude"),
+        parse(|s| Regex::new(&s)),
+        many,
+        help(
+            "[regex] skip every suffixed candidate matching this pattern, e.g. `bot[0-9]+$`. can be given multiple times. no candidates skipped if not given."
+        )
+    )]
+    exclude: Vec<Regex>,
+    #[bpaf(
+        argument::<String>("INCLUDE"),
+        long("include"),
+        parse(|s| Regex::new(&s)),
+        many,
+        help(
+            "[regex] only query suffixed candidates matching this pattern (or any of them if given multiple times), e.g. `^[a-z]{4}$`. every candidate is kept if not given."
+        )
+    )]
+    include: Vec<Regex>,
+    #[bpaf(
+        argument("SHARD"),
+        long("shard"),
+        optional,
+        help(
+            "[index/count] only query the candidates whose name hashes to this shard, e.g. `3/8` (indices start at 0), to split one wordlist across machines without overlap. every candidate if not given."
+        )
+    )]
+    shard: Option<Shard>,
+    #[bpaf(
+        argument("SAMPLE"),
+        long("sample"),
+        optional,
+        help(
+            "[ratio] only query a random share of the candidates, like `5%` or `0.05`, and estimate how many uuids the full run would find. every candidate if not given."
+        )
+    )]
+    sample: Option<Sample>,
+    #[bpaf(
+        argument("SAMPLE_SEED"),
+        long("sample-seed"),
+        optional,
+        help(
+            "[seed] pick the same --sample as an earlier run. a random seed is picked (and printed) if not given."
+        )
+    )]
+    sample_seed: Option<u64>,
+    #[bpaf(
+        argument("SUFFIXES"),
+        short('s'),
+        long("suffixes"),
+        optional,
+        help(
+            "[path] list of suffixes to append to each word in the wordlist. words with no suffixes will not be kept. no suffixing if not given."
+        )
+    )]
+    suffixes: Option<String>,
+    #[bpaf(
+        argument("SUFFIX_MAX_LENGTH"),
+        long("suffix-max-length"),
+        optional,
+        help(
+            "[num] only append suffixes to words (after --pattern) of at most this many characters, longer ones are queried as they are. e.g. 12 keeps 10 character words from turning into 16+ character candidates that can't be names. suffixes for every word if not given."
+        )
+    )]
+    suffix_max_length: Option<usize>,
+    #[bpaf(
+        argument::<String>("SUFFIX_WORDS"),
+        long("suffix-words"),
+        parse(|s| Regex::new(&s)),
+        optional,
+        help(
+            "[regex] only append suffixes to words (after --pattern) matching this pattern, the others are queried as they are. e.g. `^[a-z]+$` to leave words that already end in digits alone. suffixes for every word if not given."
+        )
+    )]
+    suffix_words: Option<Regex>,
+    #[bpaf(
+        argument("MUTATE"),
+        long("mutate"),
+        optional,
+        help(
+            "[command] program (run by the shell) that turns every word into the words to use instead, for mutations the other options can't do. it's started once, gets a word per line on stdin and answers each with any number of words, one per line, then an empty line (and flushes its output). patterns and suffixes apply to its words. e.g. `python3 leet.py`. words are used as they are if not given."
+        )
+    )]
+    mutate: Option<String>,
+    #[bpaf(
+        argument("FILTER"),
+        long("filter"),
+        optional,
+        help(
+            "[command] program (run by the shell) that decides where every hit that isn't ignored goes, e.g. only names that are dictionary words. it's started once, gets `uuid<tab>name` per line on stdin and answers each with a line (flushing its output): `keep` for everywhere, `drop` for nowhere, or comma separated output numbers, 0 for -o and 1 on for the --sinks in the order given. dropped hits aren't counted as found. every hit goes everywhere if not given."
+        )
+    )]
+    filter: Option<String>,
+    #[bpaf(
+        argument("EXEC"),
+        long("exec"),
+        optional,
+        help(
+            "[command] run this (by the shell) for every hit that isn't ignored, with `{uuid}`, `{uuid_simple}` (undashed) and `{name}` replaced by the quoted values (so don't quote them yourself), e.g. `notify-send {name}` or `curl -d {uuid} https://example.com/hit`. they're also in the UUIDUMP_UUID, UUIDUMP_UUID_SIMPLE and UUIDUMP_NAME environment variables. the run waits for the last ones before it ends. no commands if not given."
+        )
+    )]
+    exec: Option<String>,
+    #[bpaf(
+        argument("EXEC_CONCURRENCY"),
+        long("exec-concurrency"),
+        fallback(4),
+        help(
+            "[num] how many --exec commands may run at once, hits wait for a free one. 4 if not given."
+        )
+    )]
+    exec_concurrency: usize,
+    #[bpaf(
+        argument("EXEC_RPS"),
+        long("exec-rps"),
+        optional,
+        help("[num] how many --exec commands may be started per second. unlimited if not given.")
+    )]
+    exec_rps: Option<f64>,
+    #[bpaf(
+        argument("PATTERN"),
+        long("pattern"),
+        many,
+        help(
+            "[template] turn every word into candidates with a template, e.g. `xX{word}Xx` or `{word}_{yy}`. `{N-M}` stands for every number from N to M (zero-padded like N, so `{00-99}`), `{yy}` for `{00-99}`. can be given multiple times, suffixes are appended to every candidate. just `{word}` if not given."
+        )
+    )]
+    patterns: Vec<Pattern>,
+    #[bpaf(
+        argument("SKIP_KEYSPACE"),
+        long("skip-keyspace"),
+        fallback(0),
+        help(
+            "[num] skip this many candidates (in the order they're generated from the wordlist, patterns and suffixes) before querying, to resume a run from the keyspace position it reported or to split a keyspace by ranges. 0 by default."
+        )
+    )]
+    skip_keyspace: u64,
+    #[bpaf(
+        argument("JOURNAL"),
+        long("journal"),
+        optional,
+        help(
+            "[path] file to write down every word that's done in, once all its candidates got an answer and the hits are written out, for --resume. words are kept by their hash, so it doesn't matter where in the wordlist they are. starts over unless --resume is given. `<output>.journal` with --resume and none otherwise if not given."
+        )
+    )]
+    journal: Option<String>,
+    #[bpaf(
+        long("resume"),
+        switch,
+        help(
+            "skip the words the journal (see --journal) says earlier runs did and keep adding to it. the output is appended to unless --on-existing says otherwise."
+        )
+    )]
+    resume: bool,
+    #[bpaf(
+        short('a'),
+        long("print-ignored"),
+        switch,
+        help("whether to print ignored uuids too, in gray where colors are on (see --color).")
+    )]
+    print_ignored: bool,
+    #[bpaf(
+        argument("COLOR"),
+        long("color"),
+        fallback(Color::Auto),
+        help(
+            "[auto|always|never] whether to color output, like the gray of ignored uuids. `auto` colors terminals unless the NO_COLOR environment variable is set, so piped output stays free of escape codes. auto if not given."
+        )
+    )]
+    color: Color,
+    #[bpaf(
+        argument("PROGRESS"),
+        long("progress"),
+        fallback(ProgressStyle::Auto),
+        help(
+            "[auto|tty|plain] how to show progress. `tty` redraws a status line in place, `plain` prints it as a line of its own every 10s without escape codes, for docker and ci logs. tty if stderr is a terminal and plain otherwise if not given."
+        )
+    )]
+    progress: ProgressStyle,
+    #[bpaf(
+        argument("PRINT_RESULTS"),
+        long("print-results"),
+        fallback(ResultStream::Stdout),
+        help(
+            "[stdout|stderr|none] where to print found uuids while running. the status line and warnings always go to stderr. stdout if not given."
+        )
+    )]
+    print_results: ResultStream,
+    #[bpaf(
+        short('q'),
+        long("quiet"),
+        switch,
+        help(
+            "print nothing but warnings and errors: no found uuids, no status line and no summary, e.g. for cron."
+        )
+    )]
+    quiet: bool,
+    #[bpaf(
+        argument("ONLY_VERSION"),
+        long("only-version"),
+        optional,
+        help(
+            "[num] only keep found uuids of this version, e.g. 4 to drop offline-mode (v3) uuids that made it into a mirror. the versions found are listed at the end either way. every version if not given."
+        )
+    )]
+    only_version: Option<usize>,
+    #[bpaf(
+        long("follow"),
+        switch,
+        help(
+            "keep watching the wordlist after going through it and query every name appended to it, like `tail -f`. runs until stopped."
+        )
+    )]
+    follow: bool,
+    #[bpaf(external(shuffle))]
+    shuffle: Option<u64>,
+    #[bpaf(
+        argument("SCORES"),
+        long("scores"),
+        optional,
+        help(
+            "[path] `word<tab>score` lines, words are queried from the highest score down and unscored words come last. can't be combined with --shuffle. wordlist order if not given."
+        )
+    )]
+    scores: Option<String>,
+    #[bpaf(
+        argument("CANDIDATE_MEMORY"),
+        long("candidate-memory"),
+        fallback(64),
+        help(
+            "[mib] memory for recognizing candidates that patterns or suffixes produce more than once (like `cool` + `123` and `cool123` + nothing), which are only queried the first time. at most 1 in 1000 unique candidates is taken for a duplicate, and once the memory is full (a warning says when) duplicates are queried again rather than skip more. 0 queries duplicates too. 64 if not given."
+        )
+    )]
+    candidate_memory: u64,
+    #[bpaf(
+        long("prioritize-short"),
+        switch,
+        help(
+            "query the shortest words first, so the 3 and 4 character names of a big mixed wordlist come out early. words of the same length keep their order, including one from --shuffle or --scores."
+        )
+    )]
+    prioritize_short: bool,
+    #[bpaf(
+        long("external-dedup"),
+        switch,
+        help(
+            "sort and deduplicate the wordlist on disk instead of in memory. always done for wordlists bigger than 1gib, can't be combined with --shuffle, --scores or --prioritize-short."
+        )
+    )]
+    external_dedup: bool,
+    #[bpaf(
+        argument("TMP_DIR"),
+        long("tmp-dir"),
+        optional,
+        help(
+            "[path] where to keep the temporary files of the on-disk deduplication. the system temp directory if not given."
+        )
+    )]
+    tmp_dir: Option<String>,
+    #[bpaf(
+        argument("JITTER"),
+        long("jitter"),
+        optional,
+        help(
+            "[duration range] random delay each thread waits between requests, e.g. `50ms..200ms` or `1s`. no delay if not given."
+        )
+    )]
+    jitter: Option<DurationRange>,
+    #[bpaf(
+        argument("SLOWDOWN_THRESHOLD"),
+        long("slowdown-threshold"),
+        fallback(0.2),
+        help(
+            "[ratio] error/429 ratio over the last 100 requests above which all threads slow down, recovering gradually once it drops. 1 to never slow down."
+        )
+    )]
+    slowdown_threshold: f64,
+    #[bpaf(
+        argument("BREAKER_THRESHOLD"),
+        long("breaker-threshold"),
+        fallback(20),
+        help(
+            "[num] consecutive failed requests after which all threads pause and a single probe request is retried with an increasing cool-down until the endpoint recovers. 0 to never pause."
+        )
+    )]
+    breaker_threshold: usize,
+    #[bpaf(
+        long("skip-health-check"),
+        switch,
+        help(
+            "don't check that the endpoint works with a request for a known name before starting."
+        )
+    )]
+    skip_health_check: bool,
+    #[bpaf(
+        long("probe"),
+        switch,
+        help(
+            "spend the first minute measuring the request and error rates at doubling thread counts up to -t with random names, then continue with the thread count that got the most requests through."
+        )
+    )]
+    probe: bool,
+    #[bpaf(
+        long("probe-only"),
+        switch,
+        help("like --probe, but exit after printing the measurements.")
+    )]
+    probe_only: bool,
+    #[bpaf(
+        argument("LOCAL_ADDR"),
+        long("local-addr"),
+        many,
+        help(
+            "[ip] local address to send requests from. can be given multiple times to spread the threads across addresses. uses the default route if not given."
+        )
+    )]
+    local_addrs: Vec<IpAddr>,
+    #[bpaf(
+        argument("RESOLVE"),
+        long("resolve"),
+        many,
+        help(
+            "[host:ip] connect to this address for a host instead of looking it up, like curl's --resolve, e.g. to reach one replica of a mirror. can be given multiple times, for more hosts or more addresses of one. every host is looked up if not given."
+        )
+    )]
+    resolve: Vec<HostOverride>,
+    #[bpaf(
+        argument::<String>("DNS_TTL"),
+        long("dns-ttl"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_mins(1)),
+        help(
+            "[duration] how long a looked up address is used by every thread, instead of asking the system resolver for every new connection. 0 to always ask. 1m by default."
+        )
+    )]
+    dns_ttl: Duration,
+    #[bpaf(
+        argument("PROXY"),
+        long("proxy"),
+        many,
+        help(
+            "[url] send requests through a proxy, `http://`, `https://`, `socks5://` or `socks5h://` (names resolved by the proxy), with `user:pass@` in front of the host for credentials. can be given multiple times to spread the threads across proxies. no proxy if not given."
+        )
+    )]
+    proxies: Vec<ProxySpec>,
+    #[bpaf(
+        argument("PROXY_LIST"),
+        long("proxy-list"),
+        optional,
+        help(
+            "[path] proxies to spread the threads across, one per line as a url like for --proxy or as `host:port:user:pass` for http proxies. blank lines and lines starting with # are skipped."
+        )
+    )]
+    proxy_list: Option<String>,
+    #[bpaf(
+        argument("PROXY_USER"),
+        long("proxy-user"),
+        optional,
+        help("[user] username for the proxies that don't have credentials of their own.")
+    )]
+    proxy_user: Option<String>,
+    #[bpaf(
+        argument("PROXY_PASSWORD"),
+        long("proxy-password"),
+        env("UUIDUMP_PROXY_PASSWORD"),
+        optional,
+        help("[password] password for --proxy-user, also read from UUIDUMP_PROXY_PASSWORD.")
+    )]
+    proxy_password: Option<String>,
+    #[bpaf(
+        argument("PROXY_RPS"),
+        long("proxy-rps"),
+        optional,
+        help(
+            "[num] requests per second through each proxy (or tor circuit), so every exit stays under the mirror's per-ip limit on its own. not limited if not given."
+        )
+    )]
+    proxy_rps: Option<f64>,
+    #[bpaf(
+        long("tor"),
+        switch,
+        help(
+            "send requests through tor, every thread on a circuit of its own. tor is asked for new circuits when requests keep getting rate limited, if its control port can be used."
+        )
+    )]
+    tor: bool,
+    #[bpaf(
+        argument("TOR_SOCKS"),
+        long("tor-socks"),
+        fallback("127.0.0.1:9050".to_string()),
+        help("[host:port] tor's socks port for --tor. 127.0.0.1:9050 if not given.")
+    )]
+    tor_socks: String,
+    #[bpaf(
+        argument("TOR_CONTROL"),
+        long("tor-control"),
+        fallback("127.0.0.1:9051".to_string()),
+        help(
+            "[host:port] tor's control port for --tor, authenticated with the cookie file if there's no --tor-password. 127.0.0.1:9051 if not given."
+        )
+    )]
+    tor_control: String,
+    #[bpaf(
+        argument("TOR_PASSWORD"),
+        long("tor-password"),
+        env("TOR_CONTROL_PASSWORD"),
+        optional,
+        help("[password] password for tor's control port, also read from TOR_CONTROL_PASSWORD.")
+    )]
+    tor_password: Option<String>,
+    #[bpaf(
+        argument("MODE"),
+        long("mode"),
+        fallback(Mode::Api),
+        help(
+            "[api|offline-uuid] `offline-uuid` computes the uuid an offline-mode server gives every name (from `OfflinePlayer:<name>`) instead of querying the backend, so no requests are made. names keep the case they have in the wordlist, since it changes the uuid (`--transform-rules` lowercase them though). can't be combined with --cache, --state, --dedup-server, --pairs or --probe, which keep or need real answers. api if not given."
+        )
+    )]
+    mode: Mode,
+    #[bpaf(
+        argument("BACKEND"),
+        long("backend"),
+        many,
+        help(
+            "[url] the mowojang-compatible endpoint to query, or `fixture:<path>` to answer from a json file of profiles instead of the network. can be given multiple times to query several at once. `,concurrency=<n>` after the url gives it n request threads of its own instead of a share of --threads, `,rps=<n>` holds it to n requests per second, e.g. `https://mirror.local,concurrency=64` next to `https://api.example,rps=5`. defaults to the public mowojang instance."
+        )
+    )]
+    backends: Vec<BackendSpec>,
+    #[bpaf(
+        argument("RECORD"),
+        long("record"),
+        optional,
+        help(
+            "[dir] append every request and the answer (or error) it got to `requests.jsonl` in this directory, to be replayed with --replay. nothing is recorded if not given."
+        )
+    )]
+    record: Option<String>,
+    #[bpaf(
+        argument("REPLAY"),
+        long("replay"),
+        optional,
+        help(
+            "[dir] answer requests from a --record directory instead of the backend, retries getting the recorded answers in order. requests that weren't recorded fail. queries the backend if not given."
+        )
+    )]
+    replay: Option<String>,
+    #[bpaf(
+        long("insecure"),
+        switch,
+        help("accept invalid tls certificates (e.g. self-signed) from the backend.")
+    )]
+    insecure: bool,
+    #[bpaf(
+        argument("CA_CERT"),
+        long("ca-cert"),
+        optional,
+        help("[path] additional pem ca certificate to trust, for backends behind a private ca.")
+    )]
+    ca_cert: Option<String>,
+    #[bpaf(
+        argument("CACHE"),
+        long("cache"),
+        optional,
+        help(
+            "[path] directory to keep every answer (including not found) in, so later runs can skip names that were already queried. no caching if not given."
+        )
+    )]
+    cache: Option<String>,
+    #[bpaf(
+        argument("STATE"),
+        long("state"),
+        optional,
+        help(
+            "[path] sqlite database recording every name ever queried with its answer and when it was given, names answered within the cache ttls are skipped by later runs. unlike --cache it isn't loaded into memory. no state if not given."
+        )
+    )]
+    state: Option<String>,
+    #[bpaf(
+        argument::<String>("CACHE_TTL"),
+        long("cache-ttl"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_hours(7 * 24)),
+        help("[duration] how old cached hits may be before their names get queried again, e.g. `12h` or `30d`. 7d by default.")
+    )]
+    cache_ttl: Duration,
+    #[bpaf(
+        argument::<String>("NEGATIVE_CACHE_TTL"),
+        long("negative-cache-ttl"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_hours(7 * 24)),
+        help("[duration] how old cached not-found answers may be before their names get queried again. 7d by default.")
+    )]
+    negative_cache_ttl: Duration,
+    #[bpaf(
+        argument("DEDUP_SERVER"),
+        long("dedup-server"),
+        optional,
+        help(
+            "[url] a `uuidump dedup-server` shared with other scrapers. names someone already checked are skipped and only uuids nobody found before get written. no sharing if not given."
+        )
+    )]
+    dedup_server: Option<String>,
+    #[bpaf(
+        argument::<String>("TIMEOUT"),
+        long("timeout"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_secs(30)),
+        help("[duration] how long a single request may take before it counts as timed out. 30s by default.")
+    )]
+    timeout: Duration,
+    #[bpaf(
+        argument("RETRY"),
+        long("retry"),
+        many,
+        help(
+            "[class=retries[:backoff]] how often to retry a failed batch per kind of error, with the backoff doubling on every retry, e.g. `5xx=5:2s`. classes are timeout (3:1s), connect (3:2s), 429 (5:5s), 4xx (0), 5xx (3:1s), json (1:500ms) and other (1:500ms). can be given multiple times."
+        )
+    )]
+    retry: Vec<RetryOverride>,
+    #[bpaf(
+        argument("TRACE_HTTP"),
+        long("trace-http"),
+        optional,
+        help(
+            "[fraction] log full request and response bodies for this fraction of requests (e.g. 0.01 for every 100th, 1 for all). no tracing if not given."
+        )
+    )]
+    trace_http: Option<f64>,
+    #[bpaf(
+        argument("TRACE_FILE"),
+        long("trace-file"),
+        optional,
+        help("[path] file to append traced requests to instead of stderr.")
+    )]
+    trace_file: Option<String>,
+    #[bpaf(
+        argument("LATENCY_LOG"),
+        long("latency-log"),
+        optional,
+        help(
+            "[path] file to append a `unix ms,batch size,status,duration ms` line to for every request. no log if not given."
+        )
+    )]
+    latency_log: Option<String>,
+    #[bpaf(
+        argument("PROGRESS_WEBHOOK"),
+        long("progress-webhook"),
+        optional,
+        help(
+            "[url] post a json progress snapshot (counts, rates, eta) to this url every --progress-interval, and once more when the run is done. no progress posts if not given."
+        )
+    )]
+    progress_webhook: Option<String>,
+    #[bpaf(
+        argument::<String>("PROGRESS_INTERVAL"),
+        long("progress-interval"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_mins(5)),
+        help("[duration] how often to post to --progress-webhook. 5m by default.")
+    )]
+    progress_interval: Duration,
+    #[bpaf(
+        argument("STATUS_FD"),
+        long("status-fd"),
+        optional,
+        help(
+            "[fd] write the progress snapshot (like for --progress-webhook) as a line of json to this already open file descriptor every --status-interval, and once more when the run is done. e.g. `--status-fd 3 3>status.jsonl`, or a pipe a wrapper reads. unix only. no status stream if not given."
+        )
+    )]
+    status_fd: Option<i32>,
+    #[bpaf(
+        argument("STATUS_JSON"),
+        long("status-json"),
+        optional,
+        help(
+            "[path] like --status-fd, but appends the lines to this file (or fifo). no status stream if not given."
+        )
+    )]
+    status_json: Option<String>,
+    #[bpaf(
+        argument::<String>("STATUS_INTERVAL"),
+        long("status-interval"),
+        parse(|s| parse_duration(&s)),
+        fallback(Duration::from_secs(1)),
+        help("[duration] how often to write to --status-fd or --status-json. 1s by default.")
+    )]
+    status_interval: Duration,
+    #[bpaf(
+        argument("MAX_ERROR_RATE"),
+        long("max-error-rate"),
+        optional,
+        help(
+            "[ratio] stop the run (with exit code 3) once more than this fraction of requests failed, checked after the first 100. never stops if not given."
+        )
+    )]
+    max_error_rate: Option<f64>,
+    #[bpaf(
+        argument("CONTROL_SOCKET"),
+        long("control-socket"),
+        optional,
+        help(
+            "[path] unix socket to take line commands on while running: status (a json snapshot), pause, resume, set-rps <n> (0 for unlimited) and stop. no socket if not given."
+        )
+    )]
+    control_socket: Option<String>,
+}
+
+// `--shuffle` takes an optional seed, so it's either `--shuffle 42` or a bare `--shuffle`
+fn shuffle() -> impl Parser<Option<u64>> {
+    let seeded = long("shuffle")
+        .help(
+            "[seed] randomize the order of the wordlist before distributing it to the threads. a random seed is picked (and printed) if not given.",
+        )
+        .argument::<u64>("SEED");
+    let unseeded = long("shuffle").req_flag(()).map(|()| random_seed()).hide();
+    construct!([seeded, unseeded]).optional()
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+const MOWOJANG: &str = "https://mowojang.matdoes.dev";
+// found uuids waiting for the handler. once it's full the request threads wait for the output to
+// catch up instead of piling up results in memory.
+const RESULT_BUFFER: usize = 4096;
+// seconds between status lines with `--progress plain`
+const PLAIN_INTERVAL: u64 = 10;
+
+static UUID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static UUID_ALL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static REQ_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static CACHED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+// names skipped because someone else on the `--dedup-server` already checked them
+static DEDUP_SKIPPED: AtomicUsize = AtomicUsize::new(0);
+static FAILED_BATCHES: AtomicUsize = AtomicUsize::new(0);
+// words of the wordlist the producer went through so far
+static WORD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+// candidates the whole wordlist expands to, unset while `--follow` keeps adding to it
+static TOTAL_CANDIDATES: OnceLock<u64> = OnceLock::new();
+// candidates that didn't need a request: skipped, filtered out or answered from the cache or the
+// state
+static SKIPPED_CANDIDATES: AtomicU64 = AtomicU64::new(0);
+// candidates skipped because the expansion of another word already produced them
+static DUPLICATE_CANDIDATES: AtomicU64 = AtomicU64::new(0);
+// found uuids that weren't ignored, by their version
+static VERSION_COUNTERS: [AtomicUsize; 16] = [const { AtomicUsize::new(0) }; 16];
+// set once the run is over, stops the status line from being drawn again
+static DONE: AtomicBool = AtomicBool::new(false);
+// set when `--max-error-rate` is exceeded or `stop` is sent to the `--control-socket`, winds the
+// run down early
+static ABORTED: AtomicBool = AtomicBool::new(false);
+// set along with ABORTED when the run was stopped over `--control-socket`, which isn't an error
+static STOPPED: AtomicBool = AtomicBool::new(false);
+static CONTROL: Control = Control::new();
+static THROTTLE: Throttle = Throttle::new();
+static BREAKER: Breaker = Breaker::new();
+static KEYSPACE: Position = Position::new();
+// counts from a `word<tab>count` wordlist, and the sum of them over the names that were found
+static WORD_COUNTS: OnceLock<HashMap<String, u64>> = OnceLock::new();
+static FOUND_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// exit codes besides 0 for a clean run and 1 for errors
+const EXIT_DROPPED_BATCHES: u8 = 2;
+const EXIT_ERROR_BUDGET: u8 = 3;
+
+#[tokio::main]
+async fn main() -> eyre::Result<ExitCode> {
+    match command().run() {
+        Command::Stats { dump } => {
+            stats::run(&dump).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Keyspace(args) => {
+            keyspace::run(&args).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Generate(args) => {
+            generate::run(&args).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Rank(args) => {
+            rank::run(&args).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Convert(args) => {
+            convert::run(&args).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::DedupServer { listen } => {
+            dedup::serve(listen).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Run(args) => run(*args).await,
+    }
+}
+
+async fn run(mut args: Cli) -> eyre::Result<ExitCode> {
+    let started = Instant::now();
+    configure(&args).await?;
+    let output_path = clock::expand_template(&args.output_path);
+    if output_path != args.output_path {
+        info!("writing to {output_path}");
+        args.output_path = output_path;
+    }
+    let mailer = (!args.mail_to.is_empty())
+        .then(|| Mailer::new(&args))
+        .transpose()?;
+    let outcome = dump(args, started).await;
+    if let Some(mailer) = mailer {
+        mailer.report(started.elapsed(), &outcome).await;
+    }
+    outcome
+}
+
+// the run itself, from asking the backends if they're up to the last hit written out
+async fn dump(mut args: Cli, started: Instant) -> eyre::Result<ExitCode> {
+    let groups = build_backends(&args).await?;
+
+    if !args.skip_health_check && args.mode == Mode::Api {
+        info!("checking endpoint health");
+        for backend in groups.iter().flat_map(|group| &group.backends) {
+            health_check(backend).await?;
+        }
+    }
+    if args.probe || args.probe_only {
+        args.threads = probe(&args, &groups).await?;
+        if args.probe_only {
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+    // opened right away, so a bad path fails now and not after parsing a huge wordlist
+    let sinks = open_sinks(&args).await?;
+    let uploader = args
+        .upload
+        .as_deref()
+        .map(|url| Uploader::new(url, &args))
+        .transpose()?;
+    // hashing the inputs can take a while for big wordlists, so it runs alongside the rest
+    let meta = tokio::spawn(RunMeta::start(args.clone()));
+    // anything appended after this is picked up by `--follow`
+    let wordlist_size = tokio::fs::metadata(&args.wordlists[0].path).await?.len();
+    let cleaning = clean_options(
+        args.allow_legacy_names,
+        args.transliterate,
+        args.transform_rules.as_deref(),
+        // offline uuids are of the name as it's written
+        args.mode == Mode::OfflineUuid,
+    )
+    .await?;
+    let wordlist = load_wordlist(&args, &cleaning).await?;
+    let suffixes = load_suffixes(args.suffixes.as_deref()).await?;
+
+    let (ignored, ignored_names) = load_ignores(&args).await?;
+    let cache = load_cache(&args).await?;
+    let state = load_state(&args).await?;
+    let pairs = open_pairs(&args).await?;
+    let (droptimes, droptime_task) = open_droptimes(&args).await?;
+
+    let progress = args
+        .progress_webhook
+        .as_deref()
+        .map(|url| Progress::start(url, args.progress_interval, args.timeout, wordlist.len()))
+        .transpose()?;
+    let status = open_status(&args, wordlist.len()).await?;
+    if let Some(path) = &args.control_socket {
+        control::serve(&CONTROL, path, Instant::now(), wordlist.len())?;
+    }
+
+    let (filter, exec) = open_hooks(&args, sinks.len())?;
+    let (tx, handler_handle) = spawn_handler(&args, ignored, sinks, filter, exec);
+
+    let patterns = pattern::or_word(args.patterns.clone());
+    let sample = seeded_sample(&args);
+    let per_word = keyspace::per_word(&patterns, &suffixes);
+    // a --mutate program decides how many words each one turns into
+    if !args.follow && args.mutate.is_none() {
+        let _ = TOTAL_CANDIDATES.set(wordlist.len() as u64 * per_word);
+    }
+    let seen = open_seen(&args, per_word);
+    open_journal(&args, &patterns, &suffixes).await?;
+    let shared = Arc::new(Shared {
+        cleaning,
+        per_word,
+        skip_keyspace: args.skip_keyspace,
+        suffixes,
+        patterns,
+        ignored_names,
+        suffix_max_length: args.suffix_max_length,
+        suffix_words: args.suffix_words.clone(),
+        exclude: args.exclude.clone(),
+        include: args.include.clone(),
+        shard: args.shard,
+        sample,
+        cache,
+        state,
+        pairs,
+        droptimes,
+        dedup: open_dedup(&args)?,
+        retry_policies: RetryPolicies::new(&args.retry),
+    });
+
+    scrape(&args, wordlist, wordlist_size, seen, shared, &groups, &tx).await?;
+    systemd::stopping();
+    // the handler stops once every sender is gone, and flushes the output before it returns
+    drop(tx);
+    handler_handle.await?;
+    journal::finish();
+    if let Some(task) = droptime_task {
+        droptime::finish(task).await;
+    }
+    if args.sort_output {
+        sink::sort_output(&args.output_path, args.output_format).await?;
+    }
+    print_summary(started.elapsed(), sample);
+    wrap_up(&args, progress, status, meta, uploader).await?;
+
+    Ok(exit_code())
+}
+
+// tells everything watching the run that it's over, and uploads its output if it succeeded
+async fn wrap_up(
+    args: &Cli,
+    progress: Option<Progress>,
+    status: Option<StatusStream>,
+    meta: tokio::task::JoinHandle<eyre::Result<RunMeta>>,
+    uploader: Option<Uploader>,
+) -> eyre::Result<()> {
+    if let Some(progress) = progress {
+        progress.finish().await;
+    }
+    if let Some(status) = status {
+        status.finish().await;
+    }
+    if let Err(e) = meta.await?.map(RunMeta::finish)?.await {
+        eprintln!("warn: failed to write run metadata: {e}");
+    }
+    if let Some(path) = &args.control_socket {
+        let _ = std::fs::remove_file(path);
+    }
+    if let Some(uploader) = uploader
+        && failure().is_none()
+    {
+        let meta = format!("{}.meta.json", args.output_path);
+        uploader.upload(&[args.output_path.clone(), meta]).await?;
+    }
+    Ok(())
+}
+
+// feeds the wordlist to the request threads and waits until all of it was queried
+async fn scrape(
+    args: &Cli,
+    wordlist: Words,
+    wordlist_size: u64,
+    seen: Option<Bloom>,
+    shared: Arc<Shared>,
+    groups: &[Group],
+    tx: &Sender<(Uuid, String)>,
+) -> eyre::Result<()> {
+    // `--threads` over the backends without a concurrency of their own, the rest get theirs
+    let shared_backends = groups
+        .iter()
+        .filter(|group| group.concurrency.is_none())
+        .flat_map(|group| group.backends.iter().cloned())
+        .collect::<Vec<_>>();
+    let mut thread_backends = vec![];
+    if !shared_backends.is_empty() {
+        let threads = args.threads.max(1);
+        thread_backends
+            .extend((0..threads).map(|i| shared_backends[i % shared_backends.len()].clone()));
+    }
+    for group in groups {
+        if let Some(concurrency) = group.concurrency {
+            thread_backends
+                .extend((0..concurrency).map(|i| group.backends[i % group.backends.len()].clone()));
+        }
+    }
+    let threads = thread_backends.len();
+    let (batch_tx, batch_rx) = channel(threads * 2);
+    let follow = args
+        .follow
+        .then(|| (args.wordlists[0].path.clone(), wordlist_size));
+    let mutator = args.mutate.as_deref().map(Mutator::spawn).transpose()?;
+    let producer_handle = tokio::spawn(producer(
+        wordlist,
+        follow,
+        mutator,
+        seen,
+        shared.clone(),
+        batch_tx,
+        tx.clone(),
+    ));
+    let batch_rx = Arc::new(Mutex::new(batch_rx));
+    if let Some(max) = args.max_error_rate {
+        tokio::spawn(error_budget(max));
+    }
+
+    info!("spawning tasks");
+    systemd::ready();
+    let mut handles = vec![];
+    for (i, backend) in thread_backends.into_iter().enumerate() {
+        handles.push(tokio::spawn(request_thread(
+            tx.clone(),
+            shared.clone(),
+            batch_rx.clone(),
+            backend,
+            args.jitter.map(|j| (j, Rng::new(random_seed() ^ i as u64))),
+        )));
+    }
+
+    // the producer notices once every request thread stopped
+    drop(batch_rx);
+    spawn(display_thread);
+
+    producer_handle.await??;
+    for h in handles {
+        h.await?;
+    }
+    Ok(())
+}
+
+fn exit_code() -> ExitCode {
+    failure().map_or(ExitCode::SUCCESS, ExitCode::from)
+}
+
+// the exit code of a run that didn't succeed
+fn failure() -> Option<u8> {
+    if ABORTED.load(Ordering::SeqCst) && !STOPPED.load(Ordering::SeqCst) {
+        Some(EXIT_ERROR_BUDGET)
+    } else if FAILED_BATCHES.load(Ordering::SeqCst) > 0 {
+        Some(EXIT_DROPPED_BATCHES)
+    } else {
+        None
+    }
+}
+
+// stops the run once the share of failed requests goes over `--max-error-rate`
+#[allow(clippy::cast_precision_loss)]
+async fn error_budget(max: f64) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let errors = error_count();
+        let attempts = errors + REQ_COUNTER.load(Ordering::SeqCst);
+        let rate = errors as f64 / attempts as f64;
+        if attempts >= 100 && rate > max {
+            eprintln!(
+                "{}error: {:.1}% of {attempts} requests failed, more than --max-error-rate allows. stopping.",
+                clear_line(),
+                rate * 100.0
+            );
+            ABORTED.store(true, Ordering::SeqCst);
+            return;
+        }
+    }
+}
+
+// loads, cleans and deduplicates the wordlist, on disk if it's too big to comfortably do it in
+// memory
+async fn load_wordlist(args: &Cli, cleaning: &CleanOptions) -> eyre::Result<Words> {
+    let mut size = 0;
+    for spec in &args.wordlists {
+        size += tokio::fs::metadata(&spec.path).await?.len();
+    }
+    let external = args.external_dedup || size > wordlist::EXTERNAL_THRESHOLD;
+    let several = args.wordlists.len() > 1;
+    if several && external {
+        eyre::bail!(
+            "multiple wordlists are combined in memory, they can't be deduplicated on disk (which lists over {} GiB together are)",
+            wordlist::EXTERNAL_THRESHOLD >> 30
+        );
+    }
+    if several && args.follow {
+        eyre::bail!("--follow can only follow a single wordlist");
+    }
+    if several && (args.shuffle.is_some() || args.scores.is_some() || args.prioritize_short) {
+        eyre::bail!(
+            "--shuffle, --scores and --prioritize-short reorder the whole wordlist, which would undo how multiple wordlists are combined"
+        );
+    }
+    if args.shuffle.is_some() && args.scores.is_some() {
+        eyre::bail!("--shuffle and --scores both decide the order, only one can be given");
+    }
+    if external && (args.shuffle.is_some() || args.scores.is_some() || args.prioritize_short) {
+        eyre::bail!(
+            "--shuffle, --scores and --prioritize-short need the whole wordlist in memory, they can't be combined with on-disk deduplication"
+        );
+    }
+
+    let wordlist = if external {
+        info!("parsing and deduplicating wordlist on disk");
+        let path = args.wordlists[0].path.clone();
+        let tmp_dir = args
+            .tmp_dir
+            .as_ref()
+            .map_or_else(std::env::temp_dir, PathBuf::from);
+        let cleaning = cleaning.clone();
+        let sorted = tokio::task::spawn_blocking(move || {
+            wordlist::external_dedup(&path, &tmp_dir, &cleaning)
+        })
+        .await??;
+        Words::Disk(sorted)
+    } else {
+        info!("parsing wordlist");
+        let (mut wordlist, counts) =
+            wordlist::load_mixed(&args.wordlists, args.wordlist_order, cleaning).await?;
+        if several {
+            info!(
+                "combined {} wordlists ({})",
+                args.wordlists.len(),
+                args.wordlist_order
+            );
+        }
+        if !counts.is_empty() {
+            info!("ordering wordlist by the counts of {} words", counts.len());
+            let _ = WORD_COUNTS.set(counts);
+        }
+        if let Some(seed) = args.shuffle {
+            info!("shuffling wordlist with seed {seed}");
+            Rng::new(seed).shuffle(&mut wordlist);
+        }
+        if let Some(path) = &args.scores {
+            let scores = wordlist::load_scores(path, cleaning).await?;
+            info!("ordering wordlist by {} scores", scores.len());
+            wordlist::sort_by_score(&mut wordlist, &scores);
+        }
+        if args.prioritize_short {
+            info!("ordering wordlist by length");
+            wordlist::sort_by_length(&mut wordlist);
+        }
+        Words::Memory(wordlist)
+    };
+    info!("loaded {} names", wordlist.len());
+    Ok(wordlist)
+}
+
+async fn load_ignores(args: &Cli) -> eyre::Result<(Ignored, HashSet<String>)> {
+    info!("parsing ignored uuids");
+    let ignored = Ignored::load(&args.ignored, args.ignored_truncation, &bad_lines(args)).await?;
+
+    info!("{} uuids ignored", ignored.len());
+    for path in ignored.db_paths() {
+        info!("also ignoring every uuid in {path}");
+    }
+
+    info!("parsing ignored names");
+    let ignored_names = if let Some(ignored_names) = &args.ignored_names {
+        load_ignored_names(ignored_names).await?
+    } else {
+        HashSet::default()
+    };
+
+    info!("{} names ignored", ignored_names.len());
+    Ok((ignored, ignored_names))
+}
+
+async fn load_cache(args: &Cli) -> eyre::Result<Option<Cache>> {
+    let Some(dir) = &args.cache else {
+        return Ok(None);
+    };
+    offline_conflict(args, "--cache")?;
+    info!("loading cache");
+    let cache = Cache::open(dir, args.cache_ttl, args.negative_cache_ttl).await?;
+    info!(
+        "{} cached hits, {} cached misses",
+        cache.hits(),
+        cache.misses()
+    );
+    Ok(Some(cache))
+}
+
+async fn load_state(args: &Cli) -> eyre::Result<Option<State>> {
+    let Some(path) = &args.state else {
+        return Ok(None);
+    };
+    offline_conflict(args, "--state")?;
+    let state = State::open(path, args.cache_ttl, args.negative_cache_ttl).await?;
+    info!("{} names in the state", state.len()?);
+    Ok(Some(state))
+}
+
+// `--probe`, returns the thread count to continue with
+async fn probe(args: &Cli, groups: &[Group]) -> eyre::Result<usize> {
+    if args.mode == Mode::OfflineUuid {
+        eyre::bail!("--probe measures the backend, there is none with --mode offline-uuid");
+    }
+    // only --threads is up for probing, backends with a concurrency keep it
+    let backends = groups
+        .iter()
+        .filter(|group| group.concurrency.is_none())
+        .flat_map(|group| group.backends.iter().cloned())
+        .collect::<Vec<_>>();
+    if backends.is_empty() {
+        eyre::bail!("--probe picks --threads, but every backend has a concurrency of its own");
+    }
+    Ok(probe::run(&backends, args.threads.max(1)).await)
+}
+
+// `--sample` with the seed from `--sample-seed`, or a random one
+fn seeded_sample(args: &Cli) -> Option<Sample> {
+    args.sample.map(|sample| {
+        let seed = args.sample_seed.unwrap_or_else(random_seed);
+        info!(
+            "sampling {:.2}% of candidates with seed {seed}",
+            sample.ratio * 100.0
+        );
+        Sample { seed, ..sample }
+    })
+}
+
+// applies the settings that are kept in statics
+async fn configure(args: &Cli) -> eyre::Result<()> {
+    term::init(args.progress, args.print_results, args.quiet, args.color);
+    THROTTLE.set_threshold(args.slowdown_threshold);
+    BREAKER.set_threshold(args.breaker_threshold);
+    limits::init(args.max_connections, backend::total_threads(args));
+    if let Some(fraction) = args.trace_http {
+        trace::init(fraction, args.trace_file.as_deref())?;
+    }
+    if let Some(path) = &args.latency_log {
+        latency::open_log(path).await?;
+    }
+    if let Some(dir) = &args.record {
+        recording::start(dir).await?;
+    }
+    systemd::start();
+    Ok(())
+}
+
+// how wordlist lines are turned into words, shared with `keyspace`
+async fn clean_options(
+    allow_legacy_names: bool,
+    transliterate: Scheme,
+    transform_rules: Option<&str>,
+    keep_case: bool,
+) -> eyre::Result<CleanOptions> {
+    let rules = match transform_rules {
+        Some(path) => {
+            let rules = Rules::load(path).await?;
+            info!("loaded {} transform rules", rules.len());
+            Some(Arc::new(rules))
+        }
+        None => None,
+    };
+    Ok(CleanOptions {
+        legacy_names: allow_legacy_names,
+        transliteration: transliterate,
+        rules,
+        keep_case,
+    })
+}
+
+// the filter for candidates that come up more than once, only needed when words are expanded
+fn open_seen(args: &Cli, per_word: u64) -> Option<Bloom> {
+    if (per_word <= 1 && args.mutate.is_none()) || args.candidate_memory == 0 {
+        return None;
+    }
+    let total = TOTAL_CANDIDATES.get().copied();
+    let seen = Bloom::new(total, args.candidate_memory << 20);
+    if let Some(total) = total
+        && total > seen.capacity()
+    {
+        eprintln!(
+            "warn: --candidate-memory only fits {} of the {total} candidates, duplicates after those are queried again",
+            seen.capacity()
+        );
+    }
+    Some(seen)
+}
+
+async fn open_pairs(args: &Cli) -> eyre::Result<Option<Pairs>> {
+    let Some(path) = &args.pairs else {
+        return Ok(None);
+    };
+    if args.mode == Mode::OfflineUuid {
+        eyre::bail!(
+            "--pairs needs the api to find premium uuids, it can't be used with --mode offline-uuid"
+        );
+    }
+    Ok(Some(Pairs::open(path).await?))
+}
+
+async fn open_droptimes(
+    args: &Cli,
+) -> eyre::Result<(Option<Droptimes>, Option<tokio::task::JoinHandle<()>>)> {
+    let Some(path) = &args.droptimes else {
+        return Ok((None, None));
+    };
+    let Some(url) = &args.droptime_url else {
+        eyre::bail!("--droptimes needs a service to ask from --droptime-url");
+    };
+    let (droptimes, task) = Droptimes::start(path, url, args.timeout).await?;
+    Ok((Some(droptimes), Some(task)))
+}
+
+async fn open_status(args: &Cli, words: usize) -> eyre::Result<Option<StatusStream>> {
+    let target = match (args.status_fd, &args.status_json) {
+        (Some(_), Some(_)) => eyre::bail!("--status-fd and --status-json can't both be given"),
+        (Some(fd), None) => StatusTarget::Fd(fd),
+        (None, Some(path)) => StatusTarget::Path(path.clone()),
+        (None, None) => return Ok(None),
+    };
+    if args.status_interval.is_zero() {
+        eyre::bail!("--status-interval has to be more than 0");
+    }
+    Ok(Some(
+        StatusStream::start(target, args.status_interval, words).await?,
+    ))
+}
+
+fn open_dedup(args: &Cli) -> eyre::Result<Option<Dedup>> {
+    let Some(url) = &args.dedup_server else {
+        return Ok(None);
+    };
+    offline_conflict(args, "--dedup-server")?;
+    Ok(Some(Dedup::new(url, args.timeout)?))
+}
+
+// what `flag` keeps is shared with runs against the api, which would take computed offline uuids
+// for found ones (and the other way around)
+fn offline_conflict(args: &Cli, flag: &str) -> eyre::Result<()> {
+    if args.mode == Mode::OfflineUuid {
+        eyre::bail!(
+            "{flag} keeps what the api answered, it can't be used with --mode offline-uuid"
+        );
+    }
+    Ok(())
+}
+
+fn bad_lines(args: &Cli) -> BadLines {
+    BadLines {
+        max_share: args.max_bad_ignored,
+        rejects: args.ignored_rejects.clone(),
+    }
+}
+
+async fn load_ignored_names(path: &str) -> eyre::Result<HashSet<String>> {
+    let ignored_names_f = tokio::fs::read_to_string(path).await?;
+    Ok(ignored_names_f
+        .lines()
+        .map(|n| n.trim().to_ascii_lowercase())
+        .filter(|n| !n.is_empty())
+        .collect())
+}
+
+// `--journal`, named after the output when only `--resume` is given
+async fn open_journal(args: &Cli, patterns: &[Pattern], suffixes: &[String]) -> eyre::Result<()> {
+    let path = match &args.journal {
+        Some(path) => path.clone(),
+        None if args.resume && s3::is_s3(&args.output_path) => {
+            eyre::bail!("--resume with an s3 output needs a local --journal")
+        }
+        None if args.resume => format!("{}.journal", args.output_path),
+        None => return Ok(()),
+    };
+    // what turns a word into candidates, a word done with other settings isn't done with these
+    let settings = format!(
+        "{patterns:?} {suffixes:?} {:?} {:?} {:?}",
+        args.suffix_max_length,
+        args.suffix_words.as_ref().map(Regex::as_str),
+        args.mutate
+    );
+    journal::open(&path, args.resume, &settings).await
+}
+
+// `--filter` and `--exec`, the programs every hit goes through
+fn open_hooks(args: &Cli, outputs: usize) -> eyre::Result<(Option<ResultFilter>, Option<Exec>)> {
+    let filter = args
+        .filter
+        .as_deref()
+        .map(|command| ResultFilter::spawn(command, outputs))
+        .transpose()?;
+    if args
+        .exec_rps
+        .is_some_and(|rps| !(rps > 0.0 && rps.is_finite()))
+    {
+        eyre::bail!("--exec-rps has to be more than 0");
+    }
+    let exec = args
+        .exec
+        .as_deref()
+        .map(|command| Exec::new(command, args.exec_concurrency, args.exec_rps));
+    Ok((filter, exec))
+}
+
+async fn open_sinks(args: &Cli) -> eyre::Result<Vec<Sink>> {
+    if args.sort_output && args.output_format == OutputFormat::Arrow {
+        eyre::bail!("--sort-output sorts entries of a file, arrow output is a stream of batches");
+    }
+    if s3::is_s3(&args.output_path) {
+        if args.sort_output {
+            eyre::bail!("--sort-output sorts a file, an s3 output is uploaded as it's found");
+        }
+        // the object is replaced once the upload completes, there is nothing to append to
+        let object = s3::Object::open(&args.output_path, args)?;
+        let mut sinks = vec![Sink::s3(object, args.output_format, args.timestamps)?];
+        sinks.extend(open_extra_sinks(args).await?);
+        return Ok(sinks);
+    }
+    let existing = match tokio::fs::metadata(&args.output_path).await {
+        Ok(meta) => meta.len() > 0,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => return Err(e.into()),
+    };
+    if existing {
+        let on_existing = match args
+            .on_existing
+            .or_else(|| args.resume.then_some(OnExisting::Append))
+        {
+            Some(on_existing) => on_existing,
+            None => ask_on_existing(&args.output_path).await?,
+        };
+        match on_existing {
+            OnExisting::Append => info!("appending to {}", args.output_path),
+            OnExisting::Overwrite => {
+                info!("overwriting {}", args.output_path);
+                tokio::fs::write(&args.output_path, b"").await?;
+            }
+            OnExisting::Abort => eyre::bail!(
+                "output file {} already exists, pass --on-existing append or overwrite to use it anyway",
+                args.output_path
+            ),
+        }
+    }
+    let mut sinks = vec![Sink::text(&args.output_path, args.output_format, args.timestamps).await?];
+    sinks.extend(open_extra_sinks(args).await?);
+    Ok(sinks)
+}
+
+// the `--sink`s
+async fn open_extra_sinks(args: &Cli) -> eyre::Result<Vec<Sink>> {
+    let mut sinks = vec![];
+    for spec in &args.sinks {
+        sinks.push(
+            Sink::open(
+                spec,
+                args.output_format,
+                args.timestamps,
+                args.timeout,
+                args.hypixel_key.as_deref(),
+                args.only_caped,
+            )
+            .await?,
+        );
+    }
+    Ok(sinks)
+}
+
+// asks whether to append to or overwrite an existing output, aborts without a terminal to ask on
+async fn ask_on_existing(path: &str) -> eyre::Result<OnExisting> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Ok(OnExisting::Abort);
+    }
+    let path = path.to_string();
+    let answer = tokio::task::spawn_blocking(move || -> std::io::Result<OnExisting> {
+        loop {
+            eprint!("output file {path} already exists, [a]ppend, [o]verwrite or a[b]ort? ");
+            std::io::stderr().flush()?;
+            let mut line = String::new();
+            if std::io::stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(OnExisting::Abort);
+            }
+            match line.trim().to_ascii_lowercase().as_str() {
+                "a" | "append" => return Ok(OnExisting::Append),
+                "o" | "overwrite" => return Ok(OnExisting::Overwrite),
+                "b" | "abort" => return Ok(OnExisting::Abort),
+                _ => {}
+            }
+        }
+    })
+    .await??;
+    Ok(answer)
+}
+
+async fn load_suffixes(path: Option<&str>) -> eyre::Result<Vec<String>> {
+    let Some(path) = path else {
+        return Ok(vec![String::new()]);
+    };
+    let suffixes = tokio::fs::read_to_string(path)
+        .await?
+        .lines()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    // no suffix at all would leave every word without candidates
+    if suffixes.is_empty() {
+        eyre::bail!("--suffixes {path} is empty, add an empty line to keep the words as they are");
+    }
+    Ok(suffixes)
+}
+
+// opens the output for appending, creating missing parent directories
+async fn open_output(path: &str) -> eyre::Result<tokio::fs::File> {
+    if let Some(parent) = Path::new(path).parent()
+        && !parent.as_os_str().is_empty()
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .wrap_err_with(|| format!("failed to create output directory {}", parent.display()))?;
+    }
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .wrap_err_with(|| format!("failed to open output file {path}"))
+}
+
+// state every request thread needs
+struct Shared {
+    // for words appended to a followed wordlist
+    cleaning: CleanOptions,
+    suffixes: Vec<String>,
+    patterns: Vec<Pattern>,
+    // candidates every word expands to
+    per_word: u64,
+    skip_keyspace: u64,
+    ignored_names: HashSet<String>,
+    suffix_max_length: Option<usize>,
+    suffix_words: Option<Regex>,
+    exclude: Vec<Regex>,
+    include: Vec<Regex>,
+    shard: Option<Shard>,
+    sample: Option<Sample>,
+    cache: Option<Cache>,
+    state: Option<State>,
+    pairs: Option<Pairs>,
+    droptimes: Option<Droptimes>,
+    dedup: Option<Dedup>,
+    retry_policies: RetryPolicies,
 }
 
-const ALLOWED_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz1234567890_";
-const MOWOJANG: &str = "https://mowojang.matdoes.dev";
+impl Shared {
+    // whether the suffixes go on this word, see `--suffix-max-length` and `--suffix-words`
+    fn suffixes(&self, word: &str) -> bool {
+        self.suffix_max_length
+            .is_none_or(|max| word.chars().count() <= max)
+            && self
+                .suffix_words
+                .as_ref()
+                .is_none_or(|re| re.is_match(word))
+    }
 
-lazy_static! {
-    static ref CLIENT: reqwest::Client = reqwest::Client::new();
-    static ref UUID_COUNTER: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
-    static ref UUID_ALL_COUNTER: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
-    static ref REQ_COUNTER: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    // whether a candidate is filtered out by `--ignored-names`, `--exclude`, `--include`,
+    // `--shard` or `--sample`
+    fn skips(&self, candidate: &str) -> bool {
+        let lowercase = candidate.to_ascii_lowercase();
+        self.ignored_names.contains(&lowercase)
+            || self.shard.is_some_and(|shard| !shard.contains(&lowercase))
+            || self
+                .sample
+                .is_some_and(|sample| !sample.contains(&lowercase))
+            || self.exclude.iter().any(|re| re.is_match(candidate))
+            || !(self.include.is_empty() || self.include.iter().any(|re| re.is_match(candidate)))
+    }
 }
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
-    let args: Cli = cli().run();
-    if tokio::fs::try_exists(&args.output_path).await? {
-        eprintln!("warn: output file already exists, found uuids will be appended.");
-    }
-    eprintln!("parsing wordlist");
-    let wordlist_f = tokio::fs::read_to_string(args.wordlist_path).await?;
-    let mut wordlist = wordlist_f
-        .lines()
-        .map(|w| {
-            w.chars()
-                .filter(|c| ALLOWED_CHARS.contains(*c))
-                .collect::<String>()
-        })
-        .filter(|w| (3..16).contains(&w.len()))
-        .map(|w| w.to_ascii_lowercase())
-        .collect::<Vec<String>>();
-    wordlist.sort();
-    wordlist.dedup();
-    drop(wordlist_f);
-
-    let suffixes = if let Some(suffixes) = args.suffixes {
-        let suffixes = tokio::fs::read_to_string(suffixes).await?;
-        suffixes.lines().map(String::from).collect::<Vec<String>>()
-    } else {
-        vec![String::new()]
+// task which expands the wordlist into candidates and hands them to the request threads in
+// batches. cached answers are sent to the handler right away. with `follow`, it keeps going with
+// every word appended to the wordlist file after the given offset.
+async fn producer(
+    wordlist: Words,
+    follow: Option<(String, u64)>,
+    mutator: Option<Mutator>,
+    seen: Option<Bloom>,
+    shared: Arc<Shared>,
+    batches: Sender<Batch>,
+    tx: Sender<(Uuid, String)>,
+) -> eyre::Result<()> {
+    let mut queue = Batcher {
+        mutator,
+        seen,
+        shared,
+        batches,
+        tx,
+        batch: Batch {
+            start: 0,
+            names: Vec::with_capacity(10),
+            words: vec![],
+        },
+        word: None,
     };
-
-    eprintln!("loaded {} names", wordlist.len());
-
-    eprintln!("parsing ignored uuids");
-    let ignored = if let Some(ignored) = args.ignored {
-        let ignored_f = tokio::fs::read_to_string(ignored).await?;
-        let ignored: HashSet<Uuid> = ignored_f
-            .lines()
-            .map(String::from)
-            .map(|mut u| {
-                if args.ignored_truncation.is_some() {
-                    u = format!("{u}{}", "0".repeat(32 - u.len()));
+    match &wordlist {
+        Words::Memory(words) => {
+            for word in words {
+                if ABORTED.load(Ordering::SeqCst) {
+                    break;
                 }
-                Uuid::from_str(&u).expect("failed to parse uuid")
-            })
-            .collect::<HashSet<_>>();
-        ignored
-    } else {
-        HashSet::default()
-    };
+                queue.push(word).await?;
+            }
+        }
+        Words::Disk(sorted) => {
+            let mut lines = BufReader::new(tokio::fs::File::open(&sorted.path).await?).lines();
+            while let Some(word) = lines.next_line().await?
+                && !ABORTED.load(Ordering::SeqCst)
+            {
+                queue.push(&word).await?;
+            }
+        }
+    }
+    drop(wordlist);
+    queue.flush().await;
 
-    eprintln!("{} uuids ignored", ignored.len());
+    let Some((path, offset)) = follow else {
+        return Ok(());
+    };
+    info!("{}following {path} for new names", clear_line());
+    let mut follower = Follower::open(&path, offset, queue.shared.cleaning.clone()).await?;
+    while !ABORTED.load(Ordering::SeqCst) {
+        if let Some(word) = follower.next().await? {
+            queue.push(&word).await?;
+        } else {
+            // whatever is there goes out now instead of waiting for a full batch
+            queue.flush().await;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+    Ok(())
+}
 
-    let (tx, rx) = unbounded_channel::<(Uuid, String)>();
-    tokio::spawn(handler(
-        rx,
-        ignored,
-        args.ignored_truncation,
-        args.output_path,
-        args.print_ignored,
-    ));
+// collects candidates into batches of 10 for the request threads
+struct Batcher {
+    mutator: Option<Mutator>,
+    // the candidates handed out so far, when patterns or suffixes can produce one twice
+    seen: Option<Bloom>,
+    shared: Arc<Shared>,
+    batches: Sender<Batch>,
+    tx: Sender<(Uuid, String)>,
+    batch: Batch,
+    // journal id of the word being expanded
+    word: Option<u64>,
+}
 
-    let words = wordlist.len();
-    let wordlist_parts = wordlist.chunks(words / args.threads.clamp(1, words));
+struct Batch {
+    // keyspace position of the first name
+    start: u64,
+    names: Vec<String>,
+    // journal ids of the words the names are from
+    words: Vec<u64>,
+}
 
-    eprintln!("spawning tasks");
-    let mut handles = vec![];
-    for w in wordlist_parts {
-        let suffixes = suffixes.clone();
-        handles.push(tokio::spawn(request_thread(
-            tx.clone(),
-            w.to_vec(),
-            suffixes,
-        )));
+impl Batcher {
+    async fn push(&mut self, word: &str) -> eyre::Result<()> {
+        WORD_COUNTER.fetch_add(1, Ordering::SeqCst);
+        if journal::skips(word) {
+            // what a --mutate program makes of it is unknown, so is how far that goes
+            if self.mutator.is_none() {
+                KEYSPACE.advance(self.shared.per_word);
+                SKIPPED_CANDIDATES.fetch_add(self.shared.per_word, Ordering::SeqCst);
+            }
+            return Ok(());
+        }
+        self.word = journal::begin(word);
+        let words = match &mut self.mutator {
+            Some(mutator) => mutator.mutate(word).await?,
+            None => vec![word.to_string()],
+        };
+        for word in words {
+            self.expand(&word).await;
+        }
+        if let Some(id) = self.word.take() {
+            journal::expanded(id);
+        }
+        Ok(())
     }
 
-    spawn(display_thread);
+    async fn expand(&mut self, word: &str) {
+        let shared = self.shared.clone();
+        // whole words are skipped without expanding them
+        if KEYSPACE.peek() + shared.per_word <= shared.skip_keyspace {
+            KEYSPACE.advance(shared.per_word);
+            SKIPPED_CANDIDATES.fetch_add(shared.per_word, Ordering::SeqCst);
+            return;
+        }
+        for pattern in &shared.patterns {
+            for expanded in pattern.expand(word) {
+                if shared.suffixes(&expanded) {
+                    for suf in &shared.suffixes {
+                        self.push_candidate(format!("{expanded}{suf}")).await;
+                    }
+                    continue;
+                }
+                // queried once as it is, in the place of its first suffix. the keyspace still
+                // counts every suffix, so positions stay the same as without the rule.
+                self.push_candidate(expanded).await;
+                let rest = (shared.suffixes.len() as u64).saturating_sub(1);
+                KEYSPACE.advance(rest);
+                SKIPPED_CANDIDATES.fetch_add(rest, Ordering::SeqCst);
+            }
+        }
+    }
 
-    for h in handles {
-        h.await?;
+    async fn push_candidate(&mut self, candidate: String) {
+        let shared = self.shared.clone();
+        if KEYSPACE.peek() < shared.skip_keyspace || shared.skips(&candidate) {
+            KEYSPACE.take(false);
+            SKIPPED_CANDIDATES.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        // `cool` + `123` and `cool123` + nothing are the same name
+        if let Some(seen) = &mut self.seen
+            && seen.insert(&if shared.cleaning.keep_case {
+                candidate.clone()
+            } else {
+                candidate.to_ascii_lowercase()
+            })
+        {
+            KEYSPACE.take(false);
+            SKIPPED_CANDIDATES.fetch_add(1, Ordering::SeqCst);
+            DUPLICATE_CANDIDATES.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        // querying a duplicate costs a request, skipping a unique candidate can cost a hit
+        if self.seen.as_ref().is_some_and(Bloom::full) {
+            eprintln!(
+                "{}warn: --candidate-memory is full, any more would skip over {}% of the unique candidates. duplicates are queried from now on",
+                clear_line(),
+                bloom::MAX_FALSE_POSITIVES * 100.0
+            );
+            self.seen = None;
+        }
+        let mut answer = shared.cache.as_ref().and_then(|c| c.get(&candidate));
+        if answer.is_none()
+            && let Some(state) = &shared.state
+        {
+            answer = state.get(&candidate).await;
+        }
+        if let Some(answer) = answer {
+            KEYSPACE.take(false);
+            SKIPPED_CANDIDATES.fetch_add(1, Ordering::SeqCst);
+            CACHED_COUNTER.fetch_add(1, Ordering::SeqCst);
+            let found = match answer {
+                Answer::Found(uuid, name) => vec![(uuid, name)],
+                Answer::NotFound => vec![],
+            };
+            let candidate = [candidate];
+            if let Some(pairs) = &shared.pairs {
+                pairs.record(&candidate, &found).await;
+            }
+            if let Some(droptimes) = &shared.droptimes {
+                droptimes.record(&candidate, &found).await;
+            }
+            for uuid_name in found {
+                self.tx.send(uuid_name).await.unwrap();
+            }
+            return;
+        }
+        let at = KEYSPACE.take(self.batch.names.is_empty());
+        if self.batch.names.is_empty() {
+            self.batch.start = at;
+        }
+        self.batch.names.push(candidate);
+        if let Some(id) = self.word
+            && self.batch.words.last() != Some(&id)
+        {
+            self.batch.words.push(id);
+            journal::batched(id);
+        }
+        if self.batch.names.len() == 10 {
+            self.flush().await;
+        }
     }
 
-    Ok(())
+    async fn flush(&mut self) {
+        if !self.batch.names.is_empty() {
+            let batch = Batch {
+                start: self.batch.start,
+                names: std::mem::take(&mut self.batch.names),
+                words: std::mem::take(&mut self.batch.words),
+            };
+            // only fails if every request thread is gone, nothing left to do then
+            let _ = self.batches.send(batch).await;
+        }
+    }
 }
 
 // thread which scrapes uuids and sends found uuids to the handler
 async fn request_thread(
-    tx: UnboundedSender<(Uuid, String)>,
-    wordlist_part: Vec<String>,
-    suffixes: Vec<String>,
+    tx: Sender<(Uuid, String)>,
+    shared: Arc<Shared>,
+    batches: Arc<Mutex<Receiver<Batch>>>,
+    backend: Backend,
+    mut jitter: Option<(DurationRange, Rng)>,
 ) {
-    for wordlist_chunk in wordlist_part.chunks(100) {
-        let mut wordlist_suffixed = vec![];
-        for word in wordlist_chunk {
-            for suf in &suffixes {
-                wordlist_suffixed.push(format!("{word}{suf}"));
+    while !ABORTED.load(Ordering::SeqCst) {
+        let Some(batch) = batches.lock().await.recv().await else {
+            break;
+        };
+        let unchecked = match &shared.dedup {
+            Some(dedup) => Some(dedup.unchecked(&batch.names).await),
+            None => None,
+        };
+        let names = unchecked.as_deref().unwrap_or(&batch.names);
+        DEDUP_SKIPPED.fetch_add(batch.names.len() - names.len(), Ordering::SeqCst);
+        let (answered, mut uuids) = if names.is_empty() {
+            (vec![], vec![])
+        } else {
+            resolve_batch(&backend, names, &shared.retry_policies).await
+        };
+        if let Some(cache) = &shared.cache
+            && !answered.is_empty()
+        {
+            cache.record(&answered, &uuids).await;
+        }
+        if let Some(state) = &shared.state
+            && !answered.is_empty()
+        {
+            state.record(&answered, &uuids).await;
+        }
+        if let Some(pairs) = &shared.pairs {
+            pairs.record(&answered, &uuids).await;
+        }
+        if let Some(droptimes) = &shared.droptimes {
+            droptimes.record(&answered, &uuids).await;
+        }
+        if let Some(dedup) = &shared.dedup
+            && !answered.is_empty()
+        {
+            uuids = dedup.checked(&answered, uuids).await;
+        }
+        for uuid_name in uuids {
+            tx.send(uuid_name).await.unwrap();
+        }
+        // dropped names keep the position before them, so resuming queries them again
+        if answered.len() == names.len() {
+            KEYSPACE.close(batch.start);
+            journal::answered(&batch.words);
+        }
+        if let Some((range, rng)) = &mut jitter {
+            tokio::time::sleep(range.sample(rng)).await;
+        }
+    }
+}
+
+// requests a batch, retrying it according to the policy for each kind of error and waiting
+// out endpoint outages. if it still fails and the error could be caused by one of the names, they
+// are looked up one by one instead. returns the names that got an answer and the profiles found
+// among them.
+async fn resolve_batch(
+    backend: &Backend,
+    names: &[String],
+    retry_policies: &RetryPolicies,
+) -> (Vec<String>, Vec<(Uuid, String)>) {
+    let mut retries = 0;
+    let e = loop {
+        let probe = BREAKER.wait().await;
+        let delay = THROTTLE.delay();
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        CONTROL.wait().await;
+        let e = match request(backend, names.to_vec()).await {
+            Ok(uuids) => {
+                BREAKER.success();
+                tor::answered();
+                return (names.to_vec(), uuids);
             }
+            Err(e) => e,
+        };
+        tor::failed(e.class);
+        // outages are waited out by the breaker and don't use up retries
+        if BREAKER.failure(probe) {
+            continue;
+        }
+        let policy = retry_policies.get(e.class);
+        if retries >= policy.retries {
+            break e;
         }
+        tokio::time::sleep(e.retry_after.unwrap_or_else(|| policy.backoff(retries))).await;
+        retries += 1;
+    };
 
-        for w in wordlist_suffixed.chunks(10) {
-            let uuids = request(w.to_vec()).await;
-            for uuid_name in uuids {
-                tx.send(uuid_name).unwrap();
+    // timeouts, connection errors and rate limits aren't the names' fault, ten more requests
+    // wouldn't go any better
+    let single = !matches!(
+        e.class,
+        ErrorClass::Timeout | ErrorClass::Connect | ErrorClass::RateLimited
+    );
+    let mut answered = vec![];
+    let mut found = vec![];
+    if single && names.len() > 1 {
+        for name in names {
+            CONTROL.wait().await;
+            match request_single(backend, name).await {
+                Ok(profile) => {
+                    answered.push(name.clone());
+                    found.extend(profile);
+                }
+                Err(e) => eprintln!(
+                    "{}dropping {name} ({}): {}",
+                    clear_line(),
+                    e.class,
+                    e.message
+                ),
             }
         }
     }
+    if answered.len() < names.len() {
+        FAILED_BATCHES.fetch_add(1, Ordering::SeqCst);
+        eprintln!(
+            "{}dropping {} names of a batch after {retries} retries ({}): {}",
+            clear_line(),
+            names.len() - answered.len(),
+            e.class,
+            e.message
+        );
+    }
+    (answered, found)
+}
+
+// starts the handler, plus reloading the ignored uuids into it if there are any files
+fn spawn_handler(
+    args: &Cli,
+    ignored: Ignored,
+    sinks: Vec<Sink>,
+    filter: Option<ResultFilter>,
+    exec: Option<Exec>,
+) -> (Sender<(Uuid, String)>, tokio::task::JoinHandle<()>) {
+    let (tx, rx) = channel::<(Uuid, String)>(RESULT_BUFFER);
+    let (live_ignored, ignored) = watch::channel(Arc::new(ignored));
+    if !args.ignored.is_empty() {
+        tokio::spawn(ignore::reload(
+            args.ignored.clone(),
+            args.ignored_truncation,
+            bad_lines(args),
+            args.reload_ignored,
+            live_ignored,
+        ));
+    }
+    let handler_handle = tokio::spawn(handler(
+        rx,
+        ignored,
+        sinks,
+        filter,
+        exec,
+        args.print_ignored,
+        args.only_version,
+    ));
+    (tx, handler_handle)
 }
 
 // thread which handles ignoring uuids and outputting uuids to the file
 async fn handler(
-    mut rx: UnboundedReceiver<(Uuid, String)>,
-    ignored: HashSet<Uuid>,
-    ignored_truncation: Option<usize>,
-    out: String,
+    mut rx: Receiver<(Uuid, String)>,
+    ignored: watch::Receiver<Arc<Ignored>>,
+    mut sinks: Vec<Sink>,
+    mut filter: Option<ResultFilter>,
+    mut exec: Option<Exec>,
     print_ignored: bool,
+    only_version: Option<usize>,
 ) {
-    let mut output_f = tokio::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(out)
-        .await
-        .expect("failed to open output file");
-
-    while let Some((uuid, name)) = rx.recv().await {
-        if ignored.contains(&uuid)
-            || (ignored_truncation.is_some_and(|trunc| {
-                ignored.contains(&Uuid::from_u128(
-                    uuid.as_u128() & (u128::MAX << (128 - (trunc * 4) as u128)),
-                ))
-            }))
-        {
+    while let Some((uuid, name)) = next_hit(&mut rx, &mut sinks).await {
+        let is_ignored = ignored.borrow().contains(uuid);
+        if is_ignored {
             if print_ignored {
-                println!("\x1b[2K\r\x1b[38;5;241m{uuid}:{name}\x1b[0m");
+                term::print_result(uuid, &name, true);
                 print_status();
             }
             continue;
         }
+        // it would end up in --filter's input and --exec's commands otherwise
+        if !wordlist::is_name(&name) {
+            eprintln!(
+                "{}warn: dropping {uuid}, the backend answered with `{}` as its name",
+                clear_line(),
+                name.escape_debug()
+            );
+            continue;
+        }
+        let version = uuid.get_version_num();
+        VERSION_COUNTERS[version].fetch_add(1, Ordering::SeqCst);
+        if only_version.is_some_and(|v| v != version) {
+            continue;
+        }
+        let verdict = match &mut filter {
+            Some(f) => f.check(uuid, &name).await.unwrap_or_else(|e| {
+                // nothing found gets lost over a broken filter
+                eprintln!("{}warn: {e}, writing every hit from now on", clear_line());
+                filter = None;
+                Verdict::Keep
+            }),
+            None => Verdict::Keep,
+        };
+        if matches!(verdict, Verdict::Drop) {
+            continue;
+        }
 
         UUID_COUNTER.fetch_add(1, Ordering::SeqCst);
+        if let Some(count) = WORD_COUNTS
+            .get()
+            .and_then(|counts| counts.get(&name.to_ascii_lowercase()))
+        {
+            FOUND_COUNT.fetch_add(*count, Ordering::SeqCst);
+        }
 
-        eprintln!("\x1b[2K\r{uuid}:{name}");
+        term::print_result(uuid, &name, false);
         print_status();
 
-        output_f
-            .write_all(format!("{uuid}\n").as_bytes())
-            .await
-            .expect("failed to write to file");
+        for (i, sink) in sinks.iter_mut().enumerate() {
+            if let Verdict::Only(outputs) = &verdict
+                && !outputs.contains(&i)
+            {
+                continue;
+            }
+            sink.write(uuid, &name).await;
+        }
+        if let Some(exec) = &mut exec {
+            exec.run(uuid, &name).await;
+        }
+        // hits arriving in bursts get written together, a lone one goes out right away
+        if rx.is_empty() {
+            for sink in &mut sinks {
+                sink.flush().await;
+            }
+            journal::sync();
+        }
+    }
+    for sink in sinks {
+        sink.close().await;
+    }
+    if let Some(exec) = exec {
+        exec.finish().await;
     }
 }
 
-async fn request(names: Vec<String>) -> Vec<(Uuid, String)> {
-    assert!(names.len() <= 10, "too many uuids :(");
-
-    let res: serde_json::Value = match CLIENT
-        .post(MOWOJANG)
-        .header("content-type", "application/json")
-        .body(json!(names).to_string())
-        .send()
-        .await
-    {
-        Ok(res) => {
-            if let Ok(json) = res.json().await {
-                json
-            } else {
-                eprintln!("failed to parse response json");
-                return vec![];
-            }
+// the next hit for the handler. with a journal, the words done while no hits come in are
+// written down every second
+async fn next_hit(rx: &mut Receiver<(Uuid, String)>, sinks: &mut [Sink]) -> Option<(Uuid, String)> {
+    if !journal::enabled() {
+        return rx.recv().await;
+    }
+    loop {
+        if let Ok(hit) = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+            return hit;
         }
-        Err(e) => {
-            eprintln!("mowojang api request failed: {e:?}");
-            return vec![];
+        for sink in sinks.iter_mut() {
+            sink.flush().await;
         }
-    };
-    REQ_COUNTER.fetch_add(1, Ordering::SeqCst);
-    let mut pls = vec![];
-    for pl in res.as_array().unwrap() {
-        UUID_ALL_COUNTER.fetch_add(1, Ordering::SeqCst);
-        pls.push((
-            Uuid::from_str(pl["id"].as_str().unwrap()).unwrap(),
-            pl["name"].as_str().unwrap().to_string(),
-        ));
+        journal::sync();
     }
-    pls
 }
 
 fn display_thread() {
-    loop {
-        print_status();
+    let mut ticks = 0u64;
+    while !DONE.load(Ordering::SeqCst) {
+        if term::is_tty() {
+            print_status();
+        } else if !term::is_quiet() && ticks > 0 && ticks.is_multiple_of(PLAIN_INTERVAL) {
+            print_plain_status();
+        }
+        ticks += 1;
         sleep(Duration::from_secs(1));
     }
 }
 
+// redraws the status line, unless it's `--progress plain`
 fn print_status() {
-    print!(
-        "\x1b[2K\rreqs: {} | found: {} ({} total)",
-        REQ_COUNTER.load(Ordering::SeqCst),
+    if !term::is_tty() {
+        return;
+    }
+    let status = format!("\x1b[2K\r{}", status_line());
+    // checked with stderr locked, so nothing gets drawn after the summary cleared the line
+    let mut stderr = stderr().lock();
+    if !DONE.load(Ordering::SeqCst) {
+        let _ = stderr.write_all(status.as_bytes());
+        let _ = stderr.flush();
+    }
+}
+
+// the status line on a line of its own, for `--progress plain`
+fn print_plain_status() {
+    let mut stderr = stderr().lock();
+    if !DONE.load(Ordering::SeqCst) {
+        let _ = writeln!(stderr, "progress: {}", status_line());
+    }
+}
+
+// the counters shown while running
+fn status_line() -> String {
+    let reqs = REQ_COUNTER.load(Ordering::SeqCst);
+    let mut status = match expected_progress() {
+        Some((fraction, expected)) => {
+            format!("{:.1}% | reqs: {reqs}/~{expected}", fraction * 100.0)
+        }
+        None => format!("reqs: {reqs}"),
+    };
+    let _ = write!(
+        status,
+        " | found: {} ({} total)",
         UUID_COUNTER.load(Ordering::SeqCst),
         UUID_ALL_COUNTER.load(Ordering::SeqCst)
     );
-    let _ = stdout().lock().flush();
+    let cached = CACHED_COUNTER.load(Ordering::SeqCst);
+    if cached > 0 {
+        let _ = write!(status, " | cached: {cached}");
+    }
+    if let Some(p) = latency::percentiles() {
+        let _ = write!(
+            status,
+            " | p50/95/99: {}/{}/{}ms",
+            p.p50.as_millis(),
+            p.p95.as_millis(),
+            p.p99.as_millis()
+        );
+    }
+    if let Some(errors) = error_summary() {
+        let _ = write!(status, " | errors: {errors}");
+    }
+    let failed = FAILED_BATCHES.load(Ordering::SeqCst);
+    if failed > 0 {
+        let _ = write!(status, " | dropped batches: {failed}");
+    }
+    if BREAKER.is_open() || CONTROL.is_paused() {
+        status.push_str(" | paused");
+    }
+    let delay = THROTTLE.delay();
+    if !delay.is_zero() {
+        let _ = write!(status, " | slowed down: {}ms", delay.as_millis());
+    }
+    status
+}
+
+// how much of the keyspace is answered (skipped candidates included), and how many requests the
+// whole run will take. candidates still to come are expected to be skipped as often as the ones so
+// far, so the estimate gets better as the run goes on.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn expected_progress() -> Option<(f64, u64)> {
+    let total = *TOTAL_CANDIDATES.get()?;
+    if total == 0 {
+        return None;
+    }
+    let generated = KEYSPACE.peek().min(total);
+    let skipped = SKIPPED_CANDIDATES.load(Ordering::SeqCst).min(generated);
+    let requested = generated - skipped;
+    let kept = if generated == 0 {
+        1.0
+    } else {
+        requested as f64 / generated as f64
+    };
+    let remaining = ((total - generated) as f64 * kept).round() as u64;
+    // a request per batch of 10 candidates, or more if failed batches were looked up name by name
+    let expected = (REQ_COUNTER.load(Ordering::SeqCst) as u64).max(requested.div_ceil(10))
+        + remaining.div_ceil(10);
+    let fraction = KEYSPACE.current().min(total) as f64 / total as f64;
+    Some((fraction, expected))
+}
+
+// clears the status line for good and prints the totals of the run
+#[allow(clippy::cast_precision_loss)]
+fn print_summary(elapsed: Duration, sample: Option<Sample>) {
+    {
+        let mut stderr = stderr().lock();
+        DONE.store(true, Ordering::SeqCst);
+        if term::is_tty() {
+            let _ = stderr.write_all(b"\x1b[2K\r");
+            let _ = stderr.flush();
+        }
+    }
+    info!(
+        "done in {}: {} requests, {} uuids found ({} total), {} cached",
+        format_duration(elapsed),
+        REQ_COUNTER.load(Ordering::SeqCst),
+        UUID_COUNTER.load(Ordering::SeqCst),
+        UUID_ALL_COUNTER.load(Ordering::SeqCst),
+        CACHED_COUNTER.load(Ordering::SeqCst)
+    );
+    if let Some(p) = latency::percentiles() {
+        info!(
+            "latency p50/95/99: {}/{}/{}ms",
+            p.p50.as_millis(),
+            p.p95.as_millis(),
+            p.p99.as_millis()
+        );
+    }
+    if let Some(errors) = error_summary() {
+        eprintln!("errors: {errors}");
+    }
+    let versions = VERSION_COUNTERS
+        .iter()
+        .enumerate()
+        .filter_map(|(v, n)| {
+            let n = n.load(Ordering::SeqCst);
+            (n > 0).then(|| format!("{n} v{v}"))
+        })
+        .collect::<Vec<_>>();
+    if !versions.is_empty() {
+        info!("uuid versions: {}", versions.join(", "));
+    }
+    if let Some(counts) = WORD_COUNTS.get() {
+        let total = counts.values().sum::<u64>();
+        let found = FOUND_COUNT.load(Ordering::SeqCst);
+        info!(
+            "found words make up {found} of the {total} counted in the wordlist ({:.1}%)",
+            100.0 * found as f64 / total.max(1) as f64
+        );
+    }
+    if let Some(sample) = sample {
+        let estimate = |n: &AtomicUsize| (n.load(Ordering::SeqCst) as f64 / sample.ratio).round();
+        info!(
+            "a full run would find about {} uuids ({} total), going by this {:.2}% sample",
+            estimate(&UUID_COUNTER),
+            estimate(&UUID_ALL_COUNTER),
+            sample.ratio * 100.0
+        );
+    }
+    let duplicates = DUPLICATE_CANDIDATES.load(Ordering::SeqCst);
+    if duplicates > 0 {
+        info!(
+            "{duplicates} candidates came up more than once and were only queried the first time"
+        );
+    }
+    let dedup_skipped = DEDUP_SKIPPED.load(Ordering::SeqCst);
+    if dedup_skipped > 0 {
+        info!("{dedup_skipped} names were skipped, someone else already checked them");
+    }
+    let failed = FAILED_BATCHES.load(Ordering::SeqCst);
+    if failed > 0 {
+        eprintln!("warn: {failed} batches were dropped, their names were never answered");
+    }
+    if ABORTED.load(Ordering::SeqCst) {
+        let at = KEYSPACE.current();
+        eprintln!("stopped at keyspace position {at}, continue with --skip-keyspace {at}");
+    }
+}
+
+// `1h02m03s`, `2m03s` or `3.4s`
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..60 => format!("{:.1}s", d.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+// `index/count` from `--shard`
+#[derive(Debug, Clone, Copy)]
+struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    // fnv-1a, so every machine and version agrees on which shard a name belongs to
+    fn contains(self, name: &str) -> bool {
+        Self::index_of(name, self.count) == self.index
+    }
+
+    // which of `count` shards a (lowercase) name belongs to
+    fn index_of(name: &str, count: u64) -> u64 {
+        fnv1a(name) % count
+    }
+}
+
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// `--sample`, a random share of the candidates. whether a name is in it only depends on the name
+// and the seed, so the same seed picks the same names no matter the order or the other options.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    ratio: f64,
+    seed: u64,
+}
+
+impl Sample {
+    #[allow(clippy::cast_precision_loss)]
+    fn contains(self, name: &str) -> bool {
+        // fnv-1a alone clusters similar names, mixing it through the prng spreads them out
+        let hash = Rng::new(fnv1a(name) ^ self.seed).next_u64();
+        (hash as f64) < self.ratio * u64::MAX as f64
+    }
+}
+
+impl FromStr for Sample {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ratio = s
+            .strip_suffix('%')
+            .map_or_else(|| s.parse(), |p| p.parse::<f64>().map(|p| p / 100.0))
+            .map_err(|_| format!("expected a ratio like 5% or 0.05, got `{s}`"))?;
+        if !(ratio > 0.0 && ratio <= 1.0) {
+            return Err(format!(
+                "the sample has to be above 0% and at most 100%, got `{s}`"
+            ));
+        }
+        // the seed is filled in from `--sample-seed` once the arguments are parsed
+        Ok(Self { ratio, seed: 0 })
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected INDEX/COUNT, got `{s}`"))?;
+        let index = index
+            .parse()
+            .map_err(|_| format!("invalid shard index `{index}`"))?;
+        let count: u64 = count
+            .parse()
+            .map_err(|_| format!("invalid shard count `{count}`"))?;
+        if index >= count {
+            return Err(format!(
+                "shard index has to be below the count (0 to {}), got {index}",
+                count.saturating_sub(1)
+            ));
+        }
+        Ok(Self { index, count })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    // look names up with the backend
+    Api,
+    // compute offline-mode uuids locally
+    OfflineUuid,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "api" => Ok(Self::Api),
+            "offline-uuid" => Ok(Self::OfflineUuid),
+            _ => Err(format!("expected api or offline-uuid, got `{s}`")),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Api => "api",
+            Self::OfflineUuid => "offline-uuid",
+        })
+    }
+}
+
+// `--on-existing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnExisting {
+    Append,
+    Overwrite,
+    Abort,
+}
+
+impl FromStr for OnExisting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "append" => Ok(Self::Append),
+            "overwrite" => Ok(Self::Overwrite),
+            "abort" => Ok(Self::Abort),
+            _ => Err(format!("expected append, overwrite or abort, got `{s}`")),
+        }
+    }
+}
+
+// `min..max`, or a single duration for a fixed value
+#[derive(Debug, Clone, Copy)]
+struct DurationRange {
+    min: Duration,
+    max: Duration,
+}
+
+impl DurationRange {
+    #[allow(clippy::cast_possible_truncation)]
+    fn sample(&self, rng: &mut Rng) -> Duration {
+        let span = self.max.saturating_sub(self.min).as_nanos();
+        if span == 0 {
+            return self.min;
+        }
+        let offset = (u128::from(rng.next_u64()) * span) >> 64;
+        self.min + Duration::from_nanos(offset as u64)
+    }
+}
+
+impl FromStr for DurationRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = if let Some((min, max)) = s.split_once("..") {
+            (parse_duration(min)?, parse_duration(max)?)
+        } else {
+            let d = parse_duration(s)?;
+            (d, d)
+        };
+        if min > max {
+            return Err(format!("range start is bigger than its end: {s}"));
+        }
+        Ok(Self { min, max })
+    }
+}
+
+// parses durations like `250ms`, `5s`, `10m`, `1h` or `7d`
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num.parse().map_err(|_| format!("invalid duration: {s}"))?;
+    let secs = match unit {
+        "ms" => num / 1000.0,
+        "s" | "" => num,
+        "m" => num * 60.0,
+        "h" => num * 60.0 * 60.0,
+        "d" => num * 60.0 * 60.0 * 24.0,
+        _ => {
+            return Err(format!(
+                "unknown duration unit `{unit}` (use ms, s, m, h or d)"
+            ));
+        }
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("invalid duration: {s}"))
+}
+
+// small splitmix64 prng, so a seed gives the same order on every platform and version
+struct Rng(u64);
+
+impl Rng {
+    const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..n
+    #[allow(clippy::cast_possible_truncation)]
+    fn below(&mut self, n: usize) -> usize {
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+
+    // fisher-yates
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration(" 5 "), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_hours(2)));
+        for bad in ["", "ms", "3w", "1.2.3s", "-1s"] {
+            assert!(parse_duration(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn duration_ranges_parse_and_sample_within_their_bounds() {
+        let range: DurationRange = "100ms..2s".parse().unwrap();
+        assert_eq!(range.min, Duration::from_millis(100));
+        assert_eq!(range.max, Duration::from_secs(2));
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            assert!((range.min..range.max).contains(&range.sample(&mut rng)));
+        }
+
+        let fixed: DurationRange = "1s".parse().unwrap();
+        assert_eq!(fixed.sample(&mut rng), Duration::from_secs(1));
+        assert!("2s..1s".parse::<DurationRange>().is_err());
+        assert!("1s..".parse::<DurationRange>().is_err());
+    }
+
+    #[test]
+    fn shards_split_names_without_overlap() {
+        // fnv-1a test vectors, other machines and versions have to agree on them
+        assert_eq!(fnv1a(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a("a"), 0xaf63_dc4c_8601_ec8c);
+
+        let shards = (0..4)
+            .map(|i| format!("{i}/4").parse::<Shard>().unwrap())
+            .collect::<Vec<_>>();
+        for name in ["notch", "jeb_", "dinnerbone", "a", ""] {
+            assert_eq!(shards.iter().filter(|s| s.contains(name)).count(), 1);
+        }
+        for bad in ["4/4", "1", "a/4", "0/0", "1/b"] {
+            assert!(bad.parse::<Shard>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn samples_keep_their_share_of_names() {
+        let Sample { ratio, .. } = "5%".parse().unwrap();
+        assert!((ratio - 0.05).abs() < f64::EPSILON);
+        let Sample { ratio, .. } = "0.25".parse().unwrap();
+        assert!((ratio - 0.25).abs() < f64::EPSILON);
+        for bad in ["0", "0%", "150%", "1.5", "some", "NaN"] {
+            assert!(bad.parse::<Sample>().is_err(), "{bad}");
+        }
+
+        let names = (0..10_000).map(|i| format!("name{i}")).collect::<Vec<_>>();
+        let sample = Sample {
+            ratio: 0.1,
+            seed: 1,
+        };
+        let kept = names.iter().filter(|n| sample.contains(n)).count();
+        assert!((800..1200).contains(&kept), "{kept}");
+        // the same seed picks the same names, another seed other ones
+        assert_eq!(names.iter().filter(|n| sample.contains(n)).count(), kept);
+        let other = Sample { seed: 2, ..sample };
+        assert!(
+            names
+                .iter()
+                .any(|n| sample.contains(n) != other.contains(n))
+        );
+
+        let all = Sample {
+            ratio: 1.0,
+            seed: 1,
+        };
+        assert!(names.iter().all(|n| all.contains(n)));
+    }
 }
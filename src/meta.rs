@@ -0,0 +1,140 @@
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io::Read;
+use std::sync::atomic::Ordering;
+
+use crate::clock::{DateTime, now_unix};
+use crate::s3::{self, Object};
+use crate::{
+    CACHED_COUNTER, Cli, FAILED_BATCHES, KEYSPACE, REQ_COUNTER, UUID_ALL_COUNTER, UUID_COUNTER,
+};
+
+// `<output>.meta.json` next to the dump, recording what produced it. written as soon as the
+// inputs are hashed (so interrupted runs still have one) and again with the totals at the end.
+// an s3 output gets it as an object next to it.
+pub struct RunMeta {
+    path: String,
+    object: Option<Object>,
+    json: Value,
+}
+
+impl RunMeta {
+    pub async fn start(args: Cli) -> eyre::Result<Self> {
+        let started = now_unix();
+        let mut inputs = serde_json::Map::new();
+        for (name, path) in [
+            ("suffixes", args.suffixes.as_ref()),
+            ("ignored_names", args.ignored_names.as_ref()),
+        ] {
+            let Some(path) = path else { continue };
+            inputs.insert(name.into(), hashed_input(path).await?);
+        }
+        // a single wordlist is recorded like it was before there could be several
+        if let [spec] = args.wordlists.as_slice() {
+            inputs.insert("wordlist".into(), hashed_input(&spec.path).await?);
+        } else {
+            let mut wordlists = vec![];
+            for spec in &args.wordlists {
+                let mut input = hashed_input(&spec.path).await?;
+                input["weight"] = json!(spec.weight);
+                wordlists.push(input);
+            }
+            inputs.insert("wordlists".into(), Value::Array(wordlists));
+            inputs.insert(
+                "wordlist_order".into(),
+                json!(args.wordlist_order.to_string()),
+            );
+        }
+        let mut ignored = vec![];
+        for source in &args.ignored {
+            // hashing a database that may be written to while running says nothing about what
+            // was ignored
+            if source.sqlite {
+                ignored.push(json!({ "path": source.path, "sqlite": true }));
+                continue;
+            }
+            let mut input = hashed_input(&source.path).await?;
+            input["truncation"] = json!(source.truncation.or(args.ignored_truncation));
+            ignored.push(input);
+        }
+        if !ignored.is_empty() {
+            inputs.insert("ignored_uuids".into(), ignored.into());
+        }
+        let path = format!("{}.meta.json", args.output_path);
+        let meta = Self {
+            object: s3::is_s3(&path)
+                .then(|| Object::open(&path, &args))
+                .transpose()?,
+            path,
+            json: json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "args": std::env::args().collect::<Vec<_>>(),
+                "output": args.output_path,
+                "mode": args.mode.to_string(),
+                "backends": crate::backend::specs(&args)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                "inputs": inputs,
+                "started": timestamp(started),
+                "finished": null,
+            }),
+        };
+        meta.write().await?;
+        Ok(meta)
+    }
+
+    pub async fn finish(mut self) -> eyre::Result<()> {
+        self.json["finished"] = timestamp(now_unix());
+        self.json["totals"] = json!({
+            "requests": REQ_COUNTER.load(Ordering::SeqCst),
+            "found": UUID_COUNTER.load(Ordering::SeqCst),
+            "found_total": UUID_ALL_COUNTER.load(Ordering::SeqCst),
+            "cached": CACHED_COUNTER.load(Ordering::SeqCst),
+            "dropped_batches": FAILED_BATCHES.load(Ordering::SeqCst),
+            "keyspace_position": KEYSPACE.current(),
+            "latency": crate::latency::json(),
+        });
+        self.write().await
+    }
+
+    async fn write(&self) -> eyre::Result<()> {
+        let json = serde_json::to_string_pretty(&self.json)? + "\n";
+        match &self.object {
+            Some(object) => object.put(json.into_bytes()).await?,
+            None => tokio::fs::write(&self.path, json).await?,
+        }
+        Ok(())
+    }
+}
+
+fn timestamp(unix: u64) -> Value {
+    json!({
+        "unix": unix,
+        "utc": DateTime::from_unix(unix).iso(),
+    })
+}
+
+async fn hashed_input(path: &str) -> eyre::Result<Value> {
+    let hashed = path.to_string();
+    let sha256 = tokio::task::spawn_blocking(move || sha256_file(&hashed)).await??;
+    Ok(json!({ "path": path, "sha256": sha256 }))
+}
+
+fn sha256_file(path: &str) -> eyre::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    }))
+}
@@ -0,0 +1,151 @@
+use std::io::{self, BufRead, Read};
+use uuid::Uuid;
+
+use crate::clock::{DateTime, now_unix};
+use crate::sink::Timestamps;
+
+// `--output-format msgpack`, a map per hit: `uuid` as 16 bytes (bin), `name` as a string and with
+// `--timestamps` `found_at`, unix seconds as an int or the iso time as a string. records are
+// written back to back, so a reader just decodes values until the end.
+pub fn record(uuid: Uuid, name: &str, timestamps: Option<Timestamps>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(48);
+    out.push(0x80 | if timestamps.is_some() { 3 } else { 2 });
+    str(&mut out, "uuid");
+    out.extend([0xc4, 16]);
+    out.extend(uuid.as_bytes());
+    str(&mut out, "name");
+    str(&mut out, name);
+    match timestamps {
+        None => {}
+        Some(Timestamps::Unix) => {
+            str(&mut out, "found_at");
+            out.push(0xcf);
+            out.extend(now_unix().to_be_bytes());
+        }
+        Some(Timestamps::Iso) => {
+            str(&mut out, "found_at");
+            str(&mut out, &DateTime::from_unix(now_unix()).iso());
+        }
+    }
+    out
+}
+
+// names, keys and iso times are all shorter than 32 bytes, a fixstr fits them
+#[allow(clippy::cast_possible_truncation)]
+fn str(out: &mut Vec<u8>, s: &str) {
+    out.push(0xa0 | s.len() as u8);
+    out.extend(s.as_bytes());
+}
+
+// the length of the complete records at the start of `reader`, anything after them is torn
+pub fn complete_len(reader: &mut impl BufRead) -> io::Result<u64> {
+    let mut complete = 0;
+    while let Some(len) = torn_or(value_len(reader))? {
+        complete += len;
+    }
+    Ok(complete)
+}
+
+// the complete records in `data`, a torn one at the end is left out
+#[allow(clippy::cast_possible_truncation)]
+pub fn records(data: &[u8]) -> io::Result<Vec<&[u8]>> {
+    let mut records = vec![];
+    let mut offset = 0;
+    while let Some(len) = torn_or(value_len(&mut &data[offset..]))? {
+        records.push(&data[offset..offset + len as usize]);
+        offset += len as usize;
+    }
+    Ok(records)
+}
+
+// running out of bytes halfway through a record is a torn end, not an error
+fn torn_or(res: io::Result<u64>) -> io::Result<Option<u64>> {
+    match res {
+        Ok(len) => Ok(Some(len)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// reads past one value of the kinds `record` writes, returning how many bytes it took
+fn value_len(reader: &mut impl BufRead) -> io::Result<u64> {
+    let mut marker = [0];
+    reader.read_exact(&mut marker)?;
+    let (mut len, data, values) = match marker[0] {
+        // a map's entries are a key and a value each
+        m @ 0x80..=0x8f => (1, 0, u64::from(m & 0x0f) * 2),
+        m @ 0xa0..=0xbf => (1, u64::from(m & 0x1f), 0),
+        0xc4 => {
+            let mut data = [0];
+            reader.read_exact(&mut data)?;
+            (2, u64::from(data[0]), 0)
+        }
+        0xcf => (1, 8, 0),
+        m => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("not a msgpack output written by uuidump (marker {m:#04x})"),
+            ));
+        }
+    };
+    if io::copy(&mut Read::take(&mut *reader, data), &mut io::sink())? < data {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    len += data;
+    for _ in 0..values {
+        len += value_len(reader)?;
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTCH: Uuid = Uuid::from_u128(0x069a_79f4_44e9_4726_a5be_fca9_0e38_aaf5);
+
+    #[test]
+    fn records_are_maps_of_their_fields() {
+        let mut expected = vec![0x82, 0xa4];
+        expected.extend(b"uuid");
+        expected.extend([0xc4, 0x10]);
+        expected.extend([
+            0x06, 0x9a, 0x79, 0xf4, 0x44, 0xe9, 0x47, 0x26, 0xa5, 0xbe, 0xfc, 0xa9, 0x0e, 0x38,
+            0xaa, 0xf5,
+        ]);
+        expected.push(0xa4);
+        expected.extend(b"name");
+        expected.push(0xa5);
+        expected.extend(b"Notch");
+        assert_eq!(record(NOTCH, "Notch", None), expected);
+    }
+
+    #[test]
+    fn timestamps_are_a_third_field() {
+        let unix = record(NOTCH, "Notch", Some(Timestamps::Unix));
+        assert_eq!(unix[0], 0x83);
+        let at = unix.len() - 9 - 9;
+        assert_eq!(unix[at], 0xa8);
+        assert_eq!(&unix[at + 1..at + 9], b"found_at");
+        assert_eq!(unix[at + 9], 0xcf);
+
+        let iso = record(NOTCH, "Notch", Some(Timestamps::Iso));
+        let at = iso.len() - 21;
+        // a fixstr like `2026-01-01T00:00:00Z`
+        assert_eq!(iso[at], 0xb4);
+        assert!(iso.ends_with(b"Z"));
+    }
+
+    #[test]
+    fn torn_records_at_the_end_are_left_out() {
+        let mut data = record(NOTCH, "Notch", None);
+        let first = data.len();
+        data.extend(record(NOTCH, "jeb_", Some(Timestamps::Unix)));
+        let second = data.len();
+        data.extend(&record(NOTCH, "Dinnerbone", None)[..20]);
+        let records = records(&data).unwrap();
+        assert_eq!(records, [&data[..first], &data[first..second]]);
+        assert_eq!(complete_len(&mut &data[..]).unwrap(), second as u64);
+        assert!(complete_len(&mut &b"not msgpack"[..]).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt::Write as _;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::backend::offline_uuid;
+use crate::open_output;
+use crate::term::clear_line;
+
+// `--pairs` file of `name,offline uuid,premium uuid` lines, for moving offline-mode player data
+// over to online mode. the premium uuid is empty for names nobody owns.
+pub struct Pairs {
+    file: Mutex<tokio::fs::File>,
+}
+
+impl Pairs {
+    pub async fn open(path: &str) -> eyre::Result<Self> {
+        Ok(Self {
+            file: Mutex::new(open_output(path).await?),
+        })
+    }
+
+    // records the answer for every queried name. the offline uuid depends on the exact
+    // capitalization, so found names use the one their owner has.
+    pub async fn record(&self, queried: &[String], found: &[(Uuid, String)]) {
+        let mut lines = String::new();
+        for name in queried {
+            match found.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)) {
+                Some((uuid, canonical)) => {
+                    let _ = writeln!(lines, "{canonical},{},{uuid}", offline_uuid(canonical));
+                }
+                None => {
+                    let _ = writeln!(lines, "{name},{},", offline_uuid(name));
+                }
+            }
+        }
+        if lines.is_empty() {
+            return;
+        }
+        let mut file = self.file.lock().await;
+        let res = async {
+            file.write_all(lines.as_bytes()).await?;
+            file.flush().await
+        };
+        if let Err(e) = res.await {
+            eprintln!("{}warn: failed to write pairs: {e}", clear_line());
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+// a `--pattern` template like `xX{word}Xx` or `{word}_{00-99}`, expanded lazily into the
+// candidates for each word. `{word}` is the word itself, `{N-M}` every number from N to M
+// (zero-padded to the width of N, so `{00-99}` gives 00, 01, ...), `{yy}` is short for `{00-99}`
+// and anything else is taken literally.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    parts: Vec<Part>,
+    // start and end of every range in `parts`
+    ranges: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Word,
+    Range { start: u64, end: u64, width: usize },
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![];
+        let mut rest = s;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed `{{` in pattern `{s}`"))?
+                + open;
+            parts.push(parse_placeholder(&rest[open + 1..close])?);
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        if !parts.iter().any(|p| matches!(p, Part::Word)) {
+            return Err(format!("pattern `{s}` has no `{{word}}` in it"));
+        }
+        let ranges = parts
+            .iter()
+            .filter_map(|p| match p {
+                Part::Range { start, end, .. } => Some((*start, *end)),
+                Part::Literal(_) | Part::Word => None,
+            })
+            .collect();
+        Ok(Self { parts, ranges })
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part, String> {
+    match placeholder {
+        "word" => return Ok(Part::Word),
+        "yy" => {
+            return Ok(Part::Range {
+                start: 0,
+                end: 99,
+                width: 2,
+            });
+        }
+        _ => {}
+    }
+    let invalid = || format!("unknown placeholder `{{{placeholder}}}`, expected word, yy or N-M");
+    let (start, end) = placeholder.split_once('-').ok_or_else(invalid)?;
+    let width = start.len();
+    let start = start.parse::<u64>().map_err(|_| invalid())?;
+    let end = end.parse::<u64>().map_err(|_| invalid())?;
+    if start > end {
+        return Err(format!("range `{{{placeholder}}}` goes backwards"));
+    }
+    Ok(Part::Range { start, end, width })
+}
+
+// the patterns to expand every word with, just the word itself if none were given
+pub fn or_word(patterns: Vec<Pattern>) -> Vec<Pattern> {
+    if patterns.is_empty() {
+        vec![Pattern {
+            parts: vec![Part::Word],
+            ranges: vec![],
+        }]
+    } else {
+        patterns
+    }
+}
+
+impl Pattern {
+    // how many candidates every word expands to
+    pub fn count(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|(start, end)| end - start + 1)
+            .product()
+    }
+
+    pub fn expand<'a>(&'a self, word: &'a str) -> Expansions<'a> {
+        let values = self.ranges.iter().map(|(start, _)| *start).collect();
+        Expansions {
+            pattern: self,
+            word,
+            values,
+            done: false,
+        }
+    }
+}
+
+// every combination of the ranges of a pattern, the last range counting up fastest
+pub struct Expansions<'a> {
+    pattern: &'a Pattern,
+    word: &'a str,
+    // the current value of every range, in order
+    values: Vec<u64>,
+    done: bool,
+}
+
+impl Iterator for Expansions<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.done {
+            return None;
+        }
+        let mut candidate = String::new();
+        let mut values = self.values.iter();
+        for part in &self.pattern.parts {
+            match part {
+                Part::Literal(s) => candidate.push_str(s),
+                Part::Word => candidate.push_str(self.word),
+                Part::Range { width, .. } => {
+                    let value = values.next().expect("one value per range");
+                    let _ = write!(candidate, "{value:0width$}");
+                }
+            }
+        }
+
+        // advance like an odometer, finishing once the first range wraps around
+        self.done = true;
+        for (value, (start, end)) in self.values.iter_mut().zip(&self.pattern.ranges).rev() {
+            if *value < *end {
+                *value += 1;
+                self.done = false;
+                break;
+            }
+            *value = *start;
+        }
+        Some(candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(pattern: &str, word: &str) -> Vec<String> {
+        pattern.parse::<Pattern>().unwrap().expand(word).collect()
+    }
+
+    #[test]
+    fn words_and_literals_are_kept_as_they_are() {
+        assert_eq!(expand("xX{word}Xx", "notch"), ["xXnotchXx"]);
+        assert_eq!(expand("{word}_{word}", "a"), ["a_a"]);
+        assert_eq!(
+            or_word(vec![])[0].expand("notch").collect::<Vec<_>>(),
+            ["notch"]
+        );
+    }
+
+    #[test]
+    fn ranges_are_padded_to_the_width_of_their_start() {
+        let years = expand("{word}{yy}", "jeb");
+        assert_eq!(years.len(), 100);
+        assert_eq!(
+            [&years[0], &years[7], &years[99]],
+            ["jeb00", "jeb07", "jeb99"]
+        );
+        assert_eq!(expand("{word}{8-11}", "a"), ["a8", "a9", "a10", "a11"]);
+        assert_eq!(
+            expand("{word}{098-101}", "a"),
+            ["a098", "a099", "a100", "a101"]
+        );
+    }
+
+    #[test]
+    fn the_last_range_counts_up_fastest() {
+        let pattern = "{0-1}{word}{1-3}".parse::<Pattern>().unwrap();
+        assert_eq!(pattern.count(), 6);
+        assert_eq!(
+            pattern.expand("x").collect::<Vec<_>>(),
+            ["0x1", "0x2", "0x3", "1x1", "1x2", "1x3"]
+        );
+    }
+
+    #[test]
+    fn bad_patterns_say_what_is_wrong() {
+        let err = |s: &str| s.parse::<Pattern>().unwrap_err();
+        assert_eq!(err("{00-99}"), "pattern `{00-99}` has no `{word}` in it");
+        assert_eq!(
+            err("{word}{00-99"),
+            "unclosed `{` in pattern `{word}{00-99`"
+        );
+        assert_eq!(err("{word}{9-0}"), "range `{9-0}` goes backwards");
+        for bad in ["{word}{yyyy}", "{word}{a-b}", "{word}{-5}", "{word}{}"] {
+            assert!(err(bad).starts_with("unknown placeholder"), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::backend::{Backend, fetch};
+use crate::{Rng, random_seed};
+
+// how long `--probe` measures for, split evenly across the concurrency levels
+const PROBE_DURATION: Duration = Duration::from_mins(1);
+// levels with more failed requests than this don't count as achievable
+const MAX_ERROR_RATE: f64 = 0.05;
+
+struct Level {
+    threads: usize,
+    requests_per_sec: f64,
+    error_rate: f64,
+}
+
+// measures the request rate and error rate at increasing thread counts (doubling up to
+// `max_threads`), and returns the thread count that got the most requests through without
+// erroring too much. the probe queries random names, so its requests aren't counted anywhere.
+pub async fn run(backends: &[Backend], max_threads: usize) -> usize {
+    let mut levels = vec![];
+    let mut threads = 1;
+    while threads < max_threads {
+        levels.push(threads);
+        threads *= 2;
+    }
+    levels.push(max_threads);
+    #[allow(clippy::cast_possible_truncation)]
+    let slice = PROBE_DURATION / levels.len() as u32;
+
+    eprintln!(
+        "probing throughput for {}s at {} thread counts",
+        PROBE_DURATION.as_secs(),
+        levels.len()
+    );
+    let mut results = vec![];
+    for threads in levels {
+        let level = measure(backends, threads, slice).await;
+        eprintln!(
+            "{:>4} threads: {:.1} req/s, {:.1}% errors",
+            level.threads,
+            level.requests_per_sec,
+            level.error_rate * 100.0
+        );
+        results.push(level);
+    }
+
+    let best = results
+        .iter()
+        .filter(|l| l.error_rate <= MAX_ERROR_RATE)
+        .max_by(|a, b| a.requests_per_sec.total_cmp(&b.requests_per_sec))
+        .map_or(1, |l| l.threads);
+    eprintln!("best throughput with {best} threads");
+    best
+}
+
+#[allow(clippy::cast_precision_loss)]
+async fn measure(backends: &[Backend], threads: usize, duration: Duration) -> Level {
+    let ok = Arc::new(AtomicUsize::new(0));
+    let failed = Arc::new(AtomicUsize::new(0));
+    let deadline = Instant::now() + duration;
+    let mut handles = vec![];
+    for i in 0..threads {
+        let backend = backends[i % backends.len()].clone();
+        let (ok, failed) = (ok.clone(), failed.clone());
+        handles.push(tokio::spawn(async move {
+            let mut rng = Rng::new(random_seed() ^ i as u64);
+            while Instant::now() < deadline {
+                let names = (0..10).map(|_| random_name(&mut rng)).collect::<Vec<_>>();
+                let counter = if fetch(&backend, &names).await.is_ok() {
+                    &ok
+                } else {
+                    &failed
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+    let ok = ok.load(Ordering::Relaxed);
+    let failed = failed.load(Ordering::Relaxed);
+    Level {
+        threads,
+        requests_per_sec: ok as f64 / duration.as_secs_f64(),
+        error_rate: failed as f64 / (ok + failed).max(1) as f64,
+    }
+}
+
+// 10 random lowercase letters and digits, almost certainly not taken
+fn random_name(rng: &mut Rng) -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    (0..10)
+        .map(|_| char::from(CHARS[rng.below(CHARS.len())]))
+        .collect()
+}
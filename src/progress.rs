@@ -0,0 +1,210 @@
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::term::clear_line;
+use crate::{
+    CACHED_COUNTER, FAILED_BATCHES, KEYSPACE, REQ_COUNTER, UUID_ALL_COUNTER, UUID_COUNTER,
+    WORD_COUNTER, expected_progress, open_output,
+};
+
+// posts a progress snapshot to `--progress-webhook` every interval, and a last one once the run
+// is done
+pub struct Progress {
+    client: reqwest::Client,
+    url: String,
+    started: Instant,
+    words: usize,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Progress {
+    pub fn start(
+        url: &str,
+        interval: Duration,
+        timeout: Duration,
+        words: usize,
+    ) -> eyre::Result<Self> {
+        // rates are measured from here, not counting the time spent loading the wordlist
+        let started = Instant::now();
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        let task = tokio::spawn({
+            let client = client.clone();
+            let url = url.to_string();
+            async move {
+                let mut ticker = tokio::time::interval(interval);
+                // the first tick is immediate, there's nothing to report yet
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    post(&client, &url, &snapshot(started, words, false)).await;
+                }
+            }
+        });
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            started,
+            words,
+            task,
+        })
+    }
+
+    pub async fn finish(self) {
+        self.task.abort();
+        post(
+            &self.client,
+            &self.url,
+            &snapshot(self.started, self.words, true),
+        )
+        .await;
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, snapshot: &Value) {
+    let res = client
+        .post(url)
+        .header("content-type", "application/json")
+        .body(snapshot.to_string())
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status);
+    if let Err(e) = res {
+        eprintln!(
+            "{}warn: failed to post progress to {url}: {e}",
+            clear_line()
+        );
+    }
+}
+
+// where `--status-fd` or `--status-json` send the snapshots
+pub enum StatusTarget {
+    Fd(i32),
+    Path(String),
+}
+
+// writes a progress snapshot as a line of json every interval, and a last one once the run is
+// done, so wrappers can show progress without picking apart the status line
+pub struct StatusStream {
+    out: Arc<Mutex<tokio::fs::File>>,
+    started: Instant,
+    words: usize,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl StatusStream {
+    pub async fn start(
+        target: StatusTarget,
+        interval: Duration,
+        words: usize,
+    ) -> eyre::Result<Self> {
+        let started = Instant::now();
+        let out = match target {
+            StatusTarget::Fd(fd) => tokio::fs::File::from_std(open_fd(fd)?),
+            StatusTarget::Path(path) => open_output(&path).await?,
+        };
+        let out = Arc::new(Mutex::new(out));
+        let task = tokio::spawn({
+            let out = out.clone();
+            async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    write_line(&out, &snapshot(started, words, false)).await;
+                }
+            }
+        });
+        Ok(Self {
+            out,
+            started,
+            words,
+            task,
+        })
+    }
+
+    pub async fn finish(self) {
+        self.task.abort();
+        write_line(&self.out, &snapshot(self.started, self.words, true)).await;
+    }
+}
+
+// takes over a descriptor the parent opened for us, like `3>status.jsonl` in a shell
+#[cfg(unix)]
+fn open_fd(fd: i32) -> eyre::Result<std::fs::File> {
+    use std::os::fd::FromRawFd;
+
+    const F_GETFD: i32 = 1;
+    const FD_CLOEXEC: i32 = 1;
+    unsafe extern "C" {
+        fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+    }
+
+    // anything the process opened itself (like the runtime's pipes) is close-on-exec, only an
+    // inherited descriptor isn't. that keeps a descriptor the parent didn't pass from being
+    // mistaken for one that just happens to be open under the same number.
+    // SAFETY: F_GETFD only reads the descriptor's flags
+    let flags = if fd < 0 {
+        -1
+    } else {
+        unsafe { fcntl(fd, F_GETFD) }
+    };
+    if flags < 0 || flags & FD_CLOEXEC != 0 {
+        eyre::bail!("--status-fd {fd} is not a file descriptor uuidump was started with");
+    }
+    // SAFETY: the descriptor is open and was handed to us for this, nothing else uses it
+    Ok(unsafe { std::fs::File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> eyre::Result<std::fs::File> {
+    eyre::bail!("--status-fd needs unix file descriptors, use --status-json instead")
+}
+
+async fn write_line(out: &Mutex<tokio::fs::File>, snapshot: &Value) {
+    let mut line = snapshot.to_string();
+    line.push('\n');
+    let mut out = out.lock().await;
+    let res = async {
+        out.write_all(line.as_bytes()).await?;
+        out.flush().await
+    }
+    .await;
+    drop(out);
+    if let Err(e) = res {
+        eprintln!("{}warn: failed to write status: {e}", clear_line());
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn snapshot(started: Instant, words: usize, done: bool) -> Value {
+    let elapsed = started.elapsed().as_secs_f64();
+    let words_done = WORD_COUNTER.load(Ordering::SeqCst);
+    let reqs = REQ_COUNTER.load(Ordering::SeqCst);
+    let words_per_sec = words_done as f64 / elapsed.max(1.0);
+    let eta =
+        (!done && words_done > 0).then(|| words.saturating_sub(words_done) as f64 / words_per_sec);
+    json!({
+        "done": done,
+        "elapsed_secs": elapsed.round(),
+        "words": words,
+        "words_done": words_done,
+        "progress": if words == 0 { 1.0 } else { words_done as f64 / words as f64 },
+        "requests": reqs,
+        "requests_per_sec": reqs as f64 / elapsed.max(1.0),
+        // see `expected_progress`, null with `--follow`
+        "requests_expected": expected_progress().map(|(_, expected)| expected),
+        "keyspace_progress": expected_progress().map(|(fraction, _)| fraction),
+        "found": UUID_COUNTER.load(Ordering::SeqCst),
+        "found_total": UUID_ALL_COUNTER.load(Ordering::SeqCst),
+        "cached": CACHED_COUNTER.load(Ordering::SeqCst),
+        "dropped_batches": FAILED_BATCHES.load(Ordering::SeqCst),
+        // every candidate before this one is answered, see `--skip-keyspace`
+        "keyspace_position": KEYSPACE.current(),
+        "eta_secs": eta.map(f64::round),
+        "latency": crate::latency::json(),
+    })
+}
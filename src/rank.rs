@@ -0,0 +1,53 @@
+use rayon::prelude::*;
+use std::io::{BufWriter, ErrorKind, Write};
+
+use crate::RankArgs;
+use crate::generate::Markov;
+use crate::wordlist::{self, CleanOptions};
+
+// `uuidump rank`, writes the cleaned wordlist to stdout with the names that look most like the
+// training names first. with `--with-scores` every line gets its score, in the format `--scores`
+// reads.
+pub async fn run(args: &RankArgs) -> eyre::Result<()> {
+    if args.order == 0 {
+        eyre::bail!("--order needs to be at least 1");
+    }
+    let training = wordlist::load(&args.train, &CleanOptions::default()).await?;
+    if training.is_empty() {
+        eyre::bail!("{} has no valid names to train on", args.train);
+    }
+    let model = Markov::train(&training, args.order);
+    let words = wordlist::load(&args.wordlist, &CleanOptions::default()).await?;
+    eprintln!(
+        "ranking {} words against {} training names",
+        words.len(),
+        training.len()
+    );
+
+    let with_scores = args.with_scores;
+    tokio::task::spawn_blocking(move || {
+        let mut ranked = words
+            .into_par_iter()
+            .map(|word| (model.score(&word), word))
+            .collect::<Vec<_>>();
+        // stable, so equally likely words stay sorted alphabetically
+        ranked.par_sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        let mut out = BufWriter::new(std::io::stdout().lock());
+        let res = (|| {
+            for (score, word) in ranked {
+                if with_scores {
+                    writeln!(out, "{word}\t{score:.4}")?;
+                } else {
+                    writeln!(out, "{word}")?;
+                }
+            }
+            out.flush()
+        })();
+        match res {
+            // the reader went away, e.g. `| head`
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.into()),
+            _ => eyre::Ok(()),
+        }
+    })
+    .await?
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::open_output;
+use crate::retry::{ErrorClass, RequestError};
+use crate::term::clear_line;
+
+// name of the file inside a `--record`/`--replay` directory
+const FILE: &str = "requests.jsonl";
+
+// what a request was answered with: the response json (the array of profiles for a batch, the
+// profile or null for a single lookup) or the error it failed with
+type Outcome = Result<serde_json::Value, RequestError>;
+
+// the kind of request and the names it was for
+type Key = (Kind, Vec<String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Batch,
+    Single,
+}
+
+impl Kind {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Batch => "batch",
+            Self::Single => "single",
+        }
+    }
+}
+
+// `--record` file, every request appended as a json line as soon as it's answered
+static RECORDING: OnceLock<Mutex<LineWriter<std::fs::File>>> = OnceLock::new();
+
+pub async fn start(dir: &str) -> eyre::Result<()> {
+    let path = Path::new(dir).join(FILE);
+    let file = open_output(&path.to_string_lossy()).await?.into_std().await;
+    let _ = RECORDING.set(Mutex::new(LineWriter::new(file)));
+    Ok(())
+}
+
+pub fn record(kind: Kind, names: &[String], outcome: Result<&impl Serialize, &RequestError>) {
+    let Some(recording) = RECORDING.get() else {
+        return;
+    };
+    let entry = match outcome {
+        Ok(response) => json!({ "kind": kind.name(), "names": names, "response": response }),
+        Err(e) => json!({
+            "kind": kind.name(),
+            "names": names,
+            "error": e.class.name(),
+            "message": e.message,
+        }),
+    };
+    let res = writeln!(recording.lock().unwrap(), "{entry}");
+    if let Err(e) = res {
+        eprintln!("{}warn: failed to write recording: {e}", clear_line());
+    }
+}
+
+// answers requests from a `--record`ing. a request that was made several times (because it was
+// retried) gets the recorded answers in order, and the last one again once they run out, so the
+// retry handling is reproduced too.
+pub struct Replay {
+    answers: Mutex<HashMap<Key, VecDeque<Outcome>>>,
+}
+
+impl Replay {
+    pub async fn load(dir: &str) -> eyre::Result<Self> {
+        let path = Path::new(dir).join(FILE);
+        let text = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| eyre::eyre!("failed to read recording {}: {e}", path.display()))?;
+        let mut answers = HashMap::<_, VecDeque<_>>::new();
+        for (i, line) in text.lines().enumerate() {
+            let (key, outcome) = parse_entry(line).ok_or_else(|| {
+                eyre::eyre!(
+                    "line {} of {} is not a recorded request",
+                    i + 1,
+                    path.display()
+                )
+            })?;
+            answers.entry(key).or_default().push_back(outcome);
+        }
+        Ok(Self {
+            answers: Mutex::new(answers),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.answers
+            .lock()
+            .unwrap()
+            .values()
+            .map(VecDeque::len)
+            .sum()
+    }
+
+    pub fn answer(&self, kind: Kind, names: &[String]) -> Outcome {
+        let mut answers = self.answers.lock().unwrap();
+        let outcome = answers.get_mut(&(kind, names.to_vec())).map(|queue| {
+            if queue.len() > 1 {
+                queue.pop_front()
+            } else {
+                queue.front().map(clone_outcome)
+            }
+            .expect("recorded requests have at least one answer")
+        });
+        drop(answers);
+        outcome.unwrap_or_else(|| {
+            Err(RequestError::new(
+                ErrorClass::Other,
+                format!("{} {names:?} is not in the recording", kind.name()),
+            ))
+        })
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(Key, Outcome)> {
+    let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+    let kind = match entry["kind"].as_str()? {
+        "batch" => Kind::Batch,
+        "single" => Kind::Single,
+        _ => return None,
+    };
+    let names = serde_json::from_value(entry["names"].clone()).ok()?;
+    let outcome = if let Some(class) = entry["error"].as_str() {
+        let class = ErrorClass::ALL.into_iter().find(|c| c.name() == class)?;
+        let message = entry["message"].as_str().unwrap_or_default();
+        Err(RequestError::new(class, message))
+    } else {
+        Ok(entry.get("response")?.clone())
+    };
+    Some(((kind, names), outcome))
+}
+
+fn clone_outcome(outcome: &Outcome) -> Outcome {
+    match outcome {
+        Ok(response) => Ok(response.clone()),
+        Err(e) => Err(RequestError::new(e.class, e.message.clone())),
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::parse_duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Timeout,
+    Connect,
+    RateLimited,
+    ClientError,
+    ServerError,
+    InvalidJson,
+    Other,
+}
+
+impl ErrorClass {
+    pub const ALL: [Self; 7] = [
+        Self::Timeout,
+        Self::Connect,
+        Self::RateLimited,
+        Self::ClientError,
+        Self::ServerError,
+        Self::InvalidJson,
+        Self::Other,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Connect => "connect",
+            Self::RateLimited => "429",
+            Self::ClientError => "4xx",
+            Self::ServerError => "5xx",
+            Self::InvalidJson => "json",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn from_reqwest(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_connect() {
+            Self::Connect
+        } else if e.is_decode() {
+            Self::InvalidJson
+        } else {
+            Self::Other
+        }
+    }
+
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Self::RateLimited
+        } else if status.is_client_error() {
+            Self::ClientError
+        } else if status.is_server_error() {
+            Self::ServerError
+        } else {
+            Self::Other
+        }
+    }
+
+    // retries and base backoff (doubled on every retry) if not overridden with `--retry`
+    const fn default_policy(self) -> RetryPolicy {
+        let (retries, backoff_ms) = match self {
+            Self::Timeout | Self::ServerError => (3, 1000),
+            Self::Connect => (3, 2000),
+            Self::RateLimited => (5, 5000),
+            Self::ClientError => (0, 0),
+            Self::InvalidJson | Self::Other => (1, 500),
+        };
+        RetryPolicy {
+            retries,
+            backoff: Duration::from_millis(backoff_ms),
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestError {
+    pub class: ErrorClass,
+    pub message: String,
+    // from a `retry-after` header, overrides the policy's backoff
+    pub retry_after: Option<Duration>,
+}
+
+impl RequestError {
+    pub fn new(class: ErrorClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    // how long to wait before the given retry (starting at 0)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(Duration::from_mins(5))
+    }
+}
+
+// `CLASS=RETRIES` or `CLASS=RETRIES:BACKOFF` from `--retry`
+#[derive(Debug, Clone, Copy)]
+pub struct RetryOverride(ErrorClass, RetryPolicy);
+
+impl FromStr for RetryOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, policy) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected CLASS=RETRIES[:BACKOFF], got `{s}`"))?;
+        let class = ErrorClass::ALL
+            .into_iter()
+            .find(|c| c.name() == class)
+            .ok_or_else(|| {
+                let names = ErrorClass::ALL.map(ErrorClass::name).join(", ");
+                format!("unknown error class `{class}` (use one of {names})")
+            })?;
+        let (retries, backoff) = match policy.split_once(':') {
+            Some((retries, backoff)) => (retries, Some(parse_duration(backoff)?)),
+            None => (policy, None),
+        };
+        let retries = retries
+            .parse()
+            .map_err(|_| format!("invalid retry count `{retries}`"))?;
+        let backoff = backoff.unwrap_or_else(|| class.default_policy().backoff);
+        Ok(Self(class, RetryPolicy { retries, backoff }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicies([RetryPolicy; ErrorClass::ALL.len()]);
+
+impl RetryPolicies {
+    pub fn new(overrides: &[RetryOverride]) -> Self {
+        let mut policies = ErrorClass::ALL.map(ErrorClass::default_policy);
+        for RetryOverride(class, policy) in overrides {
+            policies[*class as usize] = *policy;
+        }
+        Self(policies)
+    }
+
+    pub const fn get(&self, class: ErrorClass) -> RetryPolicy {
+        self.0[class as usize]
+    }
+}
+
+static ERROR_COUNTERS: [AtomicUsize; ErrorClass::ALL.len()] =
+    [const { AtomicUsize::new(0) }; ErrorClass::ALL.len()];
+
+pub fn count_error(class: ErrorClass) {
+    ERROR_COUNTERS[class as usize].fetch_add(1, Ordering::SeqCst);
+}
+
+// failed requests of every class together
+pub fn error_count() -> usize {
+    ERROR_COUNTERS
+        .iter()
+        .map(|count| count.load(Ordering::SeqCst))
+        .sum()
+}
+
+// `timeout 3, 5xx 12` for every class that happened at least once
+pub fn error_summary() -> Option<String> {
+    let summary = ErrorClass::ALL
+        .into_iter()
+        .filter_map(|class| {
+            let count = ERROR_COUNTERS[class as usize].load(Ordering::SeqCst);
+            (count > 0).then(|| format!("{class} {count}"))
+        })
+        .collect::<Vec<_>>();
+    (!summary.is_empty()).then(|| summary.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_overrides_parse() {
+        let RetryOverride(class, policy) = "429=10:30s".parse().unwrap();
+        assert_eq!(class, ErrorClass::RateLimited);
+        assert_eq!(policy.retries, 10);
+        assert_eq!(policy.backoff, Duration::from_secs(30));
+
+        // the class keeps its own backoff if none is given
+        let RetryOverride(class, policy) = "timeout=0".parse().unwrap();
+        assert_eq!(class, ErrorClass::Timeout);
+        assert_eq!(policy.retries, 0);
+        assert_eq!(policy.backoff, Duration::from_secs(1));
+
+        for bad in ["5xx", "teapot=1", "5xx=many", "5xx=1:soon"] {
+            assert!(bad.parse::<RetryOverride>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn overrides_replace_only_their_class() {
+        let policies = RetryPolicies::new(&["json=4:1s".parse().unwrap()]);
+        assert_eq!(policies.get(ErrorClass::InvalidJson).retries, 4);
+        assert_eq!(policies.get(ErrorClass::Other).retries, 1);
+        assert_eq!(policies.get(ErrorClass::ClientError).retries, 0);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_five_minutes() {
+        let policy = RetryPolicy {
+            retries: 100,
+            backoff: Duration::from_secs(1),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(9), Duration::from_mins(5));
+        assert_eq!(policy.backoff(100), Duration::from_mins(5));
+    }
+
+    #[test]
+    fn statuses_are_classified() {
+        assert_eq!(
+            ErrorClass::from_status(reqwest::StatusCode::TOO_MANY_REQUESTS),
+            ErrorClass::RateLimited
+        );
+        assert_eq!(
+            ErrorClass::from_status(reqwest::StatusCode::NOT_FOUND),
+            ErrorClass::ClientError
+        );
+        assert_eq!(
+            ErrorClass::from_status(reqwest::StatusCode::BAD_GATEWAY),
+            ErrorClass::ServerError
+        );
+    }
+}
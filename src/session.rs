@@ -0,0 +1,67 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use std::time::Duration;
+use uuid::Uuid;
+
+const PROFILE_URL: &str = "https://sessionserver.mojang.com/session/minecraft/profile";
+// the session server doesn't say how long its rate limit lasts
+const RATE_LIMIT_WAIT: Duration = Duration::from_mins(1);
+
+// a profile from the session server, looked up by uuid
+#[derive(Deserialize)]
+pub struct Profile {
+    pub name: String,
+    properties: Vec<Property>,
+}
+
+#[derive(Deserialize)]
+struct Property {
+    name: String,
+    value: String,
+}
+
+// the base64 encoded `textures` property
+#[derive(Deserialize)]
+struct Textures {
+    textures: Urls,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+struct Urls {
+    cape: Option<Texture>,
+}
+
+#[derive(Deserialize)]
+struct Texture {
+    url: String,
+}
+
+// none for uuids without a profile (any more). rate limits are waited out.
+pub async fn profile(client: &reqwest::Client, uuid: Uuid) -> eyre::Result<Option<Profile>> {
+    loop {
+        let res = client
+            .get(format!("{PROFILE_URL}/{}", uuid.simple()))
+            .send()
+            .await?;
+        match res.status() {
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                tokio::time::sleep(RATE_LIMIT_WAIT).await;
+            }
+            reqwest::StatusCode::NO_CONTENT | reqwest::StatusCode::NOT_FOUND => return Ok(None),
+            _ => return Ok(Some(res.error_for_status()?.json().await?)),
+        }
+    }
+}
+
+impl Profile {
+    // the texture url of the player's cape, none if they don't wear one
+    pub fn cape(&self) -> eyre::Result<Option<String>> {
+        let Some(textures) = self.properties.iter().find(|p| p.name == "textures") else {
+            return Ok(None);
+        };
+        let textures: Textures = serde_json::from_slice(&BASE64.decode(&textures.value)?)?;
+        Ok(textures.textures.cape.map(|cape| cape.url))
+    }
+}
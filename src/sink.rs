@@ -0,0 +1,537 @@
+use eyre::WrapErr;
+use serde_json::json;
+use std::fmt::Write as _;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use uuid::Uuid;
+
+use crate::arrow;
+use crate::capes::Capes;
+use crate::clock::{DateTime, now_unix};
+use crate::hypixel::Enricher;
+use crate::msgpack;
+use crate::open_output;
+use crate::s3::{Object, Upload};
+use crate::term::clear_line;
+
+// where found uuids go besides the output file, from `--sink`
+#[derive(Debug, Clone)]
+pub enum SinkSpec {
+    // another text file in the same format as the output
+    File(String),
+    // sqlite database with a `uuids` table, created if missing
+    Sqlite(String),
+    // url every batch of hits is posted to as a json array
+    Webhook(String),
+    // json lines file of what hypixel knows about every hit, needs `--hypixel-key`
+    Hypixel(String),
+    // json lines file of the cape every hit wears, see `--only-caped`
+    Capes(String),
+    // `uuid<tab>name` file sorted by uuid once the run is done, next to a `name<tab>uuid` copy
+    // sorted by name
+    Map(String),
+}
+
+impl FromStr for SinkSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("file", path)) => Ok(Self::File(path.to_string())),
+            Some(("sqlite", path)) => Ok(Self::Sqlite(path.to_string())),
+            Some(("webhook", url)) => Ok(Self::Webhook(url.to_string())),
+            Some(("hypixel", path)) => Ok(Self::Hypixel(path.to_string())),
+            Some(("capes", path)) => Ok(Self::Capes(path.to_string())),
+            Some(("map", path)) => Ok(Self::Map(path.to_string())),
+            _ => Err(format!(
+                "expected file:<path>, sqlite:<path>, webhook:<url>, hypixel:<path>, capes:<path> or map:<path>, got `{s}`"
+            )),
+        }
+    }
+}
+
+// how text sinks write uuids, from `--output-format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    // dashed, one per line
+    Plain,
+    // undashed lowercase, one per line, like the public uuid dumps
+    Matdump,
+    // 16 bytes big-endian each, back to back
+    Raw,
+    // the names as the api spells them instead of the uuids, one per line
+    Names,
+    // a msgpack map of the uuid, the name and when it was found per hit, back to back
+    Msgpack,
+    // an arrow ipc stream with a record batch of hits every time they're written out
+    Arrow,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "matdump" => Ok(Self::Matdump),
+            "raw" => Ok(Self::Raw),
+            "names" => Ok(Self::Names),
+            "msgpack" => Ok(Self::Msgpack),
+            "arrow" => Ok(Self::Arrow),
+            _ => Err(format!(
+                "expected plain, matdump, raw, names, msgpack or arrow, got `{s}`"
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    fn encode(self, uuid: Uuid, name: &str, timestamps: Option<Timestamps>) -> Vec<u8> {
+        let mut line = match self {
+            Self::Plain => uuid.to_string(),
+            Self::Matdump => uuid.simple().to_string(),
+            Self::Raw => return uuid.as_bytes().to_vec(),
+            Self::Names => name.to_string(),
+            Self::Msgpack => return msgpack::record(uuid, name, timestamps),
+            Self::Arrow => unreachable!("arrow output is written in batches"),
+        };
+        if let Some(timestamps) = timestamps {
+            line.push('\t');
+            line.push_str(&timestamps.now());
+        }
+        line.push('\n');
+        line.into_bytes()
+    }
+}
+
+// `--timestamps`, when each hit was found, in a column after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamps {
+    // `2024-05-17T13:37:00Z`
+    Iso,
+    // unix seconds
+    Unix,
+}
+
+impl FromStr for Timestamps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iso" => Ok(Self::Iso),
+            "unix" => Ok(Self::Unix),
+            _ => Err(format!("expected iso or unix, got `{s}`")),
+        }
+    }
+}
+
+impl Timestamps {
+    fn now(self) -> String {
+        let unix = now_unix();
+        match self {
+            Self::Iso => DateTime::from_unix(unix).iso(),
+            Self::Unix => unix.to_string(),
+        }
+    }
+}
+
+// hits are buffered by every sink and only written out on `flush`, which the handler calls
+// whenever it runs out of hits to process
+pub enum Sink {
+    Text(BufWriter<tokio::fs::File>, OutputFormat, Option<Timestamps>),
+    Sqlite {
+        // moved into a blocking task while writing
+        db: Option<rusqlite::Connection>,
+        pending: Vec<(Uuid, String, i64)>,
+    },
+    Webhook {
+        client: reqwest::Client,
+        url: String,
+        pending: Vec<(Uuid, String)>,
+    },
+    Hypixel(Enricher),
+    Capes(Capes),
+    Map(BufWriter<tokio::fs::File>, String),
+    Arrow {
+        file: BufWriter<tokio::fs::File>,
+        pending: Vec<(Uuid, String, u64)>,
+        timestamps: bool,
+    },
+    S3(Upload, OutputFormat, Option<Timestamps>),
+}
+
+impl Sink {
+    // opens a text output for appending, cutting off whatever a crashed run left half-written
+    pub async fn text(
+        path: &str,
+        format: OutputFormat,
+        timestamps: Option<Timestamps>,
+    ) -> eyre::Result<Self> {
+        if timestamps.is_some() && format == OutputFormat::Raw {
+            eyre::bail!("--timestamps adds a column to text output, --output-format raw has none");
+        }
+        // held until the file is closed, so two runs can't interleave their appends. taken before
+        // repairing, the end of a file another run is writing to isn't from a crash.
+        let file = open_output(path).await?.into_std().await;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                eyre::bail!("{path} is already being written to by another uuidump run")
+            }
+            Err(std::fs::TryLockError::Error(e)) => {
+                return Err(e).wrap_err_with(|| format!("failed to lock {path}"));
+            }
+        }
+        let owned = path.to_string();
+        if format == OutputFormat::Arrow {
+            let schema = arrow::schema(timestamps.is_some());
+            let expected = schema.clone();
+            let (cut, fresh) =
+                tokio::task::spawn_blocking(move || arrow::reopen(&owned, &expected))
+                    .await?
+                    .wrap_err_with(|| format!("can't append to {path}"))?;
+            if cut > 0 {
+                eprintln!("warn: dropped {cut} bytes of a truncated batch at the end of {path}");
+            }
+            let mut file = BufWriter::new(tokio::fs::File::from_std(file));
+            if fresh {
+                file.write_all(&schema).await?;
+                file.flush().await?;
+            }
+            return Ok(Self::Arrow {
+                file,
+                pending: vec![],
+                timestamps: timestamps.is_some(),
+            });
+        }
+        let cut = tokio::task::spawn_blocking(move || repair(&owned, format))
+            .await?
+            .wrap_err_with(|| format!("can't append to {path}"))?;
+        if cut > 0 {
+            eprintln!("warn: dropped {cut} bytes of a truncated entry at the end of {path}");
+        }
+        Ok(Self::Text(
+            BufWriter::new(tokio::fs::File::from_std(file)),
+            format,
+            timestamps,
+        ))
+    }
+
+    // an output uploaded to an s3 object, in the same format as a text output
+    pub fn s3(
+        object: Object,
+        format: OutputFormat,
+        timestamps: Option<Timestamps>,
+    ) -> eyre::Result<Self> {
+        if timestamps.is_some() && format == OutputFormat::Raw {
+            eyre::bail!("--timestamps adds a column to text output, --output-format raw has none");
+        }
+        if format == OutputFormat::Arrow {
+            eyre::bail!("--output-format arrow is only written to files");
+        }
+        Ok(Self::S3(Upload::new(object), format, timestamps))
+    }
+
+    pub async fn open(
+        spec: &SinkSpec,
+        format: OutputFormat,
+        timestamps: Option<Timestamps>,
+        timeout: Duration,
+        hypixel_key: Option<&str>,
+        only_caped: bool,
+    ) -> eyre::Result<Self> {
+        Ok(match spec {
+            SinkSpec::File(path) => Self::text(path, format, timestamps).await?,
+            SinkSpec::Sqlite(path) => {
+                let path = path.clone();
+                let db = tokio::task::spawn_blocking(move || {
+                    let db = rusqlite::Connection::open(&path)?;
+                    db.execute_batch(
+                        "CREATE TABLE IF NOT EXISTS uuids (
+                            uuid TEXT PRIMARY KEY,
+                            name TEXT NOT NULL,
+                            found_at INTEGER NOT NULL
+                        )",
+                    )?;
+                    eyre::Ok(db)
+                })
+                .await??;
+                Self::Sqlite {
+                    db: Some(db),
+                    pending: vec![],
+                }
+            }
+            SinkSpec::Webhook(url) => Self::Webhook {
+                client: reqwest::Client::builder().timeout(timeout).build()?,
+                url: url.clone(),
+                pending: vec![],
+            },
+            SinkSpec::Hypixel(path) => {
+                let Some(key) = hypixel_key else {
+                    eyre::bail!("--sink hypixel:{path} needs an api key from --hypixel-key");
+                };
+                Self::Hypixel(Enricher::start(path, key, timeout).await?)
+            }
+            SinkSpec::Capes(path) => Self::Capes(Capes::start(path, only_caped, timeout).await?),
+            SinkSpec::Map(path) => {
+                Self::Map(BufWriter::new(open_output(path).await?), path.clone())
+            }
+        })
+    }
+
+    pub async fn write(&mut self, uuid: Uuid, name: &str) {
+        match self {
+            Self::Text(f, format, timestamps) => f
+                .write_all(&format.encode(uuid, name, *timestamps))
+                .await
+                .expect("failed to write to file"),
+            Self::Sqlite { pending, .. } => {
+                let at = i64::try_from(now_unix()).unwrap_or(i64::MAX);
+                pending.push((uuid, name.to_string(), at));
+            }
+            Self::Webhook { pending, .. } => pending.push((uuid, name.to_string())),
+            Self::Hypixel(enricher) => enricher.push(uuid, name),
+            Self::Capes(capes) => capes.push(uuid, name),
+            Self::Map(f, _) => f
+                .write_all(format!("{uuid}\t{name}\n").as_bytes())
+                .await
+                .expect("failed to write to file"),
+            Self::Arrow { pending, .. } => pending.push((uuid, name.to_string(), now_unix())),
+            Self::S3(upload, format, timestamps) => {
+                upload.push(&format.encode(uuid, name, *timestamps));
+                // a steady stream of hits never leaves the handler idle, parts go out as they fill
+                upload
+                    .flush()
+                    .await
+                    .unwrap_or_else(|e| panic!("failed to upload the output: {e}"));
+            }
+        }
+    }
+
+    pub async fn flush(&mut self) {
+        match self {
+            Self::Text(f, ..) | Self::Map(f, _) => {
+                f.flush().await.expect("failed to write to file");
+            }
+            Self::Sqlite { db, pending } => {
+                if pending.is_empty() {
+                    return;
+                }
+                let rows = std::mem::take(pending);
+                let mut conn = db.take().expect("database is only taken while writing");
+                let (conn, res) = tokio::task::spawn_blocking(move || {
+                    let res = insert_hits(&mut conn, &rows);
+                    (conn, res)
+                })
+                .await
+                .unwrap();
+                *db = Some(conn);
+                res.expect("failed to write to database");
+            }
+            Self::Webhook {
+                client,
+                url,
+                pending,
+            } => {
+                if pending.is_empty() {
+                    return;
+                }
+                let hits = std::mem::take(pending)
+                    .into_iter()
+                    .map(|(uuid, name)| json!({ "uuid": uuid.to_string(), "name": name }))
+                    .collect::<Vec<_>>();
+                // a webhook being down shouldn't end the run, the hits are in the other sinks
+                let res = client
+                    .post(&*url)
+                    .header("content-type", "application/json")
+                    .body(json!(hits).to_string())
+                    .send()
+                    .await;
+                match res.map(reqwest::Response::error_for_status) {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) | Err(e) => {
+                        eprintln!(
+                            "{}warn: failed to post {} hits to {url}: {e}",
+                            clear_line(),
+                            hits.len()
+                        );
+                    }
+                }
+            }
+            Self::Arrow {
+                file,
+                pending,
+                timestamps,
+            } => {
+                if pending.is_empty() {
+                    return;
+                }
+                let batch = arrow::batch(&std::mem::take(pending), *timestamps);
+                file.write_all(&batch)
+                    .await
+                    .expect("failed to write to file");
+                file.flush().await.expect("failed to write to file");
+            }
+            Self::S3(upload, ..) => upload
+                .flush()
+                .await
+                .unwrap_or_else(|e| panic!("failed to upload the output: {e}")),
+            // these write their records as soon as they're looked up
+            Self::Hypixel(_) | Self::Capes(_) => {}
+        }
+    }
+
+    // flushes the sink for good, waiting for anything still running in the background
+    pub async fn close(mut self) {
+        self.flush().await;
+        match self {
+            Self::Hypixel(enricher) => enricher.finish().await,
+            Self::Capes(capes) => capes.finish().await,
+            Self::Map(_, path) => {
+                let res = tokio::task::spawn_blocking(move || sort_map(&path)).await;
+                if let Ok(Err(e)) = res {
+                    eprintln!("warn: failed to sort the map: {e}");
+                }
+            }
+            Self::S3(upload, ..) => upload
+                .finish()
+                .await
+                .unwrap_or_else(|e| panic!("failed to upload the output: {e}")),
+            Self::Arrow { mut file, .. } => {
+                file.write_all(&arrow::END)
+                    .await
+                    .expect("failed to write to file");
+                file.flush().await.expect("failed to write to file");
+            }
+            _ => {}
+        }
+    }
+}
+
+// truncates a partial entry at the end of an existing output, returning how many bytes were cut.
+// every entry is written whole, so anything after the last complete one is from a crash.
+fn repair(path: &str, format: OutputFormat) -> io::Result<u64> {
+    let mut file = match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata()?.len();
+    let keep = if format == OutputFormat::Raw {
+        len - len % 16
+    } else if format == OutputFormat::Msgpack {
+        // records can't be told apart from the end, they're read from the start
+        msgpack::complete_len(&mut io::BufReader::new(&mut file))?
+    } else {
+        // look for the last newline from the end, a chunk at a time
+        let mut end = len;
+        let mut buf = vec![0; 4096];
+        loop {
+            if end == 0 {
+                break 0;
+            }
+            let start = end.saturating_sub(buf.len() as u64);
+            let chunk = &mut buf[..usize::try_from(end - start).unwrap_or(usize::MAX)];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(chunk)?;
+            if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+                break start + i as u64 + 1;
+            }
+            end = start;
+        }
+    };
+    if keep < len {
+        file.set_len(keep)?;
+    }
+    Ok(len - keep)
+}
+
+fn insert_hits(
+    db: &mut rusqlite::Connection,
+    rows: &[(Uuid, String, i64)],
+) -> rusqlite::Result<()> {
+    let tx = db.transaction()?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT OR REPLACE INTO uuids (uuid, name, found_at) VALUES (?1, ?2, ?3)",
+        )?;
+        for (uuid, name, at) in rows {
+            insert.execute((uuid.to_string(), name, at))?;
+        }
+    }
+    tx.commit()
+}
+
+// sorts and deduplicates a finished output in place, for `--sort-output`
+pub async fn sort_output(path: &str, format: OutputFormat) -> eyre::Result<()> {
+    let data = tokio::fs::read(path).await?;
+    let sorted = if format == OutputFormat::Raw {
+        // a torn uuid at the end can't be sorted in anywhere, it's dropped
+        let mut uuids = data.chunks_exact(16).collect::<Vec<_>>();
+        uuids.sort_unstable();
+        uuids.dedup();
+        uuids.concat()
+    } else if format == OutputFormat::Msgpack {
+        // every record starts with the uuid, so this sorts them by it
+        let mut records = msgpack::records(&data)?;
+        records.sort_unstable();
+        records.dedup();
+        records.concat()
+    } else {
+        let text = String::from_utf8(data)?;
+        let mut lines = text.lines().collect::<Vec<_>>();
+        lines.sort_unstable();
+        lines.dedup();
+        let mut sorted = lines.join("\n");
+        if !sorted.is_empty() {
+            sorted.push('\n');
+        }
+        sorted.into_bytes()
+    };
+    // written next to it and renamed over, so the hits survive a crash halfway through
+    let tmp = format!("{path}.sorting");
+    tokio::fs::write(&tmp, sorted).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+// sorts and deduplicates a `map:` sink by uuid in place, and writes the same pairs the other way
+// around sorted by name (case insensitively) next to it, `found.tsv` -> `found.by-name.tsv`
+fn sort_map(path: &str) -> eyre::Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    let mut pairs = text
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect::<Vec<_>>();
+    pairs.sort_unstable();
+    pairs.dedup();
+    let mut by_uuid = String::new();
+    for (uuid, name) in &pairs {
+        let _ = writeln!(by_uuid, "{uuid}\t{name}");
+    }
+    pairs.sort_by_cached_key(|(uuid, name)| (name.to_ascii_lowercase(), *uuid));
+    let mut by_name = String::new();
+    for (uuid, name) in &pairs {
+        let _ = writeln!(by_name, "{name}\t{uuid}");
+    }
+
+    let path = std::path::Path::new(path);
+    let by_name_ext = path.extension().map_or_else(
+        || "by-name".to_string(),
+        |ext| format!("by-name.{}", ext.to_string_lossy()),
+    );
+    let by_name_path = path.with_extension(by_name_ext);
+    // written next to them and renamed over, like `--sort-output`
+    for (path, content) in [(path.to_path_buf(), by_uuid), (by_name_path, by_name)] {
+        let tmp = path.with_extension("sorting");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, &path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::cache::Answer;
+use crate::clock::now_unix;
+use crate::term::clear_line;
+
+// every candidate ever queried with its answer and when it was given, in a sqlite database that
+// outlives the runs. unlike `--cache` nothing is loaded into memory, so it can grow with every run
+// and later runs only query what's new or stale.
+pub struct State {
+    db: Arc<Mutex<rusqlite::Connection>>,
+    hit_ttl: Duration,
+    miss_ttl: Duration,
+}
+
+impl State {
+    pub async fn open(path: &str, hit_ttl: Duration, miss_ttl: Duration) -> eyre::Result<Self> {
+        let path = path.to_string();
+        let db = tokio::task::spawn_blocking(move || {
+            let db = rusqlite::Connection::open(&path)
+                .map_err(|e| eyre::eyre!("failed to open state {path}: {e}"))?;
+            // a crash loses at most the last batches, never the database
+            db.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                PRAGMA synchronous = NORMAL;
+                CREATE TABLE IF NOT EXISTS candidates (
+                    name TEXT PRIMARY KEY,
+                    uuid TEXT,
+                    canonical TEXT,
+                    checked_at INTEGER NOT NULL
+                )",
+            )?;
+            eyre::Ok(db)
+        })
+        .await??;
+        Ok(Self {
+            db: Arc::new(Mutex::new(db)),
+            hit_ttl,
+            miss_ttl,
+        })
+    }
+
+    pub fn len(&self) -> eyre::Result<u64> {
+        let db = self.db.lock().unwrap();
+        let len: i64 = db.query_row("SELECT COUNT(*) FROM candidates", (), |row| row.get(0))?;
+        drop(db);
+        Ok(len.cast_unsigned())
+    }
+
+    // the answer if the candidate was answered within its ttl. sqlite blocks, so the lookup
+    // runs off the producer's thread like the writes in `record`
+    pub async fn get(&self, name: &str) -> Option<Answer> {
+        let name = name.to_ascii_lowercase();
+        let db = self.db.clone();
+        let (hit_ttl, miss_ttl) = (self.hit_ttl, self.miss_ttl);
+        let res = tokio::task::spawn_blocking(move || lookup(&db, &name, hit_ttl, miss_ttl))
+            .await
+            .map_err(eyre::Report::from)
+            .flatten();
+        match res {
+            Ok(answer) => answer,
+            Err(e) => {
+                eprintln!("{}warn: failed to read from the state: {e}", clear_line());
+                None
+            }
+        }
+    }
+
+    // records the answer to a request, every queried name that isn't in `found` wasn't found
+    pub async fn record(&self, queried: &[String], found: &[(Uuid, String)]) {
+        let at = now_unix().cast_signed();
+        let rows = queried
+            .iter()
+            .map(|name| {
+                let hit = found.iter().find(|(_, n)| n.eq_ignore_ascii_case(name));
+                (
+                    name.to_ascii_lowercase(),
+                    hit.map(|(uuid, _)| uuid.to_string()),
+                    hit.map(|(_, canonical)| canonical.clone()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let db = self.db.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let mut db = db.lock().unwrap();
+            let tx = db.transaction()?;
+            {
+                let mut upsert = tx.prepare_cached(
+                    "INSERT INTO candidates (name, uuid, canonical, checked_at)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT (name) DO UPDATE SET
+                        uuid = excluded.uuid,
+                        canonical = excluded.canonical,
+                        checked_at = excluded.checked_at",
+                )?;
+                for (name, uuid, canonical) in rows {
+                    upsert.execute((name, uuid, canonical, at))?;
+                }
+            }
+            tx.commit()?;
+            drop(db);
+            eyre::Ok(())
+        })
+        .await;
+        if let Ok(Err(e)) = res {
+            eprintln!("{}warn: failed to write to the state: {e}", clear_line());
+        }
+    }
+}
+
+fn lookup(
+    db: &Mutex<rusqlite::Connection>,
+    name: &str,
+    hit_ttl: Duration,
+    miss_ttl: Duration,
+) -> eyre::Result<Option<Answer>> {
+    let db = db.lock().unwrap();
+    let mut select =
+        db.prepare_cached("SELECT uuid, canonical, checked_at FROM candidates WHERE name = ?1")?;
+    let row = select
+        .query_row([name], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, i64>(2)?.cast_unsigned(),
+            ))
+        })
+        .map_or_else(
+            |e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            },
+            |row| Ok(Some(row)),
+        )?;
+    drop(select);
+    drop(db);
+    let Some((uuid, canonical, at)) = row else {
+        return Ok(None);
+    };
+    let age = now_unix().saturating_sub(at);
+    Ok(match (uuid, canonical) {
+        (Some(uuid), Some(canonical)) if age <= hit_ttl.as_secs() => {
+            Some(Answer::Found(Uuid::from_str(&uuid)?, canonical))
+        }
+        (None, _) if age <= miss_ttl.as_secs() => Some(Answer::NotFound),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn answers_are_kept_until_their_ttl_runs_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        let path = path.to_str().unwrap();
+        let (hit_ttl, miss_ttl) = (Duration::from_hours(2), Duration::from_mins(1));
+        let state = State::open(path, hit_ttl, miss_ttl).await.unwrap();
+        let queried = ["Notch".to_string(), "jeb_".to_string()];
+        let uuid = Uuid::from_u128(1);
+        state.record(&queried, &[(uuid, "Notch".to_string())]).await;
+        assert_eq!(state.len().unwrap(), 2);
+        assert!(matches!(
+            state.get("NOTCH").await,
+            Some(Answer::Found(u, name)) if u == uuid && name == "Notch"
+        ));
+        assert!(matches!(state.get("jeb_").await, Some(Answer::NotFound)));
+        assert!(state.get("dinnerbone").await.is_none());
+
+        // an hour later the miss is stale and gets queried again, the hit isn't
+        let hour_ago = now_unix().cast_signed() - 3600;
+        state
+            .db
+            .lock()
+            .unwrap()
+            .execute("UPDATE candidates SET checked_at = ?1", [hour_ago])
+            .unwrap();
+        assert!(state.get("notch").await.is_some());
+        assert!(state.get("jeb_").await.is_none());
+
+        // answering again refreshes it, in a later run too
+        state.record(&["jeb_".to_string()], &[]).await;
+        drop(state);
+        let state = State::open(path, hit_ttl, miss_ttl).await.unwrap();
+        assert!(matches!(state.get("jeb_").await, Some(Answer::NotFound)));
+    }
+}
@@ -0,0 +1,159 @@
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+use uuid::{Uuid, Variant};
+
+use crate::wordlist::ALLOWED_CHARS;
+
+// how many invalid lines get printed as examples
+const SHOWN_INVALID: usize = 5;
+
+#[derive(Default)]
+struct Stats {
+    lines: u64,
+    blank: u64,
+    // line number and content of the first few invalid lines
+    invalid: Vec<(u64, String)>,
+    invalid_count: u64,
+    nil: u64,
+    uuids: Vec<u128>,
+    versions: BTreeMap<usize, u64>,
+    variants: BTreeMap<&'static str, u64>,
+    name_lengths: BTreeMap<usize, u64>,
+    uppercase: u64,
+    digits: u64,
+    underscores: u64,
+    // characters that can't be in a minecraft name
+    other_chars: u64,
+}
+
+impl Stats {
+    fn add(&mut self, line: &str) {
+        self.lines += 1;
+        let line = line.trim();
+        if line.is_empty() {
+            self.blank += 1;
+            return;
+        }
+        let (uuid, name) = line
+            .split_once(':')
+            .map_or((line, None), |(uuid, name)| (uuid, Some(name)));
+        let Ok(uuid) = Uuid::from_str(uuid) else {
+            self.invalid_count += 1;
+            if self.invalid.len() < SHOWN_INVALID {
+                self.invalid.push((self.lines, line.to_string()));
+            }
+            return;
+        };
+        if uuid.is_nil() {
+            self.nil += 1;
+        }
+        self.uuids.push(uuid.as_u128());
+        *self.versions.entry(uuid.get_version_num()).or_default() += 1;
+        *self
+            .variants
+            .entry(variant_name(uuid.get_variant()))
+            .or_default() += 1;
+        if let Some(name) = name {
+            self.add_name(name);
+        }
+    }
+
+    fn add_name(&mut self, name: &str) {
+        *self.name_lengths.entry(name.chars().count()).or_default() += 1;
+        self.uppercase += u64::from(name.chars().any(|c| c.is_ascii_uppercase()));
+        self.digits += u64::from(name.chars().any(|c| c.is_ascii_digit()));
+        self.underscores += u64::from(name.contains('_'));
+        self.other_chars += u64::from(name.chars().any(|c| !ALLOWED_CHARS.contains(c)));
+    }
+
+    // sorts the uuids to count them without a hash set, which would need several times the
+    // memory for multi-gb dumps
+    fn duplicates(&mut self) -> u64 {
+        self.uuids.par_sort_unstable();
+        let total = self.uuids.len();
+        self.uuids.dedup();
+        (total - self.uuids.len()) as u64
+    }
+}
+
+const fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::NCS => "ncs",
+        Variant::RFC4122 => "rfc4122",
+        Variant::Microsoft => "microsoft",
+        _ => "future",
+    }
+}
+
+// `uuidump stats <dump>`, prints an overview of a dump to stdout
+pub async fn run(path: &str) -> eyre::Result<()> {
+    let path = path.to_string();
+    let stats = tokio::task::spawn_blocking(move || {
+        let mut stats = Stats::default();
+        for line in BufReader::new(File::open(&path)?).lines() {
+            stats.add(&line?);
+        }
+        eyre::Ok(stats)
+    })
+    .await??;
+    print(stats);
+    Ok(())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print(mut stats: Stats) {
+    let duplicates = stats.duplicates();
+    let valid = stats.uuids.len() as u64 + duplicates;
+    let percent = |n: u64| 100.0 * n as f64 / valid.max(1) as f64;
+
+    println!("lines: {} ({} blank)", stats.lines, stats.blank);
+    println!(
+        "uuids: {valid} valid, {} invalid, {} nil",
+        stats.invalid_count, stats.nil
+    );
+    for (line, content) in &stats.invalid {
+        println!("  invalid at line {line}: {content}");
+    }
+    println!("duplicates: {duplicates} ({} unique)", stats.uuids.len());
+    let versions = stats
+        .versions
+        .iter()
+        .map(|(v, n)| format!("v{v} {n} ({:.1}%)", percent(*n)))
+        .collect::<Vec<_>>();
+    println!("versions: {}", versions.join(", "));
+    let variants = stats
+        .variants
+        .iter()
+        .map(|(v, n)| format!("{v} {n} ({:.1}%)", percent(*n)))
+        .collect::<Vec<_>>();
+    println!("variants: {}", variants.join(", "));
+
+    let names = stats.name_lengths.values().sum::<u64>();
+    if names == 0 {
+        return;
+    }
+    let total_len = stats
+        .name_lengths
+        .iter()
+        .map(|(len, n)| *len as u64 * n)
+        .sum::<u64>();
+    println!(
+        "names: {names}, length {}-{} (avg {:.1})",
+        stats.name_lengths.keys().next().unwrap_or(&0),
+        stats.name_lengths.keys().next_back().unwrap_or(&0),
+        total_len as f64 / names as f64
+    );
+    let lengths = stats
+        .name_lengths
+        .iter()
+        .map(|(len, n)| format!("{len}: {n}"))
+        .collect::<Vec<_>>();
+    println!("  by length: {}", lengths.join(", "));
+    println!(
+        "  {} with uppercase, {} with digits, {} with underscores, {} with invalid characters",
+        stats.uppercase, stats.digits, stats.underscores, stats.other_chars
+    );
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::term::clear_line;
+use crate::{
+    BREAKER, CACHED_COUNTER, CONTROL, DEDUP_SKIPPED, REQ_COUNTER, WORD_COUNTER, error_count,
+    status_line,
+};
+
+// how often `systemctl status` gets the current counters, the watchdog may need it more often
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+// the `NOTIFY_SOCKET` systemd sets for `Type=notify` services, connected once at the start
+#[cfg(unix)]
+static SOCKET: std::sync::OnceLock<std::os::unix::net::UnixDatagram> = std::sync::OnceLock::new();
+// between `ready` and `stopping`, when the watchdog is only fed while requests get answered
+static SCRAPING: AtomicBool = AtomicBool::new(false);
+
+// connects to systemd if it's waiting for notifications, and keeps the status and watchdog
+// updated from then on. not running under systemd isn't an error, nothing is sent then.
+pub fn start() {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(e) = connect(&path.to_string_lossy()) {
+        eprintln!(
+            "warn: failed to connect to systemd at {}: {e}",
+            path.display()
+        );
+        return;
+    }
+    let watchdog = watchdog_interval();
+    let interval = watchdog.map_or(STATUS_INTERVAL, |w| (w / 2).min(STATUS_INTERVAL));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        let mut last = activity();
+        loop {
+            ticker.tick().await;
+            notify(&format!("STATUS={}", status_line()));
+            // a run that stopped making requests without waiting on purpose is hung, systemd
+            // restarts it once the watchdog runs out
+            let now = activity();
+            let alive = !SCRAPING.load(Ordering::SeqCst)
+                || now != last
+                || BREAKER.is_open()
+                || CONTROL.is_paused();
+            last = now;
+            if watchdog.is_some() && alive {
+                notify("WATCHDOG=1");
+            }
+        }
+    });
+}
+
+// the wordlist is loaded and the requests start
+pub fn ready() {
+    SCRAPING.store(true, Ordering::SeqCst);
+    notify("READY=1\nSTATUS=scraping");
+}
+
+// every name was queried, what's left is writing out the results
+pub fn stopping() {
+    SCRAPING.store(false, Ordering::SeqCst);
+    notify("STOPPING=1\nSTATUS=finishing up");
+}
+
+// anything that changes while the run makes progress, including requests that fail
+fn activity() -> usize {
+    REQ_COUNTER.load(Ordering::SeqCst)
+        + error_count()
+        + CACHED_COUNTER.load(Ordering::SeqCst)
+        + DEDUP_SKIPPED.load(Ordering::SeqCst)
+        + WORD_COUNTER.load(Ordering::SeqCst)
+}
+
+// `WatchdogSec=` of the unit, if it's meant for this process
+fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec))
+}
+
+#[cfg(unix)]
+fn connect(path: &str) -> std::io::Result<()> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    // a leading @ is an abstract socket, which only linux has
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        socket.connect_addr(&addr)?;
+        let _ = SOCKET.set(socket);
+        return Ok(());
+    }
+    socket.connect(path)?;
+    let _ = SOCKET.set(socket);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn connect(_path: &str) -> std::io::Result<()> {
+    Err(std::io::Error::other("systemd needs unix sockets"))
+}
+
+fn notify(message: &str) {
+    #[cfg(unix)]
+    if let Some(socket) = SOCKET.get()
+        && let Err(e) = socket.send(message.as_bytes())
+    {
+        eprintln!("{}warn: failed to notify systemd: {e}", clear_line());
+    }
+    #[cfg(not(unix))]
+    let _ = message;
+}
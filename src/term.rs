@@ -0,0 +1,206 @@
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use uuid::Uuid;
+
+// how the status line is shown while running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStyle {
+    // tty if stderr is a terminal, plain otherwise
+    Auto,
+    // redrawn in place every second
+    Tty,
+    // printed as a line of its own every now and then, without escape codes, for docker and ci
+    // logs
+    Plain,
+}
+
+impl FromStr for ProgressStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "tty" => Ok(Self::Tty),
+            "plain" => Ok(Self::Plain),
+            _ => Err(format!("expected auto, tty or plain, got `{s}`")),
+        }
+    }
+}
+
+// where found uuids are printed while running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultStream {
+    Stdout,
+    Stderr,
+    None,
+}
+
+impl FromStr for ResultStream {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            "none" => Ok(Self::None),
+            _ => Err(format!("expected stdout, stderr or none, got `{s}`")),
+        }
+    }
+}
+
+// whether output gets colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    // on terminals, unless `NO_COLOR` is set
+    Auto,
+    // even when piped to a file, for pagers like `less -R`
+    Always,
+    Never,
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!("expected auto, always or never, got `{s}`")),
+        }
+    }
+}
+
+// whether the status line on stderr is redrawn in place, and whether found uuids on stdout share
+// the terminal with it (and can be grayed out). set once before the run starts.
+static TTY: AtomicBool = AtomicBool::new(true);
+static STDOUT_TTY: AtomicBool = AtomicBool::new(false);
+static RESULTS: OnceLock<ResultStream> = OnceLock::new();
+static QUIET: AtomicBool = AtomicBool::new(false);
+// whether colors go to stdout and stderr, decided by `--color`
+static STDOUT_COLOR: AtomicBool = AtomicBool::new(false);
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+
+pub fn init(style: ProgressStyle, results: ResultStream, quiet: bool, color: Color) {
+    QUIET.store(quiet, Ordering::SeqCst);
+    let tty = match style {
+        _ if quiet => false,
+        ProgressStyle::Auto => std::io::stderr().is_terminal(),
+        ProgressStyle::Tty => true,
+        ProgressStyle::Plain => false,
+    };
+    // a console that can't show escape codes gets the plain status line instead of garbage
+    let tty = tty && enable_ansi(Stream::Stderr);
+    TTY.store(tty, Ordering::SeqCst);
+    let stdout_tty = tty && std::io::stdout().is_terminal() && enable_ansi(Stream::Stdout);
+    STDOUT_TTY.store(stdout_tty, Ordering::SeqCst);
+    STDOUT_COLOR.store(colors(color, Stream::Stdout), Ordering::SeqCst);
+    STDERR_COLOR.store(colors(color, Stream::Stderr), Ordering::SeqCst);
+    let _ = RESULTS.set(if quiet { ResultStream::None } else { results });
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+// `NO_COLOR` (https://no-color.org) turns off what `auto` would color, an explicit `always`
+// still wins over it
+fn colors(color: Color, stream: Stream) -> bool {
+    let terminal = match stream {
+        Stream::Stdout => std::io::stdout().is_terminal(),
+        Stream::Stderr => std::io::stderr().is_terminal(),
+    };
+    match color {
+        Color::Always => {
+            // a console is only told to understand escape codes if it is one
+            !terminal || enable_ansi(stream)
+        }
+        Color::Never => false,
+        Color::Auto => {
+            terminal
+                && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && enable_ansi(stream)
+        }
+    }
+}
+
+// the windows console only understands escape codes once virtual terminal processing is turned
+// on, which fails on versions too old to have it (and on handles that aren't a console)
+#[cfg(windows)]
+fn enable_ansi(stream: Stream) -> bool {
+    use std::ffi::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetConsoleMode(handle: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut c_void, mode: u32) -> i32;
+    }
+
+    let handle = match stream {
+        Stream::Stdout => std::io::stdout().as_raw_handle(),
+        Stream::Stderr => std::io::stderr().as_raw_handle(),
+    };
+    let mut mode = 0;
+    // SAFETY: the handle is one of the process' standard handles and stays open, `mode` outlives
+    // the call writing to it
+    unsafe {
+        GetConsoleMode(handle, &raw mut mode) != 0
+            && (mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0)
+    }
+}
+
+// terminals everywhere else understand them as they are
+#[cfg(not(windows))]
+const fn enable_ansi(_stream: Stream) -> bool {
+    true
+}
+
+pub fn is_tty() -> bool {
+    TTY.load(Ordering::SeqCst)
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
+
+// goes in front of anything printed to stderr while running, so it doesn't end up behind the
+// status line
+pub fn clear_line() -> &'static str {
+    if TTY.load(Ordering::SeqCst) {
+        "\x1b[2K\r"
+    } else {
+        ""
+    }
+}
+
+// prints a found uuid to the `--print-results` stream. ignored ones are grayed out where colors
+// are on and marked otherwise.
+pub fn print_result(uuid: Uuid, name: &str, ignored: bool) {
+    let results = RESULTS.get().copied().unwrap_or(ResultStream::Stdout);
+    let (ansi, color) = match results {
+        ResultStream::Stdout => (
+            STDOUT_TTY.load(Ordering::SeqCst),
+            STDOUT_COLOR.load(Ordering::SeqCst),
+        ),
+        ResultStream::Stderr => (is_tty(), STDERR_COLOR.load(Ordering::SeqCst)),
+        ResultStream::None => return,
+    };
+    let clear = if ansi { "\x1b[2K\r" } else { "" };
+    let line = match (ignored, color) {
+        (false, _) => format!("{clear}{uuid}:{name}"),
+        (true, true) => format!("{clear}\x1b[38;5;241m{uuid}:{name}\x1b[0m"),
+        (true, false) => format!("{clear}{uuid}:{name} (ignored)"),
+    };
+    if results == ResultStream::Stderr {
+        eprintln!("{line}");
+    } else {
+        println!("{line}");
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::term::clear_line;
+
+// how many of the most recent requests the error ratio is calculated over
+const WINDOW: usize = 100;
+// don't react to the first few requests, a single early failure would be a 100% error rate
+const MIN_SAMPLES: usize = 20;
+const MIN_DELAY: Duration = Duration::from_millis(50);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+// slows every thread down when the error/429 ratio over the last requests gets too high,
+// and speeds them back up gradually once it drops again
+pub struct Throttle {
+    state: Mutex<State>,
+}
+
+struct State {
+    threshold: f64,
+    window: VecDeque<bool>,
+    failures: usize,
+    delay: Duration,
+}
+
+impl Throttle {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                threshold: 1.0,
+                window: VecDeque::new(),
+                failures: 0,
+                delay: Duration::ZERO,
+            }),
+        }
+    }
+
+    // error ratio above which threads get slowed down. 1 never slows down.
+    pub fn set_threshold(&self, threshold: f64) {
+        self.state.lock().unwrap().threshold = threshold;
+    }
+
+    pub fn record(&self, ok: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.window.push_back(ok);
+        if !ok {
+            state.failures += 1;
+        }
+        if state.window.len() > WINDOW && state.window.pop_front() == Some(false) {
+            state.failures -= 1;
+        }
+        if state.window.len() < MIN_SAMPLES {
+            return;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let ratio = state.failures as f64 / state.window.len() as f64;
+        state.delay = if ratio > state.threshold {
+            state.delay.mul_f64(1.1).clamp(MIN_DELAY, MAX_DELAY)
+        } else {
+            let delay = state.delay.mul_f64(0.95);
+            if delay < MIN_DELAY / 5 {
+                Duration::ZERO
+            } else {
+                delay
+            }
+        };
+    }
+
+    // how long each thread should wait before its next request
+    pub fn delay(&self) -> Duration {
+        self.state.lock().unwrap().delay
+    }
+}
+
+// spaces requests out to hold them to a rate, like the ones through a single proxy
+pub struct Pacer {
+    interval: Duration,
+    // when the next request may be made
+    next: Mutex<Option<Instant>>,
+}
+
+impl Pacer {
+    pub fn new(rps: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / rps),
+            next: Mutex::new(None),
+        }
+    }
+
+    // waits for this request's turn
+    pub async fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = next.map_or_else(Instant::now, |next| next.max(Instant::now()));
+            *next = Some(slot + self.interval);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+const BASE_COOLDOWN: Duration = Duration::from_secs(5);
+pub const MAX_COOLDOWN: Duration = Duration::from_mins(5);
+
+// stops all threads once too many requests in a row failed, then lets a single probe request
+// through after an increasing cool-down until the endpoint answers again
+pub struct Breaker {
+    state: Mutex<BreakerState>,
+}
+
+struct BreakerState {
+    threshold: usize,
+    consecutive: usize,
+    trips: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+impl Breaker {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(BreakerState {
+                threshold: 0,
+                consecutive: 0,
+                trips: 0,
+                open_until: None,
+                probing: false,
+            }),
+        }
+    }
+
+    // consecutive failures after which all threads are paused. 0 never pauses.
+    pub fn set_threshold(&self, threshold: usize) {
+        self.state.lock().unwrap().threshold = threshold;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+
+    // waits until a request may be made. returns true if the caller is the probe.
+    pub async fn wait(&self) -> bool {
+        loop {
+            match self.poll() {
+                Ok(probe) => return probe,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    // ok(is probe) if a request may be made, otherwise how long to wait before asking again
+    fn poll(&self) -> Result<bool, Duration> {
+        let mut state = self.state.lock().unwrap();
+        let Some(open_until) = state.open_until else {
+            return Ok(false);
+        };
+        let now = Instant::now();
+        if now >= open_until && !state.probing {
+            state.probing = true;
+            return Ok(true);
+        }
+        drop(state);
+        Err(open_until
+            .saturating_duration_since(now)
+            .max(Duration::from_millis(100)))
+    }
+
+    pub fn success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive = 0;
+        if state.open_until.take().is_some() {
+            eprintln!("{}endpoint recovered, resuming", clear_line());
+        }
+        state.trips = 0;
+        state.probing = false;
+    }
+
+    // returns true if the failed batch should be retried once the endpoint is back
+    pub fn failure(&self, probe: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive += 1;
+        let tripped = state.open_until.is_some();
+        if tripped && !probe {
+            return true;
+        }
+        if !tripped && (state.threshold == 0 || state.consecutive < state.threshold) {
+            return false;
+        }
+        state.trips += 1;
+        state.probing = false;
+        let cooldown = cooldown(state.trips);
+        state.open_until = Some(Instant::now() + cooldown);
+        let consecutive = state.consecutive;
+        drop(state);
+
+        if tripped {
+            eprintln!(
+                "{}endpoint still down, pausing for {}s",
+                clear_line(),
+                cooldown.as_secs()
+            );
+        } else {
+            eprintln!(
+                "{}{consecutive} requests in a row failed, pausing for {}s",
+                clear_line(),
+                cooldown.as_secs()
+            );
+        }
+        true
+    }
+}
+
+fn cooldown(trips: u32) -> Duration {
+    BASE_COOLDOWN
+        .saturating_mul(2u32.saturating_pow(trips.saturating_sub(1)))
+        .min(MAX_COOLDOWN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttle_slows_down_above_the_threshold_and_recovers() {
+        let throttle = Throttle::new();
+        for _ in 0..MIN_SAMPLES * 2 {
+            throttle.record(false);
+        }
+        // the default threshold of 1 never slows down
+        assert_eq!(throttle.delay(), Duration::ZERO);
+
+        let throttle = Throttle::new();
+        throttle.set_threshold(0.5);
+        for _ in 0..MIN_SAMPLES - 1 {
+            throttle.record(false);
+        }
+        assert_eq!(throttle.delay(), Duration::ZERO);
+        throttle.record(false);
+        assert_eq!(throttle.delay(), MIN_DELAY);
+        for _ in 0..1000 {
+            throttle.record(false);
+        }
+        assert_eq!(throttle.delay(), MAX_DELAY);
+
+        // failures leave the window as it fills with successes, then the delay winds down
+        for _ in 0..WINDOW / 4 {
+            throttle.record(true);
+        }
+        assert_eq!(throttle.delay(), MAX_DELAY);
+        for _ in 0..1000 {
+            throttle.record(true);
+        }
+        assert_eq!(throttle.delay(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn pacer_spaces_requests_out() {
+        let pacer = Pacer::new(100.0);
+        let start = Instant::now();
+        for _ in 0..5 {
+            pacer.wait().await;
+        }
+        // the first request goes right away, each one after that waits 10ms
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn breaker_opens_after_the_threshold_and_lets_one_probe_through() {
+        let breaker = Breaker::new();
+        breaker.set_threshold(3);
+        assert!(!breaker.failure(false));
+        assert!(!breaker.failure(false));
+        assert!(!breaker.is_open());
+        assert!(breaker.failure(false));
+        assert!(breaker.is_open());
+        assert!(breaker.poll().unwrap_err() >= BASE_COOLDOWN.mul_f64(0.9));
+        // batches that fail while it's open get retried once it closes
+        assert!(breaker.failure(false));
+
+        breaker.state.lock().unwrap().open_until = Some(Instant::now());
+        assert_eq!(breaker.poll(), Ok(true));
+        assert!(breaker.poll().is_err());
+        // a failed probe opens it again, for longer
+        assert!(breaker.failure(true));
+        assert_eq!(breaker.state.lock().unwrap().trips, 2);
+
+        breaker.state.lock().unwrap().open_until = Some(Instant::now());
+        assert_eq!(breaker.poll(), Ok(true));
+        breaker.success();
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.poll(), Ok(false));
+        assert!(!breaker.failure(false));
+    }
+
+    #[test]
+    fn breaker_cooldown_doubles_up_to_the_ceiling() {
+        assert_eq!(cooldown(1), BASE_COOLDOWN);
+        assert_eq!(cooldown(2), BASE_COOLDOWN * 2);
+        assert_eq!(cooldown(4), BASE_COOLDOWN * 8);
+        assert_eq!(cooldown(100), MAX_COOLDOWN);
+    }
+
+    #[test]
+    fn breaker_never_opens_without_a_threshold() {
+        let breaker = Breaker::new();
+        for _ in 0..100 {
+            assert!(!breaker.failure(false));
+        }
+        assert!(!breaker.is_open());
+    }
+}
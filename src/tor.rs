@@ -0,0 +1,172 @@
+use std::fmt::Write as _;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::proxy::{self, ProxySpec};
+use crate::retry::ErrorClass;
+use crate::term::clear_line;
+
+// rate limited answers in a row after which tor is asked for new circuits
+const ROTATE_AFTER: usize = 10;
+// tor ignores NEWNYM more often than this anyway
+const MIN_ROTATE_INTERVAL: Duration = Duration::from_secs(10);
+
+// `--tor`, set once the clients are built
+static TOR: OnceLock<Tor> = OnceLock::new();
+
+struct Tor {
+    control: String,
+    password: Option<String>,
+    state: Mutex<State>,
+}
+
+struct State {
+    rate_limited: usize,
+    rotated: Option<Instant>,
+    // a control port that can't be used is only complained about once
+    warned: bool,
+}
+
+// one proxy per request thread, all through tor's socks port. tor puts streams with different
+// socks credentials on different circuits (`IsolateSOCKSAuth`, on by default), so every thread
+// gets its own exit. the run's seed is in the credentials so a second run doesn't share circuits
+// with the first.
+pub async fn proxies(
+    socks: &str,
+    control: &str,
+    password: Option<&str>,
+    threads: usize,
+    seed: u64,
+) -> eyre::Result<Vec<reqwest::Proxy>> {
+    let spec = format!("socks5h://{socks}")
+        .parse::<ProxySpec>()
+        .map_err(|e| eyre::eyre!("invalid --tor-socks: {e}"))?;
+    let route = spec.route().await?;
+    let proxies = (0..threads.max(1))
+        .map(|i| Ok(route.proxy(Some((&format!("uuidump-{seed:x}-{i}"), "isolated")))?))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let _ = TOR.set(Tor {
+        control: control.to_string(),
+        password: password.map(str::to_string),
+        state: Mutex::new(State {
+            rate_limited: 0,
+            rotated: None,
+            warned: false,
+        }),
+    });
+    Ok(proxies)
+}
+
+pub fn answered() {
+    if let Some(tor) = TOR.get() {
+        tor.state.lock().unwrap().rate_limited = 0;
+    }
+}
+
+// the exits got throttled if rate limits keep coming, tor is asked for new circuits then
+pub fn failed(class: ErrorClass) {
+    let Some(tor) = TOR.get() else {
+        return;
+    };
+    if class != ErrorClass::RateLimited {
+        return;
+    }
+    let mut state = tor.state.lock().unwrap();
+    state.rate_limited += 1;
+    if state.rate_limited < ROTATE_AFTER
+        || state
+            .rotated
+            .is_some_and(|at| at.elapsed() < MIN_ROTATE_INTERVAL)
+    {
+        return;
+    }
+    state.rate_limited = 0;
+    state.rotated = Some(Instant::now());
+    drop(state);
+    tokio::spawn(async move {
+        match newnym(&tor.control, tor.password.as_deref()).await {
+            Ok(()) => {
+                eprintln!(
+                    "{}{ROTATE_AFTER} requests in a row were rate limited, switched to new tor circuits",
+                    clear_line()
+                );
+                proxy::reconnect();
+            }
+            Err(e) => {
+                let mut state = tor.state.lock().unwrap();
+                if !state.warned {
+                    state.warned = true;
+                    drop(state);
+                    eprintln!(
+                        "{}warn: failed to get new tor circuits from the control port at {}: {e}",
+                        clear_line(),
+                        tor.control
+                    );
+                }
+            }
+        }
+    });
+}
+
+// authenticates on the control port (with the password, the cookie file or nothing, whichever
+// tor accepts) and sends `SIGNAL NEWNYM`
+async fn newnym(control: &str, password: Option<&str>) -> eyre::Result<()> {
+    let stream = TcpStream::connect(control).await?;
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    write.write_all(b"PROTOCOLINFO 1\r\n").await?;
+    let info = reply(&mut lines).await?;
+    let auth = match password {
+        Some(password) => format!(
+            "\"{}\"",
+            password.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        None if info.contains("NULL") => String::new(),
+        None => {
+            let path = info
+                .split("COOKIEFILE=\"")
+                .nth(1)
+                .and_then(|rest| rest.split('"').next())
+                .ok_or_else(|| eyre::eyre!("tor wants a password, give it with --tor-password"))?;
+            let cookie = tokio::fs::read(path)
+                .await
+                .map_err(|e| eyre::eyre!("failed to read tor's cookie file {path}: {e}"))?;
+            cookie.iter().fold(String::new(), |mut hex, b| {
+                let _ = write!(hex, "{b:02x}");
+                hex
+            })
+        }
+    };
+    write
+        .write_all(format!("AUTHENTICATE {auth}\r\n").as_bytes())
+        .await?;
+    reply(&mut lines).await?;
+    write.write_all(b"SIGNAL NEWNYM\r\n").await?;
+    reply(&mut lines).await?;
+    let _ = write.write_all(b"QUIT\r\n").await;
+    Ok(())
+}
+
+// the lines of one reply up to the final `250 ...`, an error for anything but 250
+async fn reply(
+    lines: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+) -> eyre::Result<String> {
+    let mut reply = String::new();
+    loop {
+        let Some(line) = lines.next_line().await? else {
+            eyre::bail!("tor closed the control connection");
+        };
+        if !line.starts_with("250") {
+            eyre::bail!("tor answered `{line}`");
+        }
+        reply.push_str(&line);
+        reply.push('\n');
+        // `250-` and `250+` continue the reply, `250 ` ends it
+        if line.as_bytes().get(3) == Some(&b' ') {
+            return Ok(reply);
+        }
+    }
+}
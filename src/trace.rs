@@ -0,0 +1,60 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::term::clear_line;
+
+// logs full request/response pairs for a sampled fraction of requests, for debugging mirrors
+// that answer with something unexpected
+struct Tracer {
+    fraction: f64,
+    out: Mutex<Box<dyn Write + Send>>,
+    to_file: bool,
+}
+
+static TRACER: OnceLock<Tracer> = OnceLock::new();
+static SEQ: AtomicU64 = AtomicU64::new(0);
+
+pub fn init(fraction: f64, path: Option<&str>) -> eyre::Result<()> {
+    let (out, to_file): (Box<dyn Write + Send>, bool) = if let Some(path) = path {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        (Box::new(std::io::BufWriter::new(file)), true)
+    } else {
+        (Box::new(std::io::stderr()), false)
+    };
+    let _ = TRACER.set(Tracer {
+        fraction: fraction.clamp(0.0, 1.0),
+        out: Mutex::new(out),
+        to_file,
+    });
+    Ok(())
+}
+
+// whether the next request should be traced. spreads the sampled requests evenly instead of
+// picking them randomly, so a fraction of 0.1 traces exactly every 10th request.
+#[allow(clippy::cast_precision_loss)]
+pub fn sample() -> bool {
+    let Some(tracer) = TRACER.get() else {
+        return false;
+    };
+    let n = SEQ.fetch_add(1, Ordering::Relaxed) as f64;
+    (n * tracer.fraction).floor() < ((n + 1.0) * tracer.fraction).floor()
+}
+
+pub fn log(url: &str, request: &str, status: &str, elapsed: Duration, response: &str) {
+    let Some(tracer) = TRACER.get() else {
+        return;
+    };
+    let clear = if tracer.to_file { "" } else { clear_line() };
+    let mut out = tracer.out.lock().unwrap();
+    let _ = writeln!(
+        out,
+        "{clear}> POST {url}\n> {request}\n< {status} ({}ms)\n< {response}\n",
+        elapsed.as_millis()
+    );
+    let _ = out.flush();
+}
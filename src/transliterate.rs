@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+// how non-ascii letters in the wordlist are turned into name characters, from `--transliterate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    // dropped like any other invalid character
+    None,
+    // accents are stripped (é -> e, ü -> u) and a few letters spelled out (ß -> ss, æ -> ae)
+    Basic,
+    // like basic, but umlauts become a trailing e (ü -> ue), the way german names are spelled
+    // without them
+    German,
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "basic" => Ok(Self::Basic),
+            "german" => Ok(Self::German),
+            _ => Err(format!("expected none, basic or german, got `{s}`")),
+        }
+    }
+}
+
+impl Scheme {
+    pub fn apply(self, word: &str) -> Cow<'_, str> {
+        if self == Self::None || word.is_ascii() {
+            return Cow::Borrowed(word);
+        }
+        let mut out = String::with_capacity(word.len());
+        for c in word.chars() {
+            if let Some(s) = self.spell_out(c) {
+                out.push_str(s);
+            } else {
+                // decomposes é into e and a combining accent, which is then dropped
+                out.extend(c.nfd().filter(|c| !is_combining_mark(*c)));
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    // letters that don't decompose into a base letter and an accent
+    fn spell_out(self, c: char) -> Option<&'static str> {
+        if self == Self::German {
+            match c {
+                'ä' => return Some("ae"),
+                'ö' => return Some("oe"),
+                'ü' => return Some("ue"),
+                'Ä' => return Some("Ae"),
+                'Ö' => return Some("Oe"),
+                'Ü' => return Some("Ue"),
+                _ => {}
+            }
+        }
+        Some(match c {
+            'ß' => "ss",
+            'æ' => "ae",
+            'Æ' => "Ae",
+            'œ' => "oe",
+            'Œ' => "Oe",
+            'ø' => "o",
+            'Ø' => "O",
+            'đ' | 'ð' => "d",
+            'Đ' | 'Ð' => "D",
+            'ł' => "l",
+            'Ł' => "L",
+            'þ' => "th",
+            'Þ' => "Th",
+            'ı' => "i",
+            _ => return None,
+        })
+    }
+}
+
+// substitutions from `--transform-rules`, one `from to` pair per line (`#` starts a comment, a
+// missing `to` deletes `from`). they're applied in order before the scheme, so e.g. `ж zh`
+// romanizes cyrillic and `ß sz` overrides how basic spells it.
+pub struct Rules(Vec<(String, String)>);
+
+impl Rules {
+    pub async fn load(path: &str) -> eyre::Result<Self> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let mut rules = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let from = parts.next().unwrap_or_default();
+            let to = parts.next().unwrap_or_default();
+            if parts.next().is_some() {
+                eyre::bail!("line {} of {path} should be `from to`, got `{line}`", i + 1);
+            }
+            rules.push((from.to_lowercase(), to.to_string()));
+        }
+        Ok(Self(rules))
+    }
+
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    // words end up lowercase anyway, so they're lowercased first and the rules ignore case
+    pub fn apply<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        let mut word = if word.chars().any(char::is_uppercase) {
+            Cow::Owned(word.to_lowercase())
+        } else {
+            Cow::Borrowed(word)
+        };
+        for (from, to) in &self.0 {
+            if word.contains(from.as_str()) {
+                word = Cow::Owned(word.replace(from.as_str(), to));
+            }
+        }
+        word
+    }
+}
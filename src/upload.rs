@@ -0,0 +1,144 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::Cli;
+use crate::term::clear_line;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadMethod {
+    Put,
+    Post,
+}
+
+impl FromStr for UploadMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "put" => Ok(Self::Put),
+            "post" => Ok(Self::Post),
+            _ => Err(format!("expected put or post, got `{s}`")),
+        }
+    }
+}
+
+// `--upload`, sends the output and the files describing the run to a server once the run
+// succeeded, so nothing is lost when the machine it ran on goes away right after
+pub struct Uploader {
+    client: reqwest::Client,
+    url: String,
+    method: reqwest::Method,
+    retries: u32,
+}
+
+impl Uploader {
+    // checked before the run starts, so a bad url doesn't waste a whole run
+    pub fn new(url: &str, args: &Cli) -> eyre::Result<Self> {
+        if crate::s3::is_s3(&args.output_path) {
+            eyre::bail!(
+                "--upload needs a local output, {} is already uploaded",
+                args.output_path
+            );
+        }
+        let parsed = reqwest::Url::parse(&url.replace("{file}", "file"))
+            .map_err(|e| eyre::eyre!("invalid --upload url {url}: {e}"))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            eyre::bail!("--upload only supports http and https urls, got {url}");
+        }
+        Ok(Self {
+            // outputs can be big, the timeout for requests would cut them off
+            client: reqwest::Client::builder()
+                .connect_timeout(args.timeout)
+                .build()?,
+            url: url.to_string(),
+            method: match args.upload_method {
+                UploadMethod::Put => reqwest::Method::PUT,
+                UploadMethod::Post => reqwest::Method::POST,
+            },
+            retries: args.upload_retries,
+        })
+    }
+
+    // uploads every file in `paths` that exists, one after the other
+    pub async fn upload(&self, paths: &[String]) -> eyre::Result<()> {
+        for path in paths {
+            let body = match tokio::fs::read(path).await {
+                Ok(body) => body,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => eyre::bail!("failed to read {path} for uploading: {e}"),
+            };
+            let url = target(&self.url, path);
+            let len = body.len();
+            self.send(&url, body).await?;
+            info!(
+                "{}uploaded {path} ({len} bytes) to {}",
+                clear_line(),
+                redacted(&url)
+            );
+        }
+        Ok(())
+    }
+
+    async fn send(&self, url: &str, body: Vec<u8>) -> eyre::Result<()> {
+        let shown = redacted(url);
+        let mut retries = 0;
+        loop {
+            let res = self
+                .client
+                .request(self.method.clone(), url)
+                .header("content-type", "application/octet-stream")
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(reqwest::Error::without_url);
+            let e = match res {
+                Ok(_) => return Ok(()),
+                // the server won't change its mind about these, unless it's busy
+                Err(e)
+                    if e.status().is_some_and(|s| {
+                        s.is_client_error() && !matches!(s.as_u16(), 408 | 429)
+                    }) =>
+                {
+                    eyre::bail!("failed to upload to {shown}: {e}")
+                }
+                Err(e) => e,
+            };
+            if retries >= self.retries {
+                eyre::bail!("failed to upload to {shown} after {retries} retries: {e}");
+            }
+            retries += 1;
+            eprintln!(
+                "{}warn: failed to upload to {shown}, retrying ({retries}/{}): {e}",
+                clear_line(),
+                self.retries
+            );
+            tokio::time::sleep(Duration::from_secs(1 << retries.min(6))).await;
+        }
+    }
+}
+
+// `{file}` in the url is replaced by the file's name, otherwise the name is appended to it
+#[allow(clippy::literal_string_with_formatting_args)]
+pub fn target(url: &str, path: &str) -> String {
+    let file = Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |f| f.to_string_lossy().into_owned());
+    if url.contains("{file}") {
+        url.replace("{file}", &file)
+    } else {
+        format!("{}/{file}", url.trim_end_matches('/'))
+    }
+}
+
+// the url without its password, for messages
+pub fn redacted(url: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    if url.password().is_some() {
+        let _ = url.set_password(Some("***"));
+    }
+    url.to_string()
+}
@@ -0,0 +1,501 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt};
+
+use crate::transliterate::{Rules, Scheme};
+
+pub const ALLOWED_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz1234567890_";
+// wordlists bigger than this get deduplicated on disk instead of in memory
+pub const EXTERNAL_THRESHOLD: u64 = 1024 * 1024 * 1024;
+// roughly how much memory a single sorted run may take before it gets written out
+const RUN_BYTES: usize = 256 * 1024 * 1024;
+
+// the cleaned, sorted and deduplicated wordlist, either in memory or in a temporary file
+pub enum Words {
+    Memory(Vec<String>),
+    Disk(SortedFile),
+}
+
+impl Words {
+    pub const fn len(&self) -> usize {
+        match self {
+            Self::Memory(words) => words.len(),
+            Self::Disk(file) => file.len,
+        }
+    }
+}
+
+// how wordlist lines are turned into words, from `--allow-legacy-names`, `--transliterate` and
+// `--transform-rules`. the default is what `generate` and `rank` use.
+#[derive(Clone)]
+pub struct CleanOptions {
+    // keeps 1 and 2 character words and dashes, which a few grandfathered accounts have
+    pub legacy_names: bool,
+    pub transliteration: Scheme,
+    pub rules: Option<Arc<Rules>>,
+    // keeps the case words are written in, which offline uuids depend on
+    pub keep_case: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        Self {
+            legacy_names: false,
+            transliteration: Scheme::None,
+            rules: None,
+            keep_case: false,
+        }
+    }
+}
+
+// the transform rules and transliteration are applied if given, then all non-mc-name characters
+// get nuked. anything that isn't a valid name length afterwards is dropped entirely
+pub fn clean(line: &str, options: &CleanOptions) -> Option<String> {
+    let line = split_count(line).0;
+    let legacy = options.legacy_names;
+    let line = options
+        .rules
+        .as_ref()
+        .map_or(Cow::Borrowed(line), |r| r.apply(line));
+    let word = options
+        .transliteration
+        .apply(&line)
+        .chars()
+        .filter(|c| ALLOWED_CHARS.contains(*c) || (legacy && *c == '-'))
+        .collect::<String>();
+    let min_len = if legacy { 1 } else { 3 };
+    (min_len..16).contains(&word.len()).then(|| {
+        if options.keep_case {
+            word
+        } else {
+            word.to_ascii_lowercase()
+        }
+    })
+}
+
+// whether a name an api answered with could be a real one: letters, digits, `_` and the `-` of
+// a few legacy accounts, at most 16 of them. anything else comes from a broken or hostile backend
+pub fn is_name(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.chars().all(|c| ALLOWED_CHARS.contains(c) || c == '-')
+}
+
+// a wordlist line can be `word<tab>count`, with how often the word came up e.g. in a frequency
+// list. lines without a (valid) count are just a word
+fn split_count(line: &str) -> (&str, Option<u64>) {
+    line.rsplit_once('\t')
+        .and_then(|(word, count)| Some((word, Some(count.trim().parse().ok()?))))
+        .unwrap_or((line, None))
+}
+
+pub async fn load(path: &str, options: &CleanOptions) -> eyre::Result<Vec<String>> {
+    Ok(load_counted(path, options).await?.0)
+}
+
+// the wordlist and the counts from its `word<tab>count` lines (summed up for lines that clean to
+// the same word). sorted alphabetically, or by descending count if there are any, with words
+// without a count last.
+pub async fn load_counted(
+    path: &str,
+    options: &CleanOptions,
+) -> eyre::Result<(Vec<String>, HashMap<String, u64>)> {
+    let wordlist_f = tokio::fs::read_to_string(path).await?;
+    let options = options.clone();
+    // cleaning is most of the work for big lists, so it's spread over every core
+    tokio::task::spawn_blocking(move || {
+        let cleaned = wordlist_f
+            .par_lines()
+            .filter_map(|line| Some((clean(line, &options)?, split_count(line).1)))
+            .collect::<Vec<_>>();
+        let mut counts = HashMap::<String, u64>::new();
+        for (word, count) in &cleaned {
+            if let Some(count) = count {
+                *counts.entry(word.clone()).or_default() += count;
+            }
+        }
+        let mut wordlist = cleaned
+            .into_par_iter()
+            .map(|(word, _)| word)
+            .collect::<Vec<_>>();
+        wordlist.par_sort_unstable();
+        wordlist.dedup();
+        if !counts.is_empty() {
+            // stable, so equally common words stay alphabetical
+            wordlist.par_sort_by_key(|word| Reverse(counts.get(word).copied()));
+        }
+        (wordlist, counts)
+    })
+    .await
+    .map_err(Into::into)
+}
+
+// a `-w`, `path` or `path@weight`
+#[derive(Debug, Clone)]
+pub struct WordlistSpec {
+    pub path: String,
+    pub weight: f64,
+}
+
+impl FromStr for WordlistSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // a path that merely contains an `@` stays a path
+        if let Some((path, weight)) = s.rsplit_once('@')
+            && let Ok(weight) = weight.parse::<f64>()
+        {
+            if !(weight.is_finite() && weight > 0.0) {
+                return Err(format!(
+                    "weight of `{path}` has to be above 0, got `{weight}`"
+                ));
+            }
+            return Ok(Self {
+                path: path.to_string(),
+                weight,
+            });
+        }
+        Ok(Self {
+            path: s.to_string(),
+            weight: 1.0,
+        })
+    }
+}
+
+// how several wordlists are combined into one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    // the highest weight first, lists with the same weight in the order they were given
+    Priority,
+    // a share of every list in proportion to its weight, all the way through
+    Interleave,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "priority" => Ok(Self::Priority),
+            "interleave" => Ok(Self::Interleave),
+            _ => Err(format!("expected priority or interleave, got `{s}`")),
+        }
+    }
+}
+
+impl std::fmt::Display for Order {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Priority => "priority",
+            Self::Interleave => "interleave",
+        })
+    }
+}
+
+// every wordlist loaded like `load_counted` and combined in `order`. a word in several lists is
+// only kept where it comes up first, and counts of the same word are summed across lists.
+pub async fn load_mixed(
+    specs: &[WordlistSpec],
+    order: Order,
+    options: &CleanOptions,
+) -> eyre::Result<(Vec<String>, HashMap<String, u64>)> {
+    let mut lists = Vec::with_capacity(specs.len());
+    let mut counts = HashMap::<String, u64>::new();
+    for spec in specs {
+        let (words, list_counts) = load_counted(&spec.path, options).await?;
+        for (word, count) in list_counts {
+            *counts.entry(word).or_default() += count;
+        }
+        lists.push((words, spec.weight));
+    }
+    if let [(words, _)] = lists.as_mut_slice() {
+        return Ok((std::mem::take(words), counts));
+    }
+    Ok((mix(lists, order), counts))
+}
+
+fn mix(mut lists: Vec<(Vec<String>, f64)>, order: Order) -> Vec<String> {
+    let total = lists.iter().map(|(words, _)| words.len()).sum();
+    let mut mixed = Vec::with_capacity(total);
+    let mut seen = HashSet::with_capacity(total);
+    match order {
+        Order::Priority => {
+            // stable, so equal weights keep the order they were given in
+            lists.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            for (words, _) in lists {
+                for word in words {
+                    if seen.insert(word.clone()) {
+                        mixed.push(word);
+                    }
+                }
+            }
+        }
+        Order::Interleave => {
+            // smooth weighted round robin: every list earns its weight each turn and the richest
+            // one hands out its next word, so a 3:1 mix goes a a b a a a b a instead of in blocks
+            let mut lists = lists
+                .into_iter()
+                .map(|(words, weight)| (words.into_iter(), weight, 0.0))
+                .collect::<Vec<_>>();
+            loop {
+                let mut turn = 0.0;
+                let mut richest = None;
+                for (i, (words, weight, credit)) in lists.iter_mut().enumerate() {
+                    if words.len() == 0 {
+                        continue;
+                    }
+                    *credit += *weight;
+                    turn += *weight;
+                    if richest.is_none_or(|(_, best)| *credit > best) {
+                        richest = Some((i, *credit));
+                    }
+                }
+                let Some((i, _)) = richest else {
+                    break;
+                };
+                let (words, _, credit) = &mut lists[i];
+                *credit -= turn;
+                // a word another list already handed out doesn't use up the turn
+                if let Some(word) = words.find(|word| !seen.contains(word)) {
+                    seen.insert(word.clone());
+                    mixed.push(word);
+                }
+            }
+        }
+    }
+    mixed
+}
+
+// `word<tab>score` lines (any whitespace or a comma works too), words are cleaned the same way
+// as the wordlist so they match up
+pub async fn load_scores(path: &str, options: &CleanOptions) -> eyre::Result<HashMap<String, f64>> {
+    let scores_f = tokio::fs::read_to_string(path).await?;
+    let mut scores = HashMap::new();
+    for (i, line) in scores_f.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((word, score)) = line
+            .trim()
+            .rsplit_once(|c: char| c.is_whitespace() || c == ',')
+        else {
+            eyre::bail!("line {} of {path} has no score: {line}", i + 1);
+        };
+        let score = score
+            .parse::<f64>()
+            .map_err(|e| eyre::eyre!("line {} of {path} has an invalid score: {e}", i + 1))?;
+        if let Some(word) = clean(word, options) {
+            scores.insert(word, score);
+        }
+    }
+    Ok(scores)
+}
+
+// highest score first, unscored words last. stable, so ties keep their order
+pub fn sort_by_score(wordlist: &mut [String], scores: &HashMap<String, f64>) {
+    wordlist.par_sort_by(|a, b| {
+        let a = scores.get(a).copied().unwrap_or(f64::NEG_INFINITY);
+        let b = scores.get(b).copied().unwrap_or(f64::NEG_INFINITY);
+        b.total_cmp(&a)
+    });
+}
+
+// shortest first. stable, so words of the same length keep the order they were put in by
+// `--shuffle` or `--scores`
+pub fn sort_by_length(wordlist: &mut [String]) {
+    wordlist.par_sort_by_key(|word| word.chars().count());
+}
+
+// tails a growing wordlist from an offset, like `tail -f`. words are cleaned like the rest of the
+// wordlist and only handed out the first time they're appended.
+pub struct Follower {
+    file: tokio::io::BufReader<tokio::fs::File>,
+    // a line that's still being written
+    partial: Vec<u8>,
+    seen: HashSet<String>,
+    options: CleanOptions,
+}
+
+impl Follower {
+    pub async fn open(path: &str, offset: u64, options: CleanOptions) -> std::io::Result<Self> {
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        Ok(Self {
+            file: tokio::io::BufReader::new(file),
+            partial: vec![],
+            seen: HashSet::new(),
+            options,
+        })
+    }
+
+    // the next new word, or none once the end of the file is reached for now
+    pub async fn next(&mut self) -> std::io::Result<Option<String>> {
+        loop {
+            self.file.read_until(b'\n', &mut self.partial).await?;
+            if !self.partial.ends_with(b"\n") {
+                return Ok(None);
+            }
+            let line = std::mem::take(&mut self.partial);
+            if let Some(word) = clean(&String::from_utf8_lossy(&line), &self.options)
+                && self.seen.insert(word.clone())
+            {
+                return Ok(Some(word));
+            }
+        }
+    }
+}
+
+// a sorted, deduplicated wordlist on disk, removed again once dropped
+pub struct SortedFile {
+    dir: PathBuf,
+    pub path: PathBuf,
+    len: usize,
+}
+
+impl Drop for SortedFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+// external merge sort: cleans the wordlist in chunks that fit in memory, writes each one out
+// sorted and deduplicated, then merges all of them into a single file while dropping duplicates
+// across chunks. blocking, so run it with `spawn_blocking`.
+pub fn external_dedup(
+    path: &str,
+    tmp_dir: &Path,
+    options: &CleanOptions,
+) -> eyre::Result<SortedFile> {
+    let dir = tmp_dir.join(format!("uuidump-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    // constructed right away so the directory gets cleaned up if anything below fails
+    let mut sorted = SortedFile {
+        path: dir.join("wordlist.txt"),
+        dir,
+        len: 0,
+    };
+
+    // raw lines are collected per run and cleaned in parallel once the run is full
+    let reader = BufReader::new(File::open(path)?);
+    let mut runs = vec![];
+    let mut chunk = vec![];
+    let mut chunk_bytes = 0;
+    for line in reader.split(b'\n') {
+        let line = line?;
+        chunk_bytes += line.len() + size_of::<Vec<u8>>();
+        chunk.push(line);
+        if chunk_bytes >= RUN_BYTES {
+            runs.push(write_run(&sorted.dir, runs.len(), &mut chunk, options)?);
+            chunk_bytes = 0;
+        }
+    }
+    if !chunk.is_empty() {
+        runs.push(write_run(&sorted.dir, runs.len(), &mut chunk, options)?);
+    }
+    info!("merging {} sorted runs", runs.len());
+
+    let mut readers = runs
+        .iter()
+        .map(|run| Ok(BufReader::new(File::open(run)?).lines()))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(word) = reader.next() {
+            heap.push(Reverse((word?, i)));
+        }
+    }
+    let mut out = BufWriter::new(File::create(&sorted.path)?);
+    let mut last: Option<String> = None;
+    while let Some(Reverse((word, i))) = heap.pop() {
+        if let Some(next) = readers[i].next() {
+            heap.push(Reverse((next?, i)));
+        }
+        if last.as_ref() == Some(&word) {
+            continue;
+        }
+        writeln!(out, "{word}")?;
+        sorted.len += 1;
+        last = Some(word);
+    }
+    out.flush()?;
+    for run in runs {
+        std::fs::remove_file(run)?;
+    }
+    Ok(sorted)
+}
+
+fn write_run(
+    dir: &Path,
+    n: usize,
+    chunk: &mut Vec<Vec<u8>>,
+    options: &CleanOptions,
+) -> eyre::Result<PathBuf> {
+    let mut words = chunk
+        .par_drain(..)
+        .filter_map(|line| clean(&String::from_utf8_lossy(&line), options))
+        .collect::<Vec<String>>();
+    words.par_sort_unstable();
+    words.dedup();
+    let path = dir.join(format!("run-{n}.txt"));
+    let mut out = BufWriter::new(File::create(&path)?);
+    for word in words {
+        writeln!(out, "{word}")?;
+    }
+    out.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_follows_the_options_it_gets() {
+        let default = CleanOptions::default();
+        assert_eq!(clean("Notch\t12", &default).as_deref(), Some("notch"));
+        assert_eq!(clean("x-y", &default), None);
+        assert_eq!(clean("Jürgen", &default).as_deref(), Some("jrgen"));
+
+        let legacy = CleanOptions {
+            legacy_names: true,
+            ..CleanOptions::default()
+        };
+        assert_eq!(clean("x-y", &legacy).as_deref(), Some("x-y"));
+
+        let cased = CleanOptions {
+            keep_case: true,
+            ..CleanOptions::default()
+        };
+        assert_eq!(clean("Notch", &cased).as_deref(), Some("Notch"));
+
+        let german = CleanOptions {
+            transliteration: Scheme::German,
+            ..CleanOptions::default()
+        };
+        assert_eq!(clean("Jürgen", &german).as_deref(), Some("juergen"));
+        // the other settings weren't touched by any of that
+        assert_eq!(clean("x-y", &default), None);
+    }
+
+    #[tokio::test]
+    async fn lists_are_cleaned_deduplicated_and_ordered_by_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.txt");
+        let lines = "zed\nNotch\t3\nno\njeb_\t5\nnotch\t4\nabc\nzed\n";
+        std::fs::write(&path, lines).unwrap();
+        let (words, counts) = load_counted(path.to_str().unwrap(), &CleanOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(words, ["notch", "jeb_", "abc", "zed"]);
+        assert_eq!(
+            counts,
+            HashMap::from([("notch".into(), 7), ("jeb_".into(), 5)])
+        );
+    }
+}
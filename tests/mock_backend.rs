@@ -0,0 +1,220 @@
+// runs the binary against a local mock of the mowojang api
+
+use serde_json::json;
+use std::path::Path;
+use std::process::Output;
+use tokio::process::Command;
+use wiremock::matchers::{body_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+const JEB: &str = "853c80ef-3c37-49fd-aa49-938b674adae6";
+
+// runs uuidump over `words` against the mock, returning its output and the found uuids
+async fn run(server: &MockServer, words: &[&str], extra: &[&str]) -> (Output, Vec<String>) {
+    let dir = tempfile::tempdir().unwrap();
+    let wordlist = dir.path().join("words.txt");
+    let found = dir.path().join("found.txt");
+    std::fs::write(&wordlist, words.join("\n")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_uuidump"))
+        .arg("-w")
+        .arg(&wordlist)
+        .arg("-o")
+        .arg(&found)
+        .args(["--backend", &server.uri(), "--skip-health-check", "-t", "1"])
+        .args(extra)
+        .output()
+        .await
+        .unwrap();
+    (output, read_found(&found))
+}
+
+fn read_found(path: &Path) -> Vec<String> {
+    let mut found = std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    found.sort();
+    found
+}
+
+fn profiles(pls: &[(&str, &str)]) -> serde_json::Value {
+    pls.iter()
+        .map(|(id, name)| json!({ "id": id, "name": name }))
+        .collect()
+}
+
+#[tokio::test]
+async fn batch_success() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_json(json!(["jeb_", "notch"])))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(profiles(&[(NOTCH, "Notch"), (JEB, "jeb_")])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(&server, &["Notch", "jeb_"], &[]).await;
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(found, [NOTCH, JEB]);
+}
+
+#[tokio::test]
+async fn partial_results() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(profiles(&[(NOTCH, "Notch")])))
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(&server, &["Notch", "jeb_", "nobody123"], &[]).await;
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(found, [NOTCH]);
+}
+
+#[tokio::test]
+async fn rate_limited_then_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(profiles(&[(NOTCH, "Notch")])))
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(&server, &["Notch"], &["--breaker-threshold", "0"]).await;
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(found, [NOTCH]);
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn server_errors_drop_the_batch() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(
+        &server,
+        &["Notch"],
+        &["--retry", "5xx=2:1ms", "--breaker-threshold", "0"],
+    )
+    .await;
+    // completed, but with a dropped batch
+    assert_eq!(output.status.code(), Some(2));
+    assert!(found.is_empty());
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("dropping 1 names of a batch after 2 retries (5xx)"),
+        "{stderr}"
+    );
+}
+
+#[tokio::test]
+async fn garbage_json() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>not json</html>"))
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(
+        &server,
+        &["Notch"],
+        &["--retry", "json=0", "--breaker-threshold", "0"],
+    )
+    .await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(found.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("(json)"), "{stderr}");
+}
+
+#[tokio::test]
+async fn malformed_entry_keeps_the_rest() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": NOTCH, "name": "Notch" },
+            { "id": "not a uuid", "name": "jeb_" },
+        ])))
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(&server, &["Notch", "jeb_"], &[]).await;
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(found, [NOTCH]);
+}
+
+#[tokio::test]
+async fn poisoned_batch_falls_back_to_single_names() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(400))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/notch"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "id": NOTCH, "name": "Notch" })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/nobody123"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/jeb_"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let (output, found) = run(&server, &["Notch", "nobody123", "jeb_"], &[]).await;
+    // jeb_ still failed on its own, so the batch counts as dropped
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(found, [NOTCH]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("dropping jeb_ (5xx)"), "{stderr}");
+    assert!(
+        stderr.contains("dropping 1 names of a batch after 0 retries (4xx)"),
+        "{stderr}"
+    );
+}
+
+#[tokio::test]
+async fn offline_uuids_are_kept_out_of_what_api_runs_share() {
+    // nothing is requested, the mock only has to exist
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("cache");
+    let state = dir.path().join("state.db");
+    for extra in [
+        ["--cache", cache.to_str().unwrap()],
+        ["--state", state.to_str().unwrap()],
+        ["--dedup-server", "http://127.0.0.1:1"],
+    ] {
+        let mut args = vec!["--mode", "offline-uuid"];
+        args.extend(extra);
+        let (output, found) = run(&server, &["Notch"], &args).await;
+        assert!(!output.status.success());
+        assert!(found.is_empty());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("can't be used with --mode offline-uuid"),
+            "{stderr}"
+        );
+    }
+    assert!(!cache.exists() && !state.exists());
+}